@@ -0,0 +1,249 @@
+use crate::error::SharadError;
+use chrono::{Datelike, Local, Timelike};
+use std::collections::HashMap;
+
+/// Builds a zip file in memory. Files are stored without compression, which is what EPUB
+/// requires for its `mimetype` entry and keeps everything readable by any unzip tool.
+/// Campaign bundles use the same format.
+pub struct ZipWriter {
+    data: Vec<u8>,
+    central_directory: Vec<u8>,
+    entries: u16,
+    time: u16,
+    date: u16,
+}
+
+const LOCAL_HEADER: u32 = 0x04034b50;
+const CENTRAL_HEADER: u32 = 0x02014b50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x06054b50;
+/// Zip 2.0, the oldest version that can read stored files in directories.
+const VERSION: u16 = 20;
+/// File names are UTF-8.
+const UTF8_NAMES: u16 = 0x0800;
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB88320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+impl Default for ZipWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ZipWriter {
+    pub fn new() -> Self {
+        let now = Local::now();
+        ZipWriter {
+            data: Vec::new(),
+            central_directory: Vec::new(),
+            entries: 0,
+            time: ((now.hour() << 11) | (now.minute() << 5) | (now.second() / 2)) as u16,
+            date: (((now.year().max(1980) - 1980) as u32) << 9 | now.month() << 5 | now.day())
+                as u16,
+        }
+    }
+
+    /// Adds a file. Fails when the archive would go past what the zip format can hold: 65,535
+    /// files and 4 GiB.
+    pub fn add(&mut self, name: &str, contents: &[u8]) -> Result<(), SharadError> {
+        let too_big = || SharadError::Message(format!("'{}' does not fit in the archive.", name));
+        let entries = self
+            .entries
+            .checked_add(1)
+            .ok_or_else(|| SharadError::Message("An archive holds at most 65,535 files.".into()))?;
+        let offset = u32::try_from(self.data.len()).map_err(|_| too_big())?;
+        let size = u32::try_from(contents.len()).map_err(|_| too_big())?;
+        let name_length = u16::try_from(name.len()).map_err(|_| too_big())?;
+        let crc = crc32(contents);
+        let name = name.as_bytes();
+
+        let data = &mut self.data;
+        data.extend(LOCAL_HEADER.to_le_bytes());
+        data.extend(VERSION.to_le_bytes());
+        data.extend(UTF8_NAMES.to_le_bytes());
+        data.extend(0u16.to_le_bytes()); // Stored
+        data.extend(self.time.to_le_bytes());
+        data.extend(self.date.to_le_bytes());
+        data.extend(crc.to_le_bytes());
+        data.extend(size.to_le_bytes());
+        data.extend(size.to_le_bytes());
+        data.extend(name_length.to_le_bytes());
+        data.extend(0u16.to_le_bytes()); // No extra field
+        data.extend(name);
+        data.extend(contents);
+
+        let central = &mut self.central_directory;
+        central.extend(CENTRAL_HEADER.to_le_bytes());
+        central.extend(VERSION.to_le_bytes());
+        central.extend(VERSION.to_le_bytes());
+        central.extend(UTF8_NAMES.to_le_bytes());
+        central.extend(0u16.to_le_bytes());
+        central.extend(self.time.to_le_bytes());
+        central.extend(self.date.to_le_bytes());
+        central.extend(crc.to_le_bytes());
+        central.extend(size.to_le_bytes());
+        central.extend(size.to_le_bytes());
+        central.extend(name_length.to_le_bytes());
+        central.extend([0u8; 12]); // Extra field, comment, disk, attributes
+        central.extend(offset.to_le_bytes());
+        central.extend(name);
+
+        self.entries = entries;
+        Ok(())
+    }
+
+    /// Returns the finished zip file.
+    pub fn finish(mut self) -> Result<Vec<u8>, SharadError> {
+        let too_big = || SharadError::Message("The archive is over 4 GiB.".into());
+        let offset = u32::try_from(self.data.len()).map_err(|_| too_big())?;
+        let size = u32::try_from(self.central_directory.len()).map_err(|_| too_big())?;
+        // The central directory must start within reach of its 32-bit offset
+        offset.checked_add(size).ok_or_else(too_big)?;
+        self.data.append(&mut self.central_directory);
+        self.data.extend(END_OF_CENTRAL_DIRECTORY.to_le_bytes());
+        self.data.extend([0u8; 4]); // Disk numbers
+        self.data.extend(self.entries.to_le_bytes());
+        self.data.extend(self.entries.to_le_bytes());
+        self.data.extend(size.to_le_bytes());
+        self.data.extend(offset.to_le_bytes());
+        self.data.extend(0u16.to_le_bytes()); // No comment
+        Ok(self.data)
+    }
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+/// Reads the files of a zip written by `ZipWriter`. Compressed files are not supported.
+pub fn read_zip(data: &[u8]) -> Result<HashMap<String, Vec<u8>>, SharadError> {
+    let invalid = || SharadError::Message("Not a valid archive.".into());
+
+    // The end of central directory record is the last thing in the file, before any comment
+    let end = (0..data.len().saturating_sub(21))
+        .rev()
+        .find(|&offset| read_u32(data, offset) == Some(END_OF_CENTRAL_DIRECTORY))
+        .ok_or_else(invalid)?;
+    let entries = read_u16(data, end + 10).ok_or_else(invalid)?;
+    let mut offset = read_u32(data, end + 16).ok_or_else(invalid)? as usize;
+
+    let mut files = HashMap::new();
+    for _ in 0..entries {
+        if read_u32(data, offset) != Some(CENTRAL_HEADER) {
+            return Err(invalid());
+        }
+        let method = read_u16(data, offset + 10).ok_or_else(invalid)?;
+        let crc = read_u32(data, offset + 16).ok_or_else(invalid)?;
+        let size = read_u32(data, offset + 20).ok_or_else(invalid)? as usize;
+        let name_length = read_u16(data, offset + 28).ok_or_else(invalid)? as usize;
+        let extra_length = read_u16(data, offset + 30).ok_or_else(invalid)? as usize;
+        let comment_length = read_u16(data, offset + 32).ok_or_else(invalid)? as usize;
+        let header = read_u32(data, offset + 42).ok_or_else(invalid)? as usize;
+        let name = data
+            .get(offset + 46..offset + 46 + name_length)
+            .ok_or_else(invalid)?;
+        let name = String::from_utf8_lossy(name).to_string();
+        offset += 46 + name_length + extra_length + comment_length;
+
+        if method != 0 {
+            return Err(SharadError::Message(format!(
+                "'{}' is compressed, which this archive reader does not support.",
+                name
+            )));
+        }
+        if read_u32(data, header) != Some(LOCAL_HEADER) {
+            return Err(invalid());
+        }
+        let start = header
+            + 30
+            + read_u16(data, header + 26).ok_or_else(invalid)? as usize
+            + read_u16(data, header + 28).ok_or_else(invalid)? as usize;
+        let contents = data.get(start..start + size).ok_or_else(invalid)?;
+        if crc32(contents) != crc {
+            return Err(SharadError::Message(format!("'{}' is corrupted.", name)));
+        }
+        files.insert(name, contents.to_vec());
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc32_matches_known_values() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+        assert_eq!(
+            crc32(b"The quick brown fox jumps over the lazy dog"),
+            0x414FA339
+        );
+    }
+
+    #[test]
+    fn round_trips_files() {
+        let mut zip = ZipWriter::new();
+        zip.add("mimetype", b"application/epub+zip").unwrap();
+        zip.add("media/portrait.png", &[0, 159, 146, 150, 255])
+            .unwrap();
+        zip.add("empty.txt", b"").unwrap();
+        zip.add("notes/café.txt", "Déjà vu".as_bytes()).unwrap();
+        let files = read_zip(&zip.finish().unwrap()).unwrap();
+
+        assert_eq!(files.len(), 4);
+        assert_eq!(files["mimetype"], b"application/epub+zip");
+        assert_eq!(files["media/portrait.png"], [0, 159, 146, 150, 255]);
+        assert!(files["empty.txt"].is_empty());
+        assert_eq!(files["notes/café.txt"], "Déjà vu".as_bytes());
+    }
+
+    #[test]
+    fn rejects_corrupted_files() {
+        let mut zip = ZipWriter::new();
+        zip.add("save.json", b"{\"version\":3}").unwrap();
+        let mut data = zip.finish().unwrap();
+        let position = data.windows(3).position(|window| window == b"ver").unwrap();
+        data[position] = b'X';
+        assert!(read_zip(&data).is_err());
+        assert!(read_zip(b"not a zip").is_err());
+    }
+
+    #[test]
+    fn rejects_files_past_the_format_limits() {
+        let mut zip = ZipWriter::new();
+        zip.entries = u16::MAX - 1;
+        zip.add("last.txt", b"fits").unwrap();
+        assert!(zip.add("one-too-many.txt", b"").is_err());
+
+        // A file that does not fit is left out, and the archive stays whole
+        let mut zip = ZipWriter::new();
+        zip.add("before.txt", b"ok").unwrap();
+        assert!(zip
+            .add(&"n".repeat(usize::from(u16::MAX) + 1), b"")
+            .is_err());
+        zip.add("after.txt", b"ok").unwrap();
+        let files = read_zip(&zip.finish().unwrap()).unwrap();
+        assert_eq!(files.len(), 2);
+    }
+}
@@ -1,68 +1,56 @@
 use crate::audio::{generate_and_play_audio, record_and_transcribe_audio};
+use crate::cartridge::Cartridge;
+use crate::clock::{GameClock, DEFAULT_START};
+use crate::commands::handle_command;
+use crate::consistency::{
+    apply_changes, check_turn, revision_request, TurnStart, MAX_REVISIONS,
+    STATE_CHANGES_INSTRUCTIONS,
+};
 use crate::display::Display;
 use crate::error::SharadError;
-use crate::image::{generate_character_image, Appearance, CharacterInfo};
+use crate::game_state::GameState;
+use crate::intent::PROPOSED_TEST_INSTRUCTIONS;
+use crate::logging::timed;
 use crate::menu::{choose_assistant, load_game_menu};
-use crate::settings::load_settings;
-use crate::utils::{correct_input, open_image, shadowrun_dice_roll};
+use crate::save::{save_path, validate_name, Save};
+use crate::settings::{load_settings, Settings};
+use crate::tools::{game_master_tools, handle_tool_call};
+use crate::transcript::{self, Role, TranscriptEntry};
+use crate::utils::correct_input;
 use async_openai::{
     config::OpenAIConfig,
     types::{
-        AssistantTools, AssistantToolsFunction, CreateMessageRequestArgs, CreateRunRequestArgs,
-        CreateThreadRequestArgs, FunctionObject, ListAssistantsResponse, MessageContent,
-        MessageObject, MessageRole, RunObject, RunStatus, SubmitToolOutputsRunRequest,
-        ToolsOutputs,
+        AssistantTools, CreateMessageRequestArgs, CreateRunRequestArgs, CreateThreadRequestArgs,
+        ListAssistantsResponse, MessageContent, MessageObject, MessageRole, RunObject, RunStatus,
+        SubmitToolOutputsRunRequest, ToolsOutputs,
     },
     Audio, Client,
 };
 use crossterm::style::Color;
-use serde::{Deserialize, Serialize};
+use serde::Serialize;
 use serde_json::json;
 use serde_json::Value;
-use std::fs::{self, File};
 use std::future::Future;
-use std::io::Write;
-use std::path::Path;
 use std::pin::Pin;
-use std::sync::Arc;
-use tokio::spawn;
-use tokio::sync::Mutex;
+use std::time::Instant;
 use tokio::time::Duration;
-
-pub const SAVE_DIR: &str = "./data/logs/saves/";
+use tracing::{error, info, instrument, warn};
 
 #[derive(Serialize)]
 struct ListAssistantsQuery {}
 
-#[derive(Serialize, Deserialize)]
-pub struct Save {
-    pub assistant_id: String,
-    pub thread_id: String,
-}
-
-pub async fn save_conversation(
-    assistant_id: &str,
-    thread_id: &str,
-    display: &mut Display,
-) -> Result<(), SharadError> {
+pub async fn save_conversation(save: &mut Save, display: &mut Display) -> Result<(), SharadError> {
     let save_name = loop {
         match display.get_user_input("Enter a name for the save file:")? {
-            Some(name) if !name.trim().is_empty() => break name,
-            Some(_) => display.print_wrapped(
-                "Save name cannot be empty. Please try again.",
-                Color::Yellow,
-            ),
+            Some(name) => match validate_name(&name) {
+                Ok(()) => break name.trim().to_string(),
+                Err(e) => display.print_wrapped(&format!("{} Please try again.", e), Color::Yellow),
+            },
             None => return Ok(()), // User pressed Esc, cancel saving
         }
     };
 
-    let save_dir = Path::new(SAVE_DIR);
-    if !save_dir.exists() {
-        fs::create_dir_all(save_dir).map_err(SharadError::Io)?;
-    }
-
-    let save_file = save_dir.join(format!("{}.json", save_name));
-    if save_file.exists() {
+    if save_path(&save_name).exists() {
         let confirm = display
             .get_user_input("A save file with this name already exists. Overwrite? (y/n)")?
             .map(|s| s.to_lowercase());
@@ -75,16 +63,8 @@ pub async fn save_conversation(
         }
     }
 
-    let save = Save {
-        assistant_id: assistant_id.to_string(),
-        thread_id: thread_id.to_string(),
-    };
-
-    let json = serde_json::to_string(&save).map_err(SharadError::SerdeJson)?;
-
-    File::create(&save_file)
-        .and_then(|mut file| file.write_all(json.as_bytes()))
-        .map_err(SharadError::Io)?;
+    save.name = Some(save_name.clone());
+    save.write()?;
 
     display.print_wrapped(
         &format!("Game saved successfully as '{}'.", save_name),
@@ -114,14 +94,10 @@ pub async fn list_assistants() -> Result<Vec<(String, String)>, SharadError> {
     Ok(assistants)
 }
 
-pub async fn run_conversation(
-    log_file: &mut File,
-    is_new_game: bool,
-    display: &mut Display,
-) -> Result<(), SharadError> {
+pub async fn run_conversation(is_new_game: bool, display: &mut Display) -> Result<(), SharadError> {
     let client = Client::new();
     let language = load_settings()?.language;
-    let (assistant_id, thread_id) = if is_new_game {
+    let mut save = if is_new_game {
         let assistants = list_assistants().await?;
         if assistants.is_empty() {
             display.print_wrapped("No game cartridge available.", Color::Red);
@@ -129,29 +105,50 @@ pub async fn run_conversation(
         }
 
         match choose_assistant(assistants, display).await? {
-            Some(assistant_id) => {
+            Some((assistant_id, assistant_name)) => {
+                let cartridge = Cartridge::load(&assistant_name);
+                let rules = cartridge.rule_system.rules();
+                let Some(character) = rules.create_character(display).await? else {
+                    display.print_wrapped("Character creation cancelled.", Color::Yellow);
+                    return Ok(());
+                };
+
                 let thread = client
                     .threads()
                     .create(CreateThreadRequestArgs::default().build()?)
                     .await?;
 
-                // For a new game, send an initial message
+                // For a new game, send an initial message with the finished character sheet
+                let initial_content = format!("You are the Game Master of a Role Playing Game. Start by welcoming the player to the game world and introduce their character, whose sheet follows, in an opening scene that fits their background and motivations. Note that the player is considered a beginner in this world until they have gained significant experience. Write your response in valid JSON within a \"narration\" tag. Always write in the following language: {}\n\nThe game uses the {} rules. Character sheets follow this schema: {}\n\nCharacter sheet: {}", language, rules.name(), rules.character_schema(), character.to_json());
                 let initial_message = CreateMessageRequestArgs::default()
                     .role(MessageRole::User)
-                    .content(format!("You are the Game Master of a Role Playing Game. Start by welcoming the player to the game world and ask them to describe their character. The description should include the character's name, background, and motivations. Note that the player is considered a beginner in this world until they have gained significant experience. Write your response in valid JSON within a \"narration\" tag. Always write in the following language: {}", language))
+                    .content(initial_content.clone())
                     .build()?;
                 display.print_debug(
                     &format!("Debug: Initial message: {:?}", initial_message.content),
                     Color::Magenta,
                 );
-                client
+                let message = client
                     .threads()
                     .messages(&thread.id)
                     .create(initial_message)
                     .await?;
-
-                let _ = save_conversation(&assistant_id, &thread.id, display).await;
-                (assistant_id, thread.id)
+                transcript::append(&thread.id, &message.id, Role::User, &initial_content)?;
+
+                let mut save = Save::new(
+                    assistant_id,
+                    thread.id,
+                    GameState {
+                        clock: GameClock::new(
+                            cartridge.start_date.as_deref().unwrap_or(DEFAULT_START),
+                        ),
+                        cartridge,
+                        character: Some(character),
+                        ..GameState::default()
+                    },
+                );
+                let _ = save_conversation(&mut save, display).await;
+                save
             }
             None => {
                 // User chose to return to the main menu
@@ -159,16 +156,13 @@ pub async fn run_conversation(
             }
         }
     } else {
-        let save = load_conversation_from_file(display).await?;
-        (save.assistant_id, save.thread_id)
+        load_conversation_from_file(display).await?
     };
 
-    let response =
-        run_conversation_with_save(log_file, &assistant_id, &thread_id, is_new_game, display)
-            .await?;
+    let response = run_conversation_with_save(&mut save, is_new_game, display).await?;
     let json_response = json!({
-        "assistant_id": assistant_id,
-        "thread_id": thread_id,
+        "assistant_id": save.assistant_id,
+        "thread_id": save.thread_id,
         "response": response,
     });
 
@@ -180,152 +174,88 @@ pub async fn run_conversation(
 }
 
 pub async fn run_conversation_with_save(
-    log_file: &mut File,
-    assistant_id: &str,
-    thread_id: &str,
+    save: &mut Save,
     is_new_game: bool,
     display: &mut Display,
 ) -> Result<Value, SharadError> {
     let client = Client::new();
     let audio = Audio::new(&client);
 
-    let _request = CreateRunRequestArgs::default()
-    .assistant_id(assistant_id)
-    .tools(vec![AssistantTools::Function(AssistantToolsFunction {
-        function: FunctionObject {
-            name: "generate_character_image".to_string(),
-            description: Some("Generate a character image based on the provided details".to_string()),
-            parameters: Some(json!({
-                "type": "object",
-                "properties": {
-                    "name": {
-                        "type": "string",
-                        "description": "The name of the character"
-                    },
-                    "appearance": {
-                        "type": "object",
-                        "description": "Details about the character's physical appearance in English",
-                        "properties": {
-                            "gender": {
-                                "type": "string",
-                                "description": "The character's gender in English"
-                            },
-                            "age": {
-                                "type": "string",
-                                "description": "The character's approximate age in English"
-                            },
-                            "height": {
-                                "type": "string",
-                                "description": "The character's height in English"
-                            },
-                            "build": {
-                                "type": "string",
-                                "description": "The character's body type in English"
-                            },
-                            "hair": {
-                                "type": "string",
-                                "description": "The character's hair color and style in English"
-                            },
-                            "eyes": {
-                                "type": "string",
-                                "description": "The character's eye color in English"
-                            },
-                            "skin": {
-                                "type": "string",
-                                "description": "The character's skin tone in English"
-                            }
-                        }
-                    },
-                    "distinctive_signs": {
-                        "type": "array",
-                        "description": "List of distinctive signs or features in English",
-                        "items": {
-                            "type": "string"
-                        }
-                    },
-                    "accessories": {
-                        "type": "array",
-                        "description": "List of accessories worn by the character in English",
-                        "items": {
-                            "type": "string"
-                        }
-                    },
-                    "location": {
-                        "type": "string",
-                        "description": "The specific location where the character is situated in English"
-                    },
-                    "ambiance": {
-                        "type": "string",
-                        "description": "The mood or atmosphere of the scene in English"
-                    },
-                    "environment": {
-                        "type": "string",
-                        "description": "The surrounding environment or setting in English"
-                    },
-                    "image_generation_prompt": {
-                        "type": "string",
-                        "description": "A detailed prompt for generating the character image on Dall-E following content Policy rules, in English"
-                    }
-                },
-                "required": ["name", "appearance", "location", "environment", "image_generation_prompt"],
-            })),
-        },
-    })])
-    .build()?;
+    let assistant = client.assistants().retrieve(&save.assistant_id).await?;
+    let tools = game_master_tools(assistant.tools, save.game_state.rules());
+    save.start_session();
 
     if is_new_game {
-        handle_new_game(&client, thread_id, assistant_id, log_file, display, &audio).await?;
+        handle_new_game(&client, save, &tools, display, &audio).await?;
     } else {
-        display_previous_conversation(&client, thread_id, display).await?;
+        rewind_thread(&client, save, display).await?;
+        display_previous_conversation(&client, &save.thread_id, display).await?;
+        if let Some(recap) = save.game_state.campaign_recap() {
+            display.print_header("Campaign so far");
+            display.print_wrapped(&recap, Color::Cyan);
+            display.print_separator(Color::Yellow);
+        }
     }
 
-    main_conversation_loop(&client, thread_id, assistant_id, log_file, display, &audio).await?;
+    main_conversation_loop(&client, save, &tools, display, &audio).await?;
 
     display.print_footer("Thank you for playing!");
-    writeln!(log_file, "Conversation ended.")?;
-    log_file.sync_all()?;
+    info!(thread_id = %save.thread_id, turns = save.metadata.turns, "conversation ended");
 
     // Serialize the final state to JSON
     let final_state = json!({
         "status": "Conversation ended",
-        "assistant_id": assistant_id,
-        "thread_id": thread_id,
+        "assistant_id": save.assistant_id,
+        "thread_id": save.thread_id,
     });
 
     Ok(final_state)
 }
 
+#[instrument(name = "turn", skip_all, fields(turn = 0, thread_id = %save.thread_id), err)]
 async fn handle_new_game(
     client: &Client<OpenAIConfig>,
-    thread_id: &str,
-    assistant_id: &str,
-    log_file: &mut File,
+    save: &mut Save,
+    tools: &[AssistantTools],
     display: &mut Display,
     audio: &Audio<'_, OpenAIConfig>,
 ) -> Result<(), SharadError> {
     display.print_header("Welcome to the Adventure");
 
-    let _run = create_and_wait_for_run(client, thread_id, assistant_id, display).await?;
+    run_until_complete(client, save, tools, display).await?;
 
     let messages = client
         .threads()
-        .messages(thread_id)
+        .messages(&save.thread_id)
         .list(&[("limit", "1")])
         .await?;
 
     if let Some(latest_message) = messages.data.first() {
+        save.last_message_id = Some(latest_message.id.clone());
         if let Some(MessageContent::Text(text_content)) = latest_message.content.first() {
             let response_text = &text_content.text.value;
-            log_and_display_message(log_file, response_text, "Game Master", display)?;
+            transcript::append(
+                &save.thread_id,
+                &latest_message.id,
+                Role::Assistant,
+                response_text,
+            )?;
+            log_and_display_message(response_text, "Game Master", display)?;
             // Parse the JSON response to extract the narration for audio
             let json_response: Value = serde_json::from_str(response_text)?;
 
             if let Some(narration) = json_response.get("narration") {
-                generate_and_play_audio(audio, narration.as_str().unwrap_or(""), "Game Master")
-                    .await?;
+                save.record_turn(narration.as_str().unwrap_or(""));
+                if let Some(audio_file) =
+                    generate_and_play_audio(audio, narration.as_str().unwrap_or(""), "Game Master")
+                        .await?
+                {
+                    transcript::attach_audio(&save.thread_id, &audio_file)?;
+                }
             }
         }
     }
+    save.write()?;
 
     Ok(())
 }
@@ -338,6 +268,10 @@ async fn display_previous_conversation(
     display.print_header("Welcome back to the Adventure");
 
     let all_messages = fetch_all_messages(client, thread_id).await?;
+    // Games started before transcripts were kept get theirs from the server
+    if !transcript::exists(thread_id) {
+        transcript::write(thread_id, &transcript_entries(&all_messages))?;
+    }
 
     display.print_wrapped("Previous conversation:", Color::Yellow);
     for message in &all_messages[1..] {
@@ -351,258 +285,264 @@ async fn display_previous_conversation(
 
 async fn main_conversation_loop(
     client: &Client<OpenAIConfig>,
-    thread_id: &str,
-    assistant_id: &str,
-    log_file: &mut File,
+    save: &mut Save,
+    tools: &[AssistantTools],
     display: &mut Display,
     audio: &Audio<'_, OpenAIConfig>,
 ) -> Result<(), SharadError> {
-    let pending_tool_outputs = Arc::new(Mutex::new(Vec::new()));
-
+    let settings = load_settings()?;
     loop {
         display.print_debug("Debug: Waiting for user input", Color::Magenta);
         let user_input = get_user_input(display).await?;
         if user_input.trim().eq_ignore_ascii_case("exit") {
             break;
         }
+        if let Some(command) = user_input.trim().strip_prefix('/') {
+            handle_command(command, save, display).await?;
+            continue;
+        }
 
-        // Create the JSON structure
-        let message_json = serde_json::json!({
-            "instructions": "Act as a professional Game Master in a role-playing game. Evaluate the probability of success for each intended player action and roll the dice when pertinent. If an action falls outside the player's skills and capabilities, make them fail and face the consequences, which could include death. Allow the player to attempt one action at a time without providing choices. Do not allow the player to summon anything that was not previously introduced unless it is perfectly innocuous. For actions involving multiple steps or failure points, require the player to choose a course of action at each step. Write your reasoning and the results of the dice roll in a JSON \"reasoning\" tag and narrate the results in a JSON \"narration\" tag. Present one action at a time before prompting the player for their next action. Do not let the action stale, but keep things going.",
-            "player_action": user_input
-        });
+        play_turn(client, save, tools, display, audio, &settings, &user_input).await?;
+    }
 
-        // Convert the JSON to a string
-        let user_prompt = serde_json::to_string(&message_json)?;
+    save.game_state.collect_portraits();
+    save.write()?;
+    autosave(save, &settings, display)?;
 
-        display.print_debug(
-            &format!("Debug: Sending user message: {}", user_prompt),
-            Color::Magenta,
-        );
-        display.print_wrapped(&user_input, Color::Blue);
-        send_user_message(client, thread_id, &user_prompt).await?;
+    Ok(())
+}
 
-        // Ensure there is no active run before creating a new one
-        loop {
-            let runs = client
-                .threads()
-                .runs(thread_id)
-                .list(&[("limit", "1")])
-                .await?;
-            if runs.data.is_empty() || runs.data[0].status == RunStatus::Completed {
-                break;
+/// Plays one turn: sends the player's action, answers the Game Master's tool calls and
+/// narrates the reply.
+#[instrument(
+    name = "turn",
+    skip_all,
+    fields(turn = save.metadata.turns + 1, thread_id = %save.thread_id),
+    err
+)]
+async fn play_turn(
+    client: &Client<OpenAIConfig>,
+    save: &mut Save,
+    tools: &[AssistantTools],
+    display: &mut Display,
+    audio: &Audio<'_, OpenAIConfig>,
+    settings: &Settings,
+    user_input: &str,
+) -> Result<(), SharadError> {
+    // Create the JSON structure
+    let mut message_json = serde_json::json!({
+        "instructions": format!("Act as a professional Game Master in a role-playing game. Evaluate the probability of success for each intended player action and roll the dice when pertinent. If an action falls outside the player's skills and capabilities, make them fail and face the consequences, which could include death. Allow the player to attempt one action at a time without providing choices. Do not allow the player to summon anything that was not previously introduced unless it is perfectly innocuous. For actions involving multiple steps or failure points, require the player to choose a course of action at each step. Write your reasoning and the results of the dice roll in a JSON \"reasoning\" tag and narrate the results in a JSON \"narration\" tag. Present one action at a time before prompting the player for their next action. Do not let the action stale, but keep things going. Look up recurring characters with lookup_npc before bringing them back, and register new ones with create_npc. Keep the player's journal up to date with the quest tools when they take, progress or finish a job. Roll names, places and encounters on the random tables with consult_table instead of inventing them, and ask_oracle when unsure how the world would react. Advance the clock with advance_time whenever time passes and schedule deadlines (naming the quest they belong to), healing and callbacks with schedule_event. Record lasting consequences of the player's actions with adjust_reputation, and let their reputation color how NPCs treat them. When the player buys gear, use browse_shop and buy_item so the price and availability come from the catalog. {} {} {}", PROPOSED_TEST_INSTRUCTIONS, STATE_CHANGES_INSTRUCTIONS, save.game_state.rules().gm_instructions()),
+        "player_action": user_input
+    });
+    let proposed_test = save
+        .game_state
+        .rules()
+        .propose_test(&save.game_state, user_input);
+    info!(action = user_input, "player action");
+    if let Some(test) = &proposed_test {
+        info!(test = %test.summary, result = %test.result, "proposed test");
+        message_json["proposed_test"] = test.result.clone();
+        // Kept in the transcript for story exports
+        message_json["proposed_test"]["summary"] = json!(test.summary);
+    }
+    let game_state = save.game_state.gm_context();
+    if game_state
+        .as_object()
+        .is_some_and(|state| !state.is_empty())
+    {
+        message_json["game_state"] = game_state;
+    }
+    save.game_state.clock.clear_fired();
+
+    // Convert the JSON to a string
+    let user_prompt = serde_json::to_string(&message_json)?;
+
+    display.print_debug(
+        &format!("Debug: Sending user message: {}", user_prompt),
+        Color::Magenta,
+    );
+    display.print_wrapped(user_input, Color::Blue);
+    if let Some(test) = &proposed_test {
+        display.print_wrapped(&format!("Test: {}", test.summary), Color::Cyan);
+    }
+    send_user_message(client, &save.thread_id, &user_prompt).await?;
+
+    // Ensure there is no active run before creating a new one
+    loop {
+        let runs = client
+            .threads()
+            .runs(&save.thread_id)
+            .list(&[("limit", "1")])
+            .await?;
+        if runs.data.is_empty() || runs.data[0].status == RunStatus::Completed {
+            break;
+        }
+        display.print_debug("Debug: Waiting for active run to complete", Color::Magenta);
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+
+    let turn_start = TurnStart::capture(&save.game_state);
+    run_until_complete(client, save, tools, display).await?;
+
+    display.print_debug("Debug: Getting latest message", Color::Magenta);
+    let (response_text, warnings) = check_consistency(client, save, display, &turn_start).await?;
+    log_and_display_message(&response_text, "Game Master", display)?;
+    display.print_debug("Debug: Message displayed", Color::Magenta);
+    for warning in warnings {
+        warn!(%warning, "consistency warning");
+        display.print_wrapped(&format!("Consistency warning: {}", warning), Color::Yellow);
+    }
+
+    if let Some(status) = save.game_state.status_block() {
+        display.print_block(&status, Color::Cyan);
+    }
+    // Parse the JSON response to extract the narration for audio
+    let json_response: Value = serde_json::from_str(&response_text)?;
+    if let Some(narration) = json_response.get("narration") {
+        save.record_turn(narration.as_str().unwrap_or(""));
+    }
+    save.last_message_id = latest_message_id(client, &save.thread_id).await?;
+
+    save.game_state.collect_portraits();
+    save.write()?;
+    if save.autosave_due(settings.autosave_interval) {
+        autosave(save, settings, display)?;
+    }
+
+    if let Some(narration) = json_response.get("narration") {
+        if let Some(audio_file) =
+            generate_and_play_audio(audio, narration.as_str().unwrap_or(""), "Game Master").await?
+        {
+            transcript::attach_audio(&save.thread_id, &audio_file)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn autosave(
+    save: &mut Save,
+    settings: &Settings,
+    display: &mut Display,
+) -> Result<(), SharadError> {
+    let slot = save.autosave(settings.autosave_slots)?;
+    display.print_debug(&format!("Debug: Autosaved to {}", slot), Color::Magenta);
+    Ok(())
+}
+
+/// Checks the Game Master's turn against the game state, asking it to revise contradicting
+/// turns. Returns the final response and the contradictions left for the player to see.
+async fn check_consistency(
+    client: &Client<OpenAIConfig>,
+    save: &mut Save,
+    display: &mut Display,
+    turn_start: &TurnStart,
+) -> Result<(String, Vec<String>), SharadError> {
+    let mut response_text = get_latest_message(client, &save.thread_id).await?;
+    let mut revisions = 0;
+    loop {
+        let response: Value = serde_json::from_str(&response_text).unwrap_or_default();
+        match check_turn(&save.game_state, turn_start, &response) {
+            Ok(changes) => {
+                apply_changes(&mut save.game_state, changes);
+                return Ok((response_text, Vec::new()));
+            }
+            Err(issues) if revisions < MAX_REVISIONS => {
+                revisions += 1;
+                info!(revision = revisions, ?issues, "asking for a revision");
+                display.print_debug(
+                    &format!("Debug: Asking the Game Master to revise: {:?}", issues),
+                    Color::Magenta,
+                );
+                send_user_message(client, &save.thread_id, &revision_request(&issues)).await?;
+                // Without tools, so the first answer's tool calls are not applied twice
+                run_until_complete(client, save, &[], display).await?;
+                response_text = get_latest_message(client, &save.thread_id).await?;
             }
-            display.print_debug("Debug: Waiting for active run to complete", Color::Magenta);
-            tokio::time::sleep(Duration::from_secs(1)).await;
+            Err(issues) => return Ok((response_text, issues)),
         }
+    }
+}
 
-        display.print_debug("Debug: Creating and waiting for run", Color::Magenta);
-        let run = create_and_wait_for_run(client, thread_id, assistant_id, display).await?;
+/// Creates a run and answers its tool calls until the Game Master has finished its turn.
+#[instrument(name = "run", skip_all, fields(run_id = tracing::field::Empty))]
+async fn run_until_complete(
+    client: &Client<OpenAIConfig>,
+    save: &mut Save,
+    tools: &[AssistantTools],
+    display: &mut Display,
+) -> Result<(), SharadError> {
+    display.print_debug("Debug: Creating and waiting for run", Color::Magenta);
+    let start = Instant::now();
+    let mut run =
+        create_and_wait_for_run(client, &save.thread_id, &save.assistant_id, tools, display)
+            .await?;
 
+    while run.status == RunStatus::RequiresAction {
         display.print_debug("Debug: Checking for required actions", Color::Magenta);
-        // Handle tool calls
+        let mut tool_outputs = Vec::new();
         if let Some(required_action) = &run.required_action {
             display.print_debug(
                 &format!("Debug: Required action type: {}", required_action.r#type),
                 Color::Magenta,
             );
-            if required_action.r#type == "submit_tool_outputs" {
-                for tool_call in &required_action.submit_tool_outputs.tool_calls {
-                    display.print_debug(
-                        &format!("Debug: Processing tool call: {}", tool_call.function.name),
-                        Color::Magenta,
-                    );
-                    if tool_call.function.name == "roll_dice" {
-                        let args: serde_json::Value =
-                            serde_json::from_str(&tool_call.function.arguments)?;
-                        let dice_number = args["dice_number"].as_u64().unwrap_or(0) as u8;
-                        let threshold = args["threshold"].as_u64().unwrap_or(0) as u8;
-
-                        let roll_result = shadowrun_dice_roll(dice_number, threshold);
-                        let tool_output = serde_json::to_string(&roll_result)?;
-
-                        let tool_call_id = tool_call.id.clone();
-                        let pending_tool_outputs_clone = Arc::clone(&pending_tool_outputs);
-
-                        let mut outputs = pending_tool_outputs_clone.lock().await;
-                        let tool_output_clone = tool_output.clone();
-                        let tool_call_id_clone = tool_call_id.clone();
-                        outputs.push(ToolsOutputs {
-                            tool_call_id: Some(tool_call_id),
-                            output: Some(tool_output),
-                        });
-
-                        // Submit the tool output immediately
-                        let submit_request = SubmitToolOutputsRunRequest {
-                            tool_outputs: vec![ToolsOutputs {
-                                tool_call_id: Some(tool_call_id_clone),
-                                output: Some(tool_output_clone),
-                            }],
-                            stream: None,
-                        };
-                        client
-                            .threads()
-                            .runs(thread_id)
-                            .submit_tool_outputs(&run.id, submit_request)
-                            .await?;
-                    }
-                    if tool_call.function.name == "generate_character_image" {
-                        let args: serde_json::Value =
-                            serde_json::from_str(&tool_call.function.arguments)?;
-                        let character_info = CharacterInfo {
-                            name: args["name"].as_str().unwrap_or("").to_string(),
-                            appearance: Appearance {
-                                gender: args["appearance"]["gender"]
-                                    .as_str()
-                                    .unwrap_or("")
-                                    .to_string(),
-                                age: args["appearance"]["age"].as_str().unwrap_or("").to_string(),
-                                height: args["appearance"]["height"]
-                                    .as_str()
-                                    .unwrap_or("")
-                                    .to_string(),
-                                build: args["appearance"]["build"]
-                                    .as_str()
-                                    .unwrap_or("")
-                                    .to_string(),
-                                hair: args["appearance"]["hair"]
-                                    .as_str()
-                                    .unwrap_or("")
-                                    .to_string(),
-                                eyes: args["appearance"]["eyes"]
-                                    .as_str()
-                                    .unwrap_or("")
-                                    .to_string(),
-                                skin: args["appearance"]["skin"]
-                                    .as_str()
-                                    .unwrap_or("")
-                                    .to_string(),
-                            },
-                            distinctive_signs: args["distinctive_signs"]
-                                .as_array()
-                                .map(|arr| {
-                                    arr.iter()
-                                        .filter_map(|v| v.as_str().map(String::from))
-                                        .collect()
-                                })
-                                .unwrap_or_default(),
-                            accessories: args["accessories"]
-                                .as_array()
-                                .map(|arr| {
-                                    arr.iter()
-                                        .filter_map(|v| v.as_str().map(String::from))
-                                        .collect()
-                                })
-                                .unwrap_or_default(),
-                            location: args["location"].as_str().unwrap_or("").to_string(),
-                            ambiance: args["ambiance"].as_str().unwrap_or("").to_string(),
-                            environment: args["environment"].as_str().unwrap_or("").to_string(),
-                            image_generation_prompt: args["image_generation_prompt"]
-                                .as_str()
-                                .unwrap_or("")
-                                .to_string(),
-                        };
-                        let tool_call_id = tool_call.id.clone();
-                        let tool_call_id_clone = tool_call.id.clone();
-                        let pending_tool_outputs_clone = Arc::clone(&pending_tool_outputs);
-                        let mut display_clone = display.clone();
-
-                        // Spawn a new task to handle image generation
-                        spawn(async move {
-                            match generate_character_image(character_info).await {
-                                Ok(image_path) => {
-                                    display_clone.print_debug(
-                                        &format!("Character image generated: {}", image_path),
-                                        Color::Magenta,
-                                    );
-
-                                    // Open the generated image
-                                    if let Err(e) = open_image(&image_path) {
-                                        display_clone.print_debug(
-                                            &format!("Failed to open image: {}", e),
-                                            Color::Red,
-                                        );
-                                    }
-
-                                    let mut outputs = pending_tool_outputs_clone.lock().await;
-                                    outputs.push(ToolsOutputs {
-                                        tool_call_id: Some(tool_call_id),
-                                        output: Some(image_path),
-                                    });
-                                }
-                                Err(e) => {
-                                    display_clone.print_debug(
-                                        &format!("Failed to generate character image: {}", e),
-                                        Color::Red,
-                                    );
-                                    let mut outputs = pending_tool_outputs_clone.lock().await;
-                                    outputs.push(ToolsOutputs {
-                                        tool_call_id: Some(tool_call_id),
-                                        output: Some("Failed to generate image".to_string()),
-                                    });
-                                }
-                            }
-                        });
-
-                        // Submit a dummy output immediately
-                        let dummy_submit_request = SubmitToolOutputsRunRequest {
-                            tool_outputs: vec![ToolsOutputs {
-                                tool_call_id: Some(tool_call_id_clone.clone()),
-                                output: Some("Tool started".to_string()),
-                            }],
-                            stream: None,
-                        };
-                        client
-                            .threads()
-                            .runs(thread_id)
-                            .submit_tool_outputs(&run.id, dummy_submit_request)
-                            .await?;
+            for tool_call in &required_action.submit_tool_outputs.tool_calls {
+                display.print_debug(
+                    &format!("Debug: Processing tool call: {}", tool_call.function.name),
+                    Color::Magenta,
+                );
+                let tool_start = Instant::now();
+                let output = match handle_tool_call(tool_call, &mut save.game_state, display).await
+                {
+                    Ok(output) => output,
+                    Err(e) => {
+                        error!(
+                            tool = %tool_call.function.name,
+                            arguments = %tool_call.function.arguments,
+                            error = %e,
+                            "tool call failed"
+                        );
+                        // The run waits for an output of every call, so the error is sent instead
+                        json!({ "error": e.to_string() }).to_string()
                     }
-                }
-            }
-        }
-
-        // Wait for the run to complete after submitting tool outputs
-        loop {
-            let run_status = client.threads().runs(thread_id).retrieve(&run.id).await?;
-            display.print_debug(
-                &format!("Debug: Current run status: {:?}", run_status.status),
-                Color::Magenta,
-            );
-
-            match run_status.status {
-                RunStatus::Completed => {
-                    display.print_debug("Debug: Run completed", Color::Magenta);
-                    break;
-                }
-                RunStatus::Failed => {
-                    display.print_debug("Debug: Run failed", Color::Magenta);
-                    return Err(SharadError::Other("Run failed".to_string()));
-                }
-                RunStatus::RequiresAction => {
-                    display.print_debug("Debug: Run requires action", Color::Magenta);
-                    break;
-                }
-                _ => {
-                    display.print_thinking_dot();
-                    tokio::time::sleep(Duration::from_secs(1)).await;
-                }
+                };
+                info!(
+                    tool = %tool_call.function.name,
+                    arguments = %tool_call.function.arguments,
+                    %output,
+                    latency_ms = tool_start.elapsed().as_millis() as u64,
+                    "tool call"
+                );
+                display.print_debug(&format!("Debug: Tool output: {}", output), Color::Magenta);
+                tool_outputs.push(ToolsOutputs {
+                    tool_call_id: Some(tool_call.id.clone()),
+                    output: Some(output),
+                });
             }
         }
 
-        display.print_debug("Debug: Getting latest message", Color::Magenta);
-        let response_text = get_latest_message(client, thread_id).await?;
-        log_and_display_message(log_file, &response_text, "Game Master", display)?;
-        display.print_debug("Debug: Message displayed", Color::Magenta);
-
-        // Parse the JSON response to extract the narration for audio
-        let json_response: Value = serde_json::from_str(&response_text)?;
+        let submit_request = SubmitToolOutputsRunRequest {
+            tool_outputs,
+            stream: None,
+        };
+        timed(
+            "submit_tool_outputs",
+            client
+                .threads()
+                .runs(&save.thread_id)
+                .submit_tool_outputs(&run.id, submit_request),
+        )
+        .await?;
 
-        if let Some(narration) = json_response.get("narration") {
-            generate_and_play_audio(audio, narration.as_str().unwrap_or(""), "Game Master").await?;
-        }
+        run = wait_for_run(client, &save.thread_id, &run.id, display).await?;
     }
 
+    info!(
+        status = ?run.status,
+        latency_ms = start.elapsed().as_millis() as u64,
+        usage = ?run.usage,
+        "run finished"
+    );
     Ok(())
 }
 
@@ -610,24 +550,42 @@ async fn create_and_wait_for_run(
     client: &Client<OpenAIConfig>,
     thread_id: &str,
     assistant_id: &str,
+    tools: &[AssistantTools],
     display: &mut Display,
 ) -> Result<RunObject, SharadError> {
     display.print_debug("Debug: Creating run request", Color::Magenta);
-    let run_request = CreateRunRequestArgs::default()
-        .assistant_id(assistant_id)
-        .parallel_tool_calls(false)
-        .build()?;
+    let mut run_request = CreateRunRequestArgs::default();
+    run_request.assistant_id(assistant_id).tools(tools.to_vec());
+    if !tools.is_empty() {
+        run_request.parallel_tool_calls(false);
+    }
+    let run_request = run_request.build()?;
     display.print_debug("Debug: Sending run request", Color::Magenta);
-    let mut run = client.threads().runs(thread_id).create(run_request).await?;
+    let run = timed(
+        "create_run",
+        client.threads().runs(thread_id).create(run_request),
+    )
+    .await?;
+    tracing::Span::current().record("run_id", run.id.as_str());
+    info!("run created");
     display.print_debug(
         &format!("Debug: Run created with ID: {}", run.id),
         Color::Magenta,
     );
 
+    wait_for_run(client, thread_id, &run.id, display).await
+}
+
+async fn wait_for_run(
+    client: &Client<OpenAIConfig>,
+    thread_id: &str,
+    run_id: &str,
+    display: &mut Display,
+) -> Result<RunObject, SharadError> {
     let mut iterations = 0;
     let max_iterations = 100; // Set a reasonable maximum number of iterations
 
-    loop {
+    let run = loop {
         iterations += 1;
         if iterations > max_iterations {
             display.clear_thinking();
@@ -640,7 +598,11 @@ async fn create_and_wait_for_run(
             &format!("Debug: Checking run status (iteration {})", iterations),
             Color::Magenta,
         );
-        let run_status = client.threads().runs(thread_id).retrieve(&run.id).await?;
+        let run_status = timed(
+            "retrieve_run",
+            client.threads().runs(thread_id).retrieve(run_id),
+        )
+        .await?;
         display.print_debug(
             &format!("Debug: Current run status: {:?}", run_status.status),
             Color::Magenta,
@@ -649,24 +611,23 @@ async fn create_and_wait_for_run(
         match run_status.status {
             RunStatus::Completed => {
                 display.print_debug("Debug: Run completed", Color::Magenta);
-                run = run_status;
-                break;
+                break run_status;
             }
             RunStatus::Failed => {
+                error!(last_error = ?run_status.last_error, "run failed");
                 display.print_debug("Debug: Run failed", Color::Magenta);
                 return Err(SharadError::Other("Run failed".to_string()));
             }
             RunStatus::RequiresAction => {
                 display.print_debug("Debug: Run requires action", Color::Magenta);
-                run = run_status;
-                break;
+                break run_status;
             }
             _ => {
                 display.print_thinking_dot();
                 tokio::time::sleep(Duration::from_secs(1)).await;
             }
         }
-    }
+    };
     display.clear_thinking();
 
     Ok(run)
@@ -677,16 +638,16 @@ async fn fetch_all_messages(
     thread_id: &str,
 ) -> Result<Vec<MessageObject>, SharadError> {
     let mut all_messages = Vec::new();
-    let mut before: Option<String> = None;
+    let mut after: Option<String> = None;
     loop {
-        let mut params = vec![("order", "desc"), ("limit", "100")];
-        if let Some(before_id) = &before {
-            params.push(("before", before_id));
+        let mut params = vec![("order", "asc"), ("limit", "100")];
+        if let Some(after_id) = &after {
+            params.push(("after", after_id.as_str()));
         }
         let messages = client.threads().messages(thread_id).list(&params).await?;
-        all_messages.extend(messages.data.into_iter().rev());
+        all_messages.extend(messages.data);
         if messages.has_more {
-            before = messages.first_id;
+            after = messages.last_id;
         } else {
             break;
         }
@@ -694,6 +655,146 @@ async fn fetch_all_messages(
     Ok(all_messages)
 }
 
+/// Writes the transcript of a thread that started before transcripts were kept.
+pub async fn fetch_transcript(
+    client: &Client<OpenAIConfig>,
+    thread_id: &str,
+) -> Result<(), SharadError> {
+    let messages = fetch_all_messages(client, thread_id).await?;
+    transcript::write(thread_id, &transcript_entries(&messages))
+}
+
+/// Text messages of a thread as transcript entries.
+fn transcript_entries(messages: &[MessageObject]) -> Vec<TranscriptEntry> {
+    messages
+        .iter()
+        .filter_map(|message| {
+            let Some(MessageContent::Text(text_content)) = message.content.first() else {
+                return None;
+            };
+            let timestamp = chrono::DateTime::from_timestamp(message.created_at as i64, 0)
+                .map(|date| date.with_timezone(&chrono::Local).to_rfc3339())
+                .unwrap_or_default();
+            Some(TranscriptEntry {
+                timestamp,
+                role: match message.role {
+                    MessageRole::User => Role::User,
+                    MessageRole::Assistant => Role::Assistant,
+                },
+                id: Some(message.id.clone()),
+                content: text_content.text.value.clone(),
+                audio: None,
+            })
+        })
+        .collect()
+}
+
+/// Copies messages to a new thread with its own transcript, keeping their timestamps and audio.
+/// Returns the new thread's id and the id of its last message.
+pub async fn copy_thread(
+    client: &Client<OpenAIConfig>,
+    entries: &[TranscriptEntry],
+) -> Result<(String, Option<String>), SharadError> {
+    let thread = client
+        .threads()
+        .create(CreateThreadRequestArgs::default().build()?)
+        .await?;
+    let mut copies = Vec::new();
+    for entry in entries {
+        let role = match entry.role {
+            Role::User => MessageRole::User,
+            Role::Assistant => MessageRole::Assistant,
+        };
+        let copy = client
+            .threads()
+            .messages(&thread.id)
+            .create(
+                CreateMessageRequestArgs::default()
+                    .role(role)
+                    .content(entry.content.clone())
+                    .build()?,
+            )
+            .await?;
+        copies.push(TranscriptEntry {
+            id: Some(copy.id),
+            ..entry.clone()
+        });
+    }
+    transcript::write(&thread.id, &copies)?;
+    let last_message_id = copies.last().and_then(|entry| entry.id.clone());
+    Ok((thread.id, last_message_id))
+}
+
+/// Whether the save's thread has messages after the save's last message.
+async fn thread_went_on(client: &Client<OpenAIConfig>, save: &Save) -> Result<bool, SharadError> {
+    let messages = fetch_all_messages(client, &save.thread_id).await?;
+    let position = save.last_message_id.as_ref().and_then(|last_message_id| {
+        messages
+            .iter()
+            .position(|message| &message.id == last_message_id)
+    });
+    Ok(position.is_some_and(|position| position + 1 < messages.len()))
+}
+
+/// The save's transcript up to its last message, with the narration audio cached for it.
+async fn entries_until_save(
+    client: &Client<OpenAIConfig>,
+    save: &Save,
+) -> Result<Vec<TranscriptEntry>, SharadError> {
+    if !transcript::exists(&save.thread_id) {
+        fetch_transcript(client, &save.thread_id).await?;
+    }
+    Ok(transcript::until(
+        transcript::read(&save.thread_id)?,
+        save.last_message_id.as_deref(),
+    ))
+}
+
+/// Makes the thread end at the save's last message. When the conversation went on after the
+/// save was written, as with autosaves and quickloads, the messages up to the save are copied
+/// to a new thread so the Game Master does not remember turns that were loaded away.
+pub async fn rewind_thread(
+    client: &Client<OpenAIConfig>,
+    save: &mut Save,
+    display: &mut Display,
+) -> Result<(), SharadError> {
+    if save.last_message_id.is_none() || !thread_went_on(client, save).await? {
+        return Ok(());
+    }
+
+    display.print_wrapped("Rewinding the story to the save...", Color::Yellow);
+    let entries = entries_until_save(client, save).await?;
+    let (thread_id, last_message_id) = copy_thread(client, &entries).await?;
+    save.thread_id = thread_id;
+    save.last_message_id = last_message_id;
+    Ok(())
+}
+
+/// Moves the save to its own copy of its thread, so it can be played apart from the original.
+pub async fn branch_thread(
+    client: &Client<OpenAIConfig>,
+    save: &mut Save,
+) -> Result<(), SharadError> {
+    let entries = entries_until_save(client, save).await?;
+    let (thread_id, last_message_id) = copy_thread(client, &entries).await?;
+    save.thread_id = thread_id;
+    save.last_message_id = last_message_id;
+    Ok(())
+}
+
+/// Id of the newest message of the thread, recorded in the save after each turn.
+async fn latest_message_id(
+    client: &Client<OpenAIConfig>,
+    thread_id: &str,
+) -> Result<Option<String>, SharadError> {
+    let messages = client
+        .threads()
+        .messages(thread_id)
+        .list(&[("limit", "1")])
+        .await?;
+    Ok(messages.data.first().map(|message| message.id.clone()))
+}
+
 fn display_message(message: &MessageObject, display: &mut Display) {
     let role = match message.role {
         MessageRole::User => "You",
@@ -716,6 +817,13 @@ fn display_message(message: &MessageObject, display: &mut Display) {
                     if let Some(player_action) = json.get("player_action") {
                         display.print_wrapped(&format!("{}", player_action), Color::Blue);
                     }
+                    if let Some(test) = json.get("proposed_test") {
+                        display.print_debug(&format!("Proposed test: {}", test), Color::Magenta);
+                    }
+                    if let Some(issues) = json.get("consistency_check") {
+                        display
+                            .print_debug(&format!("Consistency check: {}", issues), Color::Magenta);
+                    }
                 }
             }
             MessageRole::Assistant => {
@@ -756,31 +864,39 @@ async fn send_user_message(
     thread_id: &str,
     content: &str,
 ) -> Result<(), SharadError> {
-    client
-        .threads()
-        .messages(thread_id)
-        .create(
+    let message = timed(
+        "create_message",
+        client.threads().messages(thread_id).create(
             CreateMessageRequestArgs::default()
                 .role(MessageRole::User)
                 .content(content)
                 .build()?,
-        )
-        .await?;
+        ),
+    )
+    .await?;
+    transcript::append(thread_id, &message.id, Role::User, content)?;
     Ok(())
 }
 
+/// Fetches the Game Master's reply at the end of a run and adds it to the transcript.
 async fn get_latest_message(
     client: &Client<OpenAIConfig>,
     thread_id: &str,
 ) -> Result<String, SharadError> {
-    let messages = client
-        .threads()
-        .messages(thread_id)
-        .list(&[("limit", "1")])
-        .await?;
+    let messages = timed(
+        "list_messages",
+        client.threads().messages(thread_id).list(&[("limit", "1")]),
+    )
+    .await?;
 
     if let Some(latest_message) = messages.data.first() {
         if let Some(MessageContent::Text(text_content)) = latest_message.content.first() {
+            transcript::append(
+                thread_id,
+                &latest_message.id,
+                Role::Assistant,
+                &text_content.text.value,
+            )?;
             return Ok(text_content.text.value.clone());
         }
     }
@@ -788,14 +904,11 @@ async fn get_latest_message(
 }
 
 fn log_and_display_message(
-    log_file: &mut File,
     message: &str,
     sender: &str,
     display: &mut Display,
 ) -> Result<(), SharadError> {
-    let timestamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
-    let log_entry = format!("[{}] {}: {}\n", timestamp, sender, message);
-    log_file.write_all(log_entry.as_bytes())?;
+    info!(sender, content = message, "message");
     // Parse the JSON message
     let json: Value = serde_json::from_str(message)?;
 
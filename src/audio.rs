@@ -1,5 +1,6 @@
 use crate::display::Display;
 use crate::error::SharadError;
+use crate::paths::{audio_dir, recording_dir};
 use crate::Color;
 use async_openai::error::OpenAIError;
 use async_openai::{
@@ -21,7 +22,7 @@ use std::time::Instant;
 use std::{
     env,
     fs::{self},
-    path::Path,
+    path::{Path, PathBuf},
 };
 use tokio::task;
 
@@ -29,10 +30,10 @@ pub async fn generate_and_play_audio(
     audio: &Audio<'_, OpenAIConfig>,
     text: &str,
     role: &str,
-) -> Result<(), SharadError> {
+) -> Result<Option<PathBuf>, SharadError> {
     let settings = crate::settings::load_settings()?;
     if !settings.audio_output_enabled {
-        return Ok(());
+        return Ok(None);
     }
 
     let voice = match role {
@@ -54,28 +55,34 @@ pub async fn generate_and_play_audio(
         .await
         .map_err(SharadError::OpenAI)?;
     let file_name = format!("{}_{}.mp3", role, Local::now().format("%Y%m%d_%H%M%S"));
-    let file_path = Path::new("./data/logs").join(file_name);
-    fs::create_dir_all("./data/logs").map_err(SharadError::Io)?;
+    let file_path = audio_dir().join(file_name);
+    fs::create_dir_all(audio_dir()).map_err(SharadError::Io)?;
     response
         .save(file_path.to_str().unwrap())
         .await
         .map_err(SharadError::OpenAI)?;
 
-    task::spawn_blocking(move || {
-        let (_stream, stream_handle) = OutputStream::try_default()
-            .map_err(|e| SharadError::AudioPlaybackError(e.to_string()))?;
-        let sink = Sink::try_new(&stream_handle)
-            .map_err(|e| SharadError::AudioPlaybackError(e.to_string()))?;
-        let file = File::open(file_path).map_err(SharadError::Io)?;
-        let source = Decoder::new(BufReader::new(file))
-            .map_err(|e| SharadError::AudioPlaybackError(e.to_string()))?;
-        sink.append(source);
-        sink.sleep_until_end();
-        Ok::<(), SharadError>(())
-    })
-    .await
-    .map_err(|e| SharadError::AudioPlaybackError(e.to_string()))??;
+    let played = file_path.clone();
+    task::spawn_blocking(move || play_audio_file(&played, &AtomicBool::new(false)))
+        .await
+        .map_err(|e| SharadError::AudioPlaybackError(e.to_string()))??;
+
+    Ok(Some(file_path))
+}
 
+/// Plays an audio file to the end, or until `stop` is set. Blocks while it plays.
+pub fn play_audio_file(path: &Path, stop: &AtomicBool) -> Result<(), SharadError> {
+    let (_stream, stream_handle) =
+        OutputStream::try_default().map_err(|e| SharadError::AudioPlaybackError(e.to_string()))?;
+    let sink = Sink::try_new(&stream_handle)
+        .map_err(|e| SharadError::AudioPlaybackError(e.to_string()))?;
+    let file = File::open(path).map_err(SharadError::Io)?;
+    let source = Decoder::new(BufReader::new(file))
+        .map_err(|e| SharadError::AudioPlaybackError(e.to_string()))?;
+    sink.append(source);
+    while !sink.empty() && !stop.load(Ordering::Relaxed) {
+        std::thread::sleep(Duration::from_millis(50));
+    }
     Ok(())
 }
 
@@ -84,10 +91,14 @@ pub async fn record_and_transcribe_audio(display: &mut Display) -> Result<String
     if !settings.audio_input_enabled {
         return Ok(String::new());
     }
-    let recording_path = format!(
-        "./data/logs/recording_{}.mp3",
-        chrono::Utc::now().format("%Y%m%d%H%M%S")
-    );
+    fs::create_dir_all(recording_dir()).map_err(SharadError::Io)?;
+    let recording_path = recording_dir()
+        .join(format!(
+            "recording_{}.mp3",
+            chrono::Utc::now().format("%Y%m%d%H%M%S")
+        ))
+        .display()
+        .to_string();
     record_audio(&recording_path, display)?;
 
     let client = Client::with_config(OpenAIConfig::default().with_api_key(
@@ -0,0 +1,349 @@
+use crate::character::{Awakening, CharacterSheet, ATTRIBUTES};
+use crate::error::SharadError;
+use async_openai::types::FunctionObject;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+
+pub const STARTING_ESSENCE: f64 = 6.0;
+/// How far augmentations can push an attribute past its natural maximum.
+pub const AUGMENTED_MAXIMUM_BONUS: u8 = 4;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AugmentationKind {
+    #[default]
+    Cyberware,
+    Bioware,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Grade {
+    Used,
+    #[default]
+    Standard,
+    Alpha,
+    Beta,
+    Delta,
+}
+
+impl Grade {
+    pub fn essence_multiplier(&self) -> f64 {
+        match self {
+            Grade::Used => 1.25,
+            Grade::Standard => 1.0,
+            Grade::Alpha => 0.8,
+            Grade::Beta => 0.7,
+            Grade::Delta => 0.5,
+        }
+    }
+}
+
+impl fmt::Display for Grade {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            Grade::Used => "used",
+            Grade::Standard => "standard",
+            Grade::Alpha => "alphaware",
+            Grade::Beta => "betaware",
+            Grade::Delta => "deltaware",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Augmentation {
+    pub name: String,
+    #[serde(default)]
+    pub kind: AugmentationKind,
+    #[serde(default)]
+    pub grade: Grade,
+    /// Essence actually paid, after the grade multiplier.
+    pub essence_cost: f64,
+    #[serde(default)]
+    pub attribute_bonuses: BTreeMap<String, i8>,
+    /// Extra dice for the named skills or tests.
+    #[serde(default)]
+    pub dice_pool_bonuses: BTreeMap<String, i8>,
+}
+
+impl Augmentation {
+    pub fn summary(&self) -> String {
+        let effects: Vec<String> = self
+            .attribute_bonuses
+            .iter()
+            .chain(&self.dice_pool_bonuses)
+            .map(|(name, bonus)| format!("{} {:+}", name, bonus))
+            .collect();
+        let mut summary = format!(
+            "{} ({}, {:.2} Essence)",
+            self.name, self.grade, self.essence_cost
+        );
+        if !effects.is_empty() {
+            summary += &format!(" - {}", effects.join(", "));
+        }
+        summary
+    }
+}
+
+/// Magic points lost for a given Essence: one per full or partial point of Essence lost.
+pub fn magic_loss(essence: f64) -> u8 {
+    // Rounded first so 6.0 - 0.2 - 0.8 does not count as a fraction of a point
+    let lost = ((STARTING_ESSENCE - essence) * 100.0).round() / 100.0;
+    lost.max(0.0).ceil() as u8
+}
+
+#[derive(Deserialize)]
+struct InstallAugmentationArgs {
+    name: String,
+    #[serde(default)]
+    kind: AugmentationKind,
+    #[serde(default)]
+    grade: Grade,
+    /// Essence cost at standard grade.
+    essence_cost: f64,
+    #[serde(default)]
+    attribute_bonuses: BTreeMap<String, i8>,
+    #[serde(default)]
+    dice_pool_bonuses: BTreeMap<String, i8>,
+}
+
+#[derive(Deserialize)]
+struct RemoveAugmentationArgs {
+    name: String,
+}
+
+pub fn install_augmentation(
+    character: Option<&mut CharacterSheet>,
+    args: Value,
+) -> Result<Value, SharadError> {
+    let args: InstallAugmentationArgs = serde_json::from_value(args)?;
+    let sheet = character
+        .ok_or_else(|| SharadError::Message("The player has no character sheet.".into()))?;
+
+    if sheet.augmentation(&args.name).is_some() {
+        return Err(SharadError::Message(format!(
+            "{} is already installed.",
+            args.name
+        )));
+    }
+    if let Some(attribute) = args
+        .attribute_bonuses
+        .keys()
+        .find(|attribute| !ATTRIBUTES.contains(&attribute.as_str()))
+    {
+        return Err(SharadError::Message(format!(
+            "Augmentations can only raise physical and mental attributes, not {}.",
+            attribute
+        )));
+    }
+    if args.essence_cost < 0.0 {
+        return Err(SharadError::Message(
+            "Essence cost cannot be negative.".into(),
+        ));
+    }
+
+    let essence_cost = args.essence_cost * args.grade.essence_multiplier();
+    let essence_before = sheet.essence();
+    // Ware installed into an essence hole left by removed augmentations costs nothing more
+    let absorbed = essence_cost.min(sheet.essence_hole);
+    let essence_after = essence_before - (essence_cost - absorbed);
+    if essence_after <= 0.0 {
+        return Err(SharadError::Message(format!(
+            "Installing {} would drop Essence to {:.2}. The character would die.",
+            args.name, essence_after
+        )));
+    }
+
+    sheet.essence_hole -= absorbed;
+    sheet.augmentations.push(Augmentation {
+        name: args.name.trim().to_string(),
+        kind: args.kind,
+        grade: args.grade,
+        essence_cost,
+        attribute_bonuses: args.attribute_bonuses,
+        dice_pool_bonuses: args.dice_pool_bonuses,
+    });
+
+    let magic_lost = if sheet.awakening == Awakening::Mundane {
+        0
+    } else {
+        let lost = magic_loss(essence_after).saturating_sub(magic_loss(essence_before));
+        let lost = lost.min(sheet.magic);
+        sheet.magic -= lost;
+        lost
+    };
+
+    Ok(json!({
+        "installed": sheet.augmentations.last().map(Augmentation::summary),
+        "essence": round(sheet.essence()),
+        "magic": sheet.magic,
+        "magic_lost": magic_lost,
+        "burned_out": sheet.awakening != Awakening::Mundane && sheet.magic == 0,
+    }))
+}
+
+pub fn remove_augmentation(
+    character: Option<&mut CharacterSheet>,
+    args: Value,
+) -> Result<Value, SharadError> {
+    let args: RemoveAugmentationArgs = serde_json::from_value(args)?;
+    let sheet = character
+        .ok_or_else(|| SharadError::Message("The player has no character sheet.".into()))?;
+
+    let index = sheet
+        .augmentations
+        .iter()
+        .position(|augmentation| augmentation.name.eq_ignore_ascii_case(args.name.trim()))
+        .ok_or_else(|| {
+            SharadError::Message(format!(
+                "No augmentation named '{}' is installed.",
+                args.name
+            ))
+        })?;
+    let removed = sheet.augmentations.remove(index);
+    // Lost Essence never comes back; it leaves a hole later ware can fill
+    sheet.essence_hole += removed.essence_cost;
+
+    Ok(json!({
+        "removed": removed.name,
+        "essence": round(sheet.essence()),
+        "essence_hole": round(sheet.essence_hole),
+    }))
+}
+
+fn round(essence: f64) -> f64 {
+    (essence * 100.0).round() / 100.0
+}
+
+pub fn tool_definitions() -> Vec<FunctionObject> {
+    vec![
+        FunctionObject {
+            name: "install_augmentation".to_string(),
+            description: Some(
+                "Install cyberware or bioware in the player character after a street doc or clinic scene. Reduces Essence and, for awakened characters, Magic"
+                    .to_string(),
+            ),
+            parameters: Some(json!({
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string", "description": "e.g. 'Wired Reflexes 1', 'Cybereyes 2'" },
+                    "kind": { "type": "string", "enum": ["cyberware", "bioware"] },
+                    "grade": { "type": "string", "enum": ["used", "standard", "alpha", "beta", "delta"] },
+                    "essence_cost": {
+                        "type": "number",
+                        "description": "Essence cost at standard grade; the grade multiplier is applied automatically"
+                    },
+                    "attribute_bonuses": {
+                        "type": "object",
+                        "description": "Attribute bonuses, e.g. {\"Reaction\": 1}",
+                        "additionalProperties": { "type": "integer" }
+                    },
+                    "dice_pool_bonuses": {
+                        "type": "object",
+                        "description": "Extra dice for skills or tests, e.g. {\"Perception\": 2}",
+                        "additionalProperties": { "type": "integer" }
+                    }
+                },
+                "required": ["name", "kind", "grade", "essence_cost"]
+            })),
+        },
+        FunctionObject {
+            name: "remove_augmentation".to_string(),
+            description: Some(
+                "Remove an installed augmentation. The Essence it cost is not recovered"
+                    .to_string(),
+            ),
+            parameters: Some(json!({
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string" }
+                },
+                "required": ["name"]
+            })),
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::character::test_sheet;
+
+    fn install(sheet: &mut CharacterSheet, name: &str, grade: &str, essence_cost: f64) -> Value {
+        install_augmentation(
+            Some(sheet),
+            json!({ "name": name, "grade": grade, "essence_cost": essence_cost }),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn grades_scale_the_essence_cost() {
+        let mut sheet = test_sheet(Awakening::Mundane, 0);
+        install(&mut sheet, "Wired Reflexes 1", "alpha", 2.0);
+        assert_eq!(round(sheet.essence()), 4.4);
+        install(&mut sheet, "Cybereyes", "used", 0.2);
+        assert_eq!(round(sheet.essence()), 4.15);
+    }
+
+    #[test]
+    fn refuses_ware_that_would_kill() {
+        let mut sheet = test_sheet(Awakening::Mundane, 0);
+        install(&mut sheet, "Cyberarm", "standard", 5.0);
+        let result = install_augmentation(
+            Some(&mut sheet),
+            json!({ "name": "Cyberleg", "essence_cost": 1.0 }),
+        );
+        assert!(result.is_err());
+        assert_eq!(sheet.augmentations.len(), 1);
+    }
+
+    #[test]
+    fn new_ware_fills_the_essence_hole_first() {
+        let mut sheet = test_sheet(Awakening::Mundane, 0);
+        install(&mut sheet, "Cybereyes", "standard", 1.0);
+        remove_augmentation(Some(&mut sheet), json!({ "name": "cybereyes" })).unwrap();
+        assert_eq!(round(sheet.essence()), 5.0);
+        assert_eq!(sheet.essence_hole, 1.0);
+
+        // Within the hole, Essence stays where it is
+        install(&mut sheet, "Datajack", "standard", 0.6);
+        assert_eq!(round(sheet.essence()), 5.0);
+        assert_eq!(round(sheet.essence_hole), 0.4);
+
+        // Past it, only the rest is paid
+        install(&mut sheet, "Smartlink", "standard", 0.5);
+        assert_eq!(round(sheet.essence()), 4.9);
+        assert_eq!(sheet.essence_hole, 0.0);
+    }
+
+    #[test]
+    fn awakened_characters_lose_magic_per_point_started() {
+        let mut sheet = test_sheet(Awakening::Adept, 5);
+        let output = install(&mut sheet, "Cybereyes", "standard", 0.2);
+        assert_eq!(output["magic_lost"], 1);
+        assert_eq!(sheet.magic, 4);
+
+        // Still within the first point of Essence lost
+        let output = install(&mut sheet, "Datajack", "alpha", 1.0);
+        assert_eq!(output["magic_lost"], 0);
+        assert_eq!(sheet.magic, 4);
+
+        let output = install(&mut sheet, "Wired Reflexes 2", "standard", 3.0);
+        assert_eq!(output["magic_lost"], 3);
+        assert_eq!(sheet.magic, 1);
+        assert_eq!(output["burned_out"], false);
+    }
+
+    #[test]
+    fn mundane_characters_lose_no_magic() {
+        let mut sheet = test_sheet(Awakening::Mundane, 0);
+        let output = install(&mut sheet, "Cyberarm", "standard", 1.0);
+        assert_eq!(output["magic_lost"], 0);
+        assert_eq!(magic_loss(sheet.essence()), 1);
+    }
+}
@@ -0,0 +1,220 @@
+use crate::archive::{read_zip, ZipWriter};
+use crate::assistant::{copy_thread, fetch_transcript, list_assistants};
+use crate::error::SharadError;
+use crate::paths::{audio_dir, export_dir, image_dir};
+use crate::save::{validate_new_name, Save};
+use crate::transcript::{self, TranscriptEntry};
+use async_openai::Client;
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// Campaign bundles are zip files holding everything needed to play a save on another machine.
+pub const BUNDLE_EXTENSION: &str = "sharad";
+const BUNDLE_VERSION: u32 = 1;
+
+const MANIFEST_FILE: &str = "bundle.json";
+const SAVE_FILE: &str = "save.json";
+const TRANSCRIPT_FILE: &str = "transcript.jsonl";
+const MEDIA_FOLDER: &str = "media/";
+const AUDIO_FOLDER: &str = "audio/";
+
+#[derive(Serialize, Deserialize)]
+struct BundleManifest {
+    version: u32,
+    /// RFC 3339 timestamp.
+    exported: String,
+    name: String,
+    cartridge: String,
+}
+
+fn file_name(path: &str) -> Option<&str> {
+    Path::new(path).file_name()?.to_str()
+}
+
+/// Writes a save with its transcript, portraits and narration audio to a single archive.
+/// Returns the archive written, in the export directory unless a path is given.
+pub async fn export_bundle(name: &str, path: Option<&Path>) -> Result<PathBuf, SharadError> {
+    let save = Save::read(name)?;
+    if !transcript::exists(&save.thread_id) {
+        fetch_transcript(&Client::new(), &save.thread_id).await?;
+    }
+    let entries = transcript::until(
+        transcript::read(&save.thread_id)?,
+        save.last_message_id.as_deref(),
+    );
+
+    let mut zip = ZipWriter::new();
+    let manifest = BundleManifest {
+        version: BUNDLE_VERSION,
+        exported: Local::now().to_rfc3339(),
+        name: name.to_string(),
+        cartridge: save.game_state.cartridge.name.clone(),
+    };
+    zip.add(MANIFEST_FILE, &serde_json::to_vec_pretty(&manifest)?)?;
+    zip.add(SAVE_FILE, &serde_json::to_vec(&save)?)?;
+
+    let mut lines = String::new();
+    for entry in &entries {
+        lines.push_str(&serde_json::to_string(entry)?);
+        lines.push('\n');
+    }
+    zip.add(TRANSCRIPT_FILE, lines.as_bytes())?;
+
+    // Files that were cleaned up since are left out; the save still plays without them
+    for path in save.game_state.media_files() {
+        if let (Some(file), Ok(data)) = (file_name(path), fs::read(path)) {
+            zip.add(&format!("{}{}", MEDIA_FOLDER, file), &data)?;
+        }
+    }
+    let mut audio: Vec<&str> = entries
+        .iter()
+        .filter_map(|entry| entry.audio.as_deref())
+        .collect();
+    audio.sort_unstable();
+    audio.dedup();
+    for path in audio {
+        if let (Some(file), Ok(data)) = (file_name(path), fs::read(path)) {
+            zip.add(&format!("{}{}", AUDIO_FOLDER, file), &data)?;
+        }
+    }
+
+    let path = match path {
+        Some(path) => path.to_path_buf(),
+        None => {
+            fs::create_dir_all(export_dir())?;
+            export_dir().join(format!("{}.{}", name, BUNDLE_EXTENSION))
+        }
+    };
+    fs::write(&path, zip.finish()?)?;
+    Ok(path)
+}
+
+/// Where an imported file goes in `dir`: under its own name, or a numbered one like
+/// `portrait-2.png` when a different file already has it. A file with the same contents is
+/// reused rather than copied again.
+fn unpacked_path(dir: &Path, file: &str, data: &[u8]) -> Result<PathBuf, SharadError> {
+    let original = Path::new(file);
+    let stem = original
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or(file);
+    let extension = original
+        .extension()
+        .and_then(|extension| extension.to_str());
+    for number in 1u32.. {
+        let path = match (number, extension) {
+            (1, _) => dir.join(file),
+            (number, Some(extension)) => dir.join(format!("{}-{}.{}", stem, number, extension)),
+            (number, None) => dir.join(format!("{}-{}", stem, number)),
+        };
+        match fs::read(&path) {
+            Ok(existing) if existing == data => return Ok(path),
+            Ok(_) => continue,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                fs::write(&path, data)?;
+                return Ok(path);
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Err(SharadError::Message(format!(
+        "No free name is left for {}.",
+        file
+    )))
+}
+
+/// Writes the files of one folder of the archive to `dir`, without replacing files already
+/// there. Returns where each file name ended up.
+fn unpack(
+    files: &HashMap<String, Vec<u8>>,
+    folder: &str,
+    dir: &Path,
+) -> Result<HashMap<String, String>, SharadError> {
+    fs::create_dir_all(dir)?;
+    let mut paths = HashMap::new();
+    for (name, data) in files {
+        let Some(file) = name.strip_prefix(folder).and_then(file_name) else {
+            continue;
+        };
+        let path = unpacked_path(dir, file, data)?;
+        paths.insert(file.to_string(), path.display().to_string());
+    }
+    Ok(paths)
+}
+
+fn moved(paths: &HashMap<String, String>, path: &str) -> Option<String> {
+    paths.get(file_name(path)?).cloned()
+}
+
+/// Restores a bundle as a new save and returns its name. When the bundle comes from another
+/// API account, the conversation is rebuilt on a fresh thread from the transcript.
+pub async fn import_bundle(path: &Path, name: Option<&str>) -> Result<String, SharadError> {
+    let files = read_zip(&fs::read(path)?)?;
+    let file = |name: &str| {
+        files
+            .get(name)
+            .ok_or_else(|| SharadError::Message(format!("This bundle has no {}.", name)))
+    };
+
+    let manifest: BundleManifest = serde_json::from_slice(file(MANIFEST_FILE)?)?;
+    if manifest.version > BUNDLE_VERSION {
+        return Err(SharadError::Message(
+            "This bundle was made by a newer version of Sharad.".into(),
+        ));
+    }
+    let name = name.unwrap_or(&manifest.name).trim().to_string();
+    validate_new_name(&name)?;
+
+    let mut save = Save::parse(file(SAVE_FILE)?)?;
+    let mut entries: Vec<TranscriptEntry> = String::from_utf8_lossy(file(TRANSCRIPT_FILE)?)
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+
+    let media = unpack(&files, MEDIA_FOLDER, &image_dir())?;
+    save.game_state.relocate_media(|path| moved(&media, path));
+    let audio = unpack(&files, AUDIO_FOLDER, &audio_dir())?;
+    for entry in &mut entries {
+        entry.audio = entry.audio.as_deref().and_then(|path| moved(&audio, path));
+    }
+
+    let client = Client::new();
+    if client
+        .assistants()
+        .retrieve(&save.assistant_id)
+        .await
+        .is_err()
+    {
+        // Cartridges are assistants named after them on each account
+        let cartridge = &save.game_state.cartridge.name;
+        save.assistant_id = list_assistants()
+            .await?
+            .into_iter()
+            .find(|(_, assistant_name)| assistant_name == cartridge)
+            .map(|(assistant_id, _)| assistant_id)
+            .ok_or_else(|| {
+                SharadError::Message(format!(
+                    "No game cartridge called '{}' is available on this account.",
+                    cartridge
+                ))
+            })?;
+    }
+
+    if client.threads().retrieve(&save.thread_id).await.is_ok() {
+        if !transcript::exists(&save.thread_id) {
+            transcript::write(&save.thread_id, &entries)?;
+        }
+    } else {
+        let (thread_id, last_message_id) = copy_thread(&client, &entries).await?;
+        save.thread_id = thread_id;
+        save.last_message_id = last_message_id;
+    }
+
+    save.name = Some(name.clone());
+    save.write()?;
+    Ok(name)
+}
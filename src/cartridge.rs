@@ -0,0 +1,45 @@
+use crate::paths::asset_file;
+use crate::rules::RuleSystemId;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Local configuration of a game cartridge, read from `cartridges/<slug>/cartridge.json` in the
+/// data directory or shipped with the game.
+/// Cartridges without a configuration file are played with the Shadowrun rules.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Cartridge {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub rule_system: RuleSystemId,
+    /// In-world date the campaign starts at, as `YYYY-MM-DD HH:MM`.
+    #[serde(default)]
+    pub start_date: Option<String>,
+}
+
+impl Cartridge {
+    pub fn load(name: &str) -> Cartridge {
+        let path = asset_file(&cartridge_dir(name).join("cartridge.json"));
+        let cartridge = fs::read_to_string(path)
+            .ok()
+            .and_then(|data| serde_json::from_str::<Cartridge>(&data).ok())
+            .unwrap_or_default();
+        Cartridge {
+            name: name.to_string(),
+            ..cartridge
+        }
+    }
+}
+
+/// Directory holding a cartridge's data files, relative to the asset directories, named after
+/// the assistant in lowercase kebab case.
+pub fn cartridge_dir(name: &str) -> PathBuf {
+    let slug = name
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    Path::new("cartridges").join(slug)
+}
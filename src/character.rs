@@ -0,0 +1,642 @@
+use crate::augmentation::{Augmentation, AUGMENTED_MAXIMUM_BONUS, STARTING_ESSENCE};
+use crate::combat::ConditionMonitor;
+use crate::karma::Karma;
+use crate::magic::Grimoire;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+
+pub const ATTRIBUTES: [&str; 8] = [
+    "Body",
+    "Agility",
+    "Reaction",
+    "Strength",
+    "Willpower",
+    "Logic",
+    "Intuition",
+    "Charisma",
+];
+
+/// Active skills and the attribute they are linked to.
+pub const SKILLS: [(&str, &str); 44] = [
+    ("Archery", "Agility"),
+    ("Automatics", "Agility"),
+    ("Blades", "Agility"),
+    ("Clubs", "Agility"),
+    ("Gunnery", "Agility"),
+    ("Gymnastics", "Agility"),
+    ("Heavy Weapons", "Agility"),
+    ("Locksmith", "Agility"),
+    ("Longarms", "Agility"),
+    ("Palming", "Agility"),
+    ("Pistols", "Agility"),
+    ("Sneaking", "Agility"),
+    ("Throwing Weapons", "Agility"),
+    ("Unarmed Combat", "Agility"),
+    ("Pilot Ground Craft", "Reaction"),
+    ("Pilot Aircraft", "Reaction"),
+    ("Running", "Strength"),
+    ("Swimming", "Strength"),
+    ("Astral Combat", "Willpower"),
+    ("Survival", "Willpower"),
+    ("Armorer", "Logic"),
+    ("Automotive Mechanic", "Logic"),
+    ("Computer", "Logic"),
+    ("Cybercombat", "Logic"),
+    ("Demolitions", "Logic"),
+    ("Electronic Warfare", "Logic"),
+    ("First Aid", "Logic"),
+    ("Hacking", "Logic"),
+    ("Hardware", "Logic"),
+    ("Medicine", "Logic"),
+    ("Software", "Logic"),
+    ("Assensing", "Intuition"),
+    ("Navigation", "Intuition"),
+    ("Perception", "Intuition"),
+    ("Tracking", "Intuition"),
+    ("Con", "Charisma"),
+    ("Etiquette", "Charisma"),
+    ("Intimidation", "Charisma"),
+    ("Leadership", "Charisma"),
+    ("Negotiation", "Charisma"),
+    ("Banishing", "Magic"),
+    ("Counterspelling", "Magic"),
+    ("Spellcasting", "Magic"),
+    ("Summoning", "Magic"),
+];
+
+pub const MAX_SKILL_RATING_AT_CREATION: u8 = 6;
+/// Unspent resources a new character may keep as cash.
+pub const MAX_STARTING_NUYEN: u32 = 5_000;
+
+pub fn linked_attribute(skill: &str) -> Option<&'static str> {
+    SKILLS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(skill))
+        .map(|(_, attribute)| *attribute)
+}
+
+/// Which inherent limit caps the hits of a skill test.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Limit {
+    Physical,
+    Mental,
+    Social,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// Limit of a skill's tests, from its linked attribute. Magic skills are limited by Force instead.
+pub fn skill_limit(skill: &str) -> Option<Limit> {
+    match linked_attribute(skill)? {
+        "Body" | "Agility" | "Reaction" | "Strength" => Some(Limit::Physical),
+        "Willpower" | "Logic" | "Intuition" => Some(Limit::Mental),
+        "Charisma" => Some(Limit::Social),
+        _ => None,
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Metatype {
+    Human,
+    Elf,
+    Dwarf,
+    Ork,
+    Troll,
+}
+
+impl Metatype {
+    /// (minimum, natural maximum) of an attribute for this metatype.
+    pub fn attribute_range(&self, attribute: &str) -> (u8, u8) {
+        match (self, attribute) {
+            (Metatype::Elf, "Agility") => (2, 7),
+            (Metatype::Elf, "Charisma") => (3, 8),
+            (Metatype::Dwarf, "Body") => (3, 8),
+            (Metatype::Dwarf, "Reaction") => (1, 5),
+            (Metatype::Dwarf, "Strength") => (3, 8),
+            (Metatype::Dwarf, "Willpower") => (2, 7),
+            (Metatype::Ork, "Body") => (4, 9),
+            (Metatype::Ork, "Strength") => (3, 8),
+            (Metatype::Ork, "Logic") => (1, 5),
+            (Metatype::Ork, "Charisma") => (1, 5),
+            (Metatype::Troll, "Body") => (5, 10),
+            (Metatype::Troll, "Agility") => (1, 5),
+            (Metatype::Troll, "Strength") => (5, 10),
+            (Metatype::Troll, "Logic") => (1, 5),
+            (Metatype::Troll, "Intuition") => (1, 5),
+            (Metatype::Troll, "Charisma") => (1, 4),
+            (Metatype::Human, "Edge") => (2, 7),
+            (_, "Edge") => (1, 6),
+            _ => (1, 6),
+        }
+    }
+}
+
+impl fmt::Display for Metatype {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    A,
+    B,
+    C,
+    D,
+    E,
+}
+
+impl Priority {
+    pub const ALL: [Priority; 5] = [
+        Priority::A,
+        Priority::B,
+        Priority::C,
+        Priority::D,
+        Priority::E,
+    ];
+
+    /// Metatypes available at this priority and the special attribute points they grant.
+    pub fn metatypes(&self) -> Vec<(Metatype, u8)> {
+        match self {
+            Priority::A => vec![
+                (Metatype::Human, 9),
+                (Metatype::Elf, 8),
+                (Metatype::Dwarf, 7),
+                (Metatype::Ork, 7),
+                (Metatype::Troll, 5),
+            ],
+            Priority::B => vec![
+                (Metatype::Human, 7),
+                (Metatype::Elf, 6),
+                (Metatype::Dwarf, 4),
+                (Metatype::Ork, 4),
+                (Metatype::Troll, 0),
+            ],
+            Priority::C => vec![
+                (Metatype::Human, 5),
+                (Metatype::Elf, 3),
+                (Metatype::Dwarf, 1),
+                (Metatype::Ork, 0),
+            ],
+            Priority::D => vec![(Metatype::Human, 3), (Metatype::Elf, 0)],
+            Priority::E => vec![(Metatype::Human, 1)],
+        }
+    }
+
+    pub fn attribute_points(&self) -> u8 {
+        match self {
+            Priority::A => 24,
+            Priority::B => 20,
+            Priority::C => 16,
+            Priority::D => 14,
+            Priority::E => 12,
+        }
+    }
+
+    /// Magic options available at this priority. An empty list means mundane.
+    pub fn magic_options(&self) -> Vec<(Awakening, u8)> {
+        match self {
+            Priority::A => vec![(Awakening::Magician, 6)],
+            Priority::B => vec![(Awakening::Magician, 4), (Awakening::Adept, 6)],
+            Priority::C => vec![(Awakening::Magician, 3), (Awakening::Adept, 4)],
+            Priority::D => vec![(Awakening::Adept, 2)],
+            Priority::E => vec![],
+        }
+    }
+
+    pub fn skill_points(&self) -> u8 {
+        match self {
+            Priority::A => 46,
+            Priority::B => 36,
+            Priority::C => 28,
+            Priority::D => 22,
+            Priority::E => 18,
+        }
+    }
+
+    pub fn resources(&self) -> u32 {
+        match self {
+            Priority::A => 450_000,
+            Priority::B => 275_000,
+            Priority::C => 140_000,
+            Priority::D => 50_000,
+            Priority::E => 6_000,
+        }
+    }
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Awakening {
+    Mundane,
+    Magician,
+    Adept,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Priorities {
+    pub metatype: Priority,
+    pub attributes: Priority,
+    pub magic: Priority,
+    pub skills: Priority,
+    pub resources: Priority,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GearItem {
+    pub name: String,
+    pub cost: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CharacterSheet {
+    pub name: String,
+    pub metatype: Metatype,
+    #[serde(default)]
+    pub background: String,
+    pub priorities: Priorities,
+    pub attributes: BTreeMap<String, u8>,
+    pub edge: u8,
+    pub awakening: Awakening,
+    #[serde(default)]
+    pub magic: u8,
+    pub skills: BTreeMap<String, u8>,
+    #[serde(default)]
+    pub gear: Vec<GearItem>,
+    pub nuyen: u32,
+    pub condition: ConditionMonitor,
+    #[serde(default)]
+    pub karma: Karma,
+    #[serde(default)]
+    pub grimoire: Grimoire,
+    #[serde(default)]
+    pub augmentations: Vec<Augmentation>,
+    /// Essence lost to removed augmentations, which never returns.
+    #[serde(default)]
+    pub essence_hole: f64,
+    #[serde(default)]
+    pub portrait: Option<String>,
+}
+
+impl CharacterSheet {
+    pub fn attribute(&self, name: &str) -> u8 {
+        match name {
+            "Edge" => self.edge,
+            "Magic" => self.magic,
+            _ => self.attributes.get(name).copied().unwrap_or(0),
+        }
+    }
+
+    pub fn skill(&self, name: &str) -> u8 {
+        self.skills
+            .iter()
+            .find(|(skill, _)| skill.eq_ignore_ascii_case(name))
+            .map(|(_, rating)| *rating)
+            .unwrap_or(0)
+    }
+
+    pub fn augmentation(&self, name: &str) -> Option<&Augmentation> {
+        self.augmentations
+            .iter()
+            .find(|augmentation| augmentation.name.eq_ignore_ascii_case(name.trim()))
+    }
+
+    pub fn essence(&self) -> f64 {
+        let installed: f64 = self
+            .augmentations
+            .iter()
+            .map(|augmentation| augmentation.essence_cost)
+            .sum();
+        STARTING_ESSENCE - installed - self.essence_hole
+    }
+
+    /// Attribute rating including augmentation bonuses, capped at the augmented maximum.
+    pub fn augmented_attribute(&self, name: &str) -> u8 {
+        let bonus: i32 = self
+            .augmentations
+            .iter()
+            .filter_map(|augmentation| augmentation.attribute_bonuses.get(name))
+            .map(|bonus| *bonus as i32)
+            .sum();
+        let base = self.attribute(name);
+        if bonus == 0 {
+            return base;
+        }
+        let maximum = self.metatype.attribute_range(name).1 + AUGMENTED_MAXIMUM_BONUS;
+        (base as i32 + bonus).clamp(0, maximum.max(base) as i32) as u8
+    }
+
+    /// Dice pool for a skill test: rating, augmented linked attribute and augmentation dice.
+    pub fn dice_pool(&self, skill: &str) -> u8 {
+        let attribute = linked_attribute(skill)
+            .map(|attribute| self.augmented_attribute(attribute))
+            .unwrap_or(0);
+        let bonus: i32 = self
+            .augmentations
+            .iter()
+            .flat_map(|augmentation| &augmentation.dice_pool_bonuses)
+            .filter(|(name, _)| name.eq_ignore_ascii_case(skill))
+            .map(|(_, bonus)| *bonus as i32)
+            .sum();
+        (self.skill(skill) as i32 + attribute as i32 + bonus).max(0) as u8
+    }
+
+    /// Inherent limit capping the hits of tests of the given kind.
+    pub fn limit(&self, limit: Limit) -> u8 {
+        let attribute = |name| self.augmented_attribute(name) as f64;
+        let total = match limit {
+            Limit::Physical => {
+                attribute("Strength") * 2.0 + attribute("Body") + attribute("Reaction")
+            }
+            Limit::Mental => {
+                attribute("Logic") * 2.0 + attribute("Intuition") + attribute("Willpower")
+            }
+            Limit::Social => {
+                attribute("Charisma") * 2.0 + attribute("Willpower") + self.essence().ceil()
+            }
+        };
+        (total / 3.0).ceil() as u8
+    }
+
+    /// Refreshes the condition monitor after Body or Willpower changed, keeping current damage.
+    pub fn refresh_condition_monitor(&mut self) {
+        let mut condition =
+            ConditionMonitor::new(self.attribute("Body"), self.attribute("Willpower"));
+        condition.physical_damage = self.condition.physical_damage;
+        condition.stun_damage = self.condition.stun_damage;
+        condition.overflow = self.condition.overflow;
+        self.condition = condition;
+    }
+
+    pub fn attribute_points_spent(&self) -> u32 {
+        ATTRIBUTES
+            .iter()
+            .map(|attribute| {
+                let (minimum, _) = self.metatype.attribute_range(attribute);
+                self.attribute(attribute).saturating_sub(minimum) as u32
+            })
+            .sum()
+    }
+
+    pub fn skill_points_spent(&self) -> u32 {
+        self.skills.values().map(|&rating| rating as u32).sum()
+    }
+
+    pub fn gear_cost(&self) -> u32 {
+        self.gear.iter().map(|item| item.cost).sum()
+    }
+
+    /// Checks the sheet against the priority system. Returns every problem found.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut problems = Vec::new();
+        let priorities = &self.priorities;
+
+        if self.name.trim().is_empty() {
+            problems.push("The character needs a name.".to_string());
+        }
+
+        let mut used = vec![
+            priorities.metatype,
+            priorities.attributes,
+            priorities.magic,
+            priorities.skills,
+            priorities.resources,
+        ];
+        used.sort();
+        used.dedup();
+        if used.len() != Priority::ALL.len() {
+            problems.push("Each priority from A to E must be used exactly once.".to_string());
+        }
+
+        let special_points = priorities
+            .metatype
+            .metatypes()
+            .into_iter()
+            .find(|(metatype, _)| *metatype == self.metatype)
+            .map(|(_, points)| points);
+        match special_points {
+            None => problems.push(format!(
+                "{} is not available at metatype priority {}.",
+                self.metatype, priorities.metatype
+            )),
+            Some(points) => {
+                let (edge_min, edge_max) = self.metatype.attribute_range("Edge");
+                if self.edge < edge_min || self.edge > edge_max {
+                    problems.push(format!(
+                        "Edge must be between {} and {}.",
+                        edge_min, edge_max
+                    ));
+                } else if (self.edge - edge_min) > points {
+                    problems.push(format!(
+                        "Edge uses more than the {} special attribute points available.",
+                        points
+                    ));
+                }
+            }
+        }
+
+        let mut at_maximum = 0;
+        for attribute in ATTRIBUTES {
+            let (minimum, maximum) = self.metatype.attribute_range(attribute);
+            let value = self.attribute(attribute);
+            if value < minimum || value > maximum {
+                problems.push(format!(
+                    "{} must be between {} and {} for a {}.",
+                    attribute, minimum, maximum, self.metatype
+                ));
+            }
+            if value == maximum {
+                at_maximum += 1;
+            }
+        }
+        if at_maximum > 1 {
+            problems
+                .push("Only one attribute may be at its natural maximum at creation.".to_string());
+        }
+        let attribute_points = priorities.attributes.attribute_points() as u32;
+        if self.attribute_points_spent() > attribute_points {
+            problems.push(format!(
+                "Attributes use {} points, but only {} are available.",
+                self.attribute_points_spent(),
+                attribute_points
+            ));
+        }
+
+        let options = priorities.magic.magic_options();
+        let magic_is_valid = match self.awakening {
+            Awakening::Mundane => self.magic == 0,
+            awakening => options.contains(&(awakening, self.magic)),
+        };
+        if !magic_is_valid {
+            problems.push(format!(
+                "{:?} with Magic {} is not available at magic priority {}.",
+                self.awakening, self.magic, priorities.magic
+            ));
+        }
+
+        for (skill, &rating) in &self.skills {
+            match linked_attribute(skill) {
+                None => problems.push(format!("Unknown skill: {}.", skill)),
+                Some("Magic") if self.awakening == Awakening::Mundane => {
+                    problems.push(format!("{} requires a magical character.", skill))
+                }
+                _ => {}
+            }
+            if rating > MAX_SKILL_RATING_AT_CREATION {
+                problems.push(format!(
+                    "{} cannot be above {} at creation.",
+                    skill, MAX_SKILL_RATING_AT_CREATION
+                ));
+            }
+        }
+        let skill_points = priorities.skills.skill_points() as u32;
+        if self.skill_points_spent() > skill_points {
+            problems.push(format!(
+                "Skills use {} points, but only {} are available.",
+                self.skill_points_spent(),
+                skill_points
+            ));
+        }
+
+        if self.gear_cost() > priorities.resources.resources() {
+            problems.push(format!(
+                "Gear costs {} nuyen, but the budget is {} nuyen.",
+                self.gear_cost(),
+                priorities.resources.resources()
+            ));
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut sheet = json!(self);
+        sheet["essence"] = json!((self.essence() * 100.0).round() / 100.0);
+        sheet
+    }
+
+    pub fn render(&self) -> String {
+        let mut lines = vec![format!("{} ({})", self.name, self.metatype), String::new()];
+        // Augmented ratings follow the natural one in parentheses
+        let rating = |attribute: &str| {
+            let base = self.attribute(attribute);
+            match self.augmented_attribute(attribute) {
+                augmented if augmented != base => format!("{:>2} ({})", base, augmented),
+                _ => format!("{:>2}", base),
+            }
+        };
+        let width = ATTRIBUTES
+            .iter()
+            .step_by(2)
+            .map(|attribute| rating(attribute).len())
+            .max()
+            .unwrap_or(2);
+        for pair in ATTRIBUTES.chunks(2) {
+            lines.push(format!(
+                "{:<10} {:<width$}    {:<10} {}",
+                pair[0],
+                rating(pair[0]),
+                pair[1],
+                rating(pair[1])
+            ));
+        }
+        let special = match self.awakening {
+            Awakening::Mundane => format!("{:<10} {:>2}", "Edge", self.edge),
+            awakening => format!(
+                "{:<10} {:>2}    {:<10} {:>2} ({:?})",
+                "Edge", self.edge, "Magic", self.magic, awakening
+            ),
+        };
+        lines.push(special);
+        lines.push(format!("{:<10} {:.2}", "Essence", self.essence()));
+        lines.push(String::new());
+
+        lines.push("Skills".to_string());
+        for (skill, rating) in &self.skills {
+            lines.push(format!(
+                "  {:<20} {:>2}  [{}]",
+                skill,
+                rating,
+                self.dice_pool(skill)
+            ));
+        }
+        lines.push(String::new());
+
+        lines.push(format!(
+            "Condition  P{}/{}  S{}/{}",
+            self.condition.physical_damage,
+            self.condition.physical_boxes,
+            self.condition.stun_damage,
+            self.condition.stun_boxes
+        ));
+        lines.push(format!("Nuyen      {}", self.nuyen));
+        lines.push(format!(
+            "Karma      {} ({} earned)",
+            self.karma.available, self.karma.total_earned
+        ));
+        if self.awakening == Awakening::Magician || !self.grimoire.is_empty() {
+            lines.extend(self.grimoire.render());
+        }
+        if !self.augmentations.is_empty() {
+            lines.push("Augmentations".to_string());
+            for augmentation in &self.augmentations {
+                lines.push(format!("  {}", augmentation.summary()));
+            }
+        }
+        if !self.gear.is_empty() {
+            lines.push("Gear".to_string());
+            for item in &self.gear {
+                lines.push(format!("  {}", item.name));
+            }
+        }
+        lines.join("\n")
+    }
+}
+
+/// A human runner for tests, with 3 in every attribute and 1,000 nuyen.
+#[cfg(test)]
+pub fn test_sheet(awakening: Awakening, magic: u8) -> CharacterSheet {
+    CharacterSheet {
+        name: "Kira".to_string(),
+        metatype: Metatype::Human,
+        background: String::new(),
+        priorities: Priorities {
+            metatype: Priority::E,
+            attributes: Priority::A,
+            magic: Priority::B,
+            skills: Priority::C,
+            resources: Priority::D,
+        },
+        attributes: ATTRIBUTES
+            .iter()
+            .map(|attribute| (attribute.to_string(), 3))
+            .collect(),
+        edge: 2,
+        awakening,
+        magic,
+        skills: BTreeMap::from([("Pistols".to_string(), 4)]),
+        gear: Vec::new(),
+        nuyen: 1000,
+        condition: ConditionMonitor::new(3, 3),
+        karma: Karma::default(),
+        grimoire: Grimoire::default(),
+        augmentations: Vec::new(),
+        essence_hole: 0.0,
+        portrait: None,
+    }
+}
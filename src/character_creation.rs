@@ -0,0 +1,528 @@
+use crate::character::{
+    linked_attribute, Awakening, CharacterSheet, GearItem, Metatype, Priorities, Priority,
+    ATTRIBUTES, MAX_SKILL_RATING_AT_CREATION, MAX_STARTING_NUYEN, SKILLS,
+};
+use crate::combat::ConditionMonitor;
+use crate::display::Display;
+use crate::error::SharadError;
+use crate::image::{generate_character_image, Appearance, CharacterInfo};
+use crate::karma::Karma;
+use crate::magic::{Grimoire, Tradition};
+use crate::menu::select_from_list;
+use crossterm::style::Color;
+use std::collections::BTreeMap;
+
+const PRIORITY_CATEGORIES: [&str; 5] = ["Metatype", "Attributes", "Magic", "Skills", "Resources"];
+
+/// Walks the player through priority-based character creation.
+/// Returns `None` if the player cancels with Esc at any step.
+pub async fn create_character(
+    display: &mut Display,
+) -> Result<Option<CharacterSheet>, SharadError> {
+    let mut sheet = loop {
+        let Some(sheet) = build_sheet(display)? else {
+            return Ok(None);
+        };
+
+        display.print_header("Your character");
+        display.print_block(&sheet.render(), Color::Cyan);
+
+        if let Err(problems) = sheet.validate() {
+            for problem in problems {
+                display.print_wrapped(&problem, Color::Red);
+            }
+            display.print_wrapped("Let's start over.", Color::Yellow);
+            continue;
+        }
+
+        match display
+            .get_user_input("Play this character? (y/n)")?
+            .map(|s| s.to_lowercase())
+            .as_deref()
+        {
+            Some("y") | Some("yes") => break sheet,
+            Some(_) => display.print_wrapped("Let's start over.", Color::Yellow),
+            None => return Ok(None),
+        }
+    };
+
+    let portrait_prompt = format!(
+        "Character portrait of a {} shadowrunner in a cyberpunk setting.",
+        sheet.metatype.to_string().to_lowercase()
+    );
+    let scene = PortraitScene {
+        location: "the neon-lit streets of Seattle",
+        ambiance: "gritty cyberpunk",
+        environment: "rain, holographic advertisements and towering arcologies",
+    };
+    sheet.portrait = create_portrait(display, &sheet.name, portrait_prompt, scene).await?;
+
+    Ok(Some(sheet))
+}
+
+fn build_sheet(display: &mut Display) -> Result<Option<CharacterSheet>, SharadError> {
+    let Some(name) = ask_non_empty(display, "What is your character's name?")? else {
+        return Ok(None);
+    };
+    let Some(priorities) = choose_priorities(display)? else {
+        return Ok(None);
+    };
+
+    let metatypes = priorities.metatype.metatypes();
+    let items: Vec<String> = metatypes
+        .iter()
+        .map(|(metatype, special)| format!("{} ({} special points)", metatype, special))
+        .collect();
+    let Some(index) = select_from_list(display, "Choose your metatype", &items)? else {
+        return Ok(None);
+    };
+    let (metatype, special_points) = metatypes[index];
+
+    let options = priorities.magic.magic_options();
+    let (awakening, magic) = if options.is_empty() {
+        (Awakening::Mundane, 0)
+    } else {
+        let mut items: Vec<String> = options
+            .iter()
+            .map(|(awakening, magic)| format!("{:?} (Magic {})", awakening, magic))
+            .collect();
+        items.push("Mundane".to_string());
+        let Some(index) = select_from_list(display, "Choose your magic", &items)? else {
+            return Ok(None);
+        };
+        options
+            .get(index)
+            .copied()
+            .unwrap_or((Awakening::Mundane, 0))
+    };
+
+    let tradition = if awakening == Awakening::Magician {
+        let items = vec![
+            "Hermetic (resists Drain with Logic)".to_string(),
+            "Shamanic (resists Drain with Charisma)".to_string(),
+        ];
+        match select_from_list(display, "Choose your tradition", &items)? {
+            Some(0) => Tradition::Hermetic,
+            Some(_) => Tradition::Shamanic,
+            None => return Ok(None),
+        }
+    } else {
+        Tradition::default()
+    };
+
+    let Some(attributes) =
+        assign_attributes(display, metatype, priorities.attributes.attribute_points())?
+    else {
+        return Ok(None);
+    };
+    let Some(edge) = assign_edge(display, metatype, special_points)? else {
+        return Ok(None);
+    };
+    let Some(skills) = assign_skills(
+        display,
+        awakening != Awakening::Mundane,
+        priorities.skills.skill_points(),
+    )?
+    else {
+        return Ok(None);
+    };
+    let budget = priorities.resources.resources();
+    let Some(gear) = buy_gear(display, budget)? else {
+        return Ok(None);
+    };
+    let Some(background) = ask_non_empty(
+        display,
+        "Describe your character's background and motivations:",
+    )?
+    else {
+        return Ok(None);
+    };
+
+    let gear_cost: u32 = gear.iter().map(|item| item.cost).sum();
+    let condition = ConditionMonitor::new(attributes["Body"], attributes["Willpower"]);
+
+    Ok(Some(CharacterSheet {
+        name,
+        metatype,
+        background,
+        priorities,
+        attributes,
+        edge,
+        awakening,
+        magic,
+        skills,
+        gear,
+        nuyen: budget.saturating_sub(gear_cost).min(MAX_STARTING_NUYEN),
+        condition,
+        karma: Karma::default(),
+        grimoire: Grimoire {
+            tradition,
+            ..Grimoire::default()
+        },
+        augmentations: Vec::new(),
+        essence_hole: 0.0,
+        portrait: None,
+    }))
+}
+
+fn choose_priorities(display: &mut Display) -> Result<Option<Priorities>, SharadError> {
+    let mut remaining = Priority::ALL.to_vec();
+    let mut chosen = Vec::new();
+
+    for category in PRIORITY_CATEGORIES {
+        let items: Vec<String> = remaining
+            .iter()
+            .map(|priority| format!("{} - {}", priority, describe_priority(category, *priority)))
+            .collect();
+        let Some(index) = select_from_list(
+            display,
+            &format!("Choose the priority for {}", category),
+            &items,
+        )?
+        else {
+            return Ok(None);
+        };
+        chosen.push(remaining.remove(index));
+    }
+
+    Ok(Some(Priorities {
+        metatype: chosen[0],
+        attributes: chosen[1],
+        magic: chosen[2],
+        skills: chosen[3],
+        resources: chosen[4],
+    }))
+}
+
+fn describe_priority(category: &str, priority: Priority) -> String {
+    match category {
+        "Metatype" => priority
+            .metatypes()
+            .iter()
+            .map(|(metatype, _)| metatype.to_string())
+            .collect::<Vec<_>>()
+            .join(", "),
+        "Attributes" => format!("{} points", priority.attribute_points()),
+        "Magic" => {
+            let options = priority.magic_options();
+            if options.is_empty() {
+                "Mundane".to_string()
+            } else {
+                options
+                    .iter()
+                    .map(|(awakening, magic)| format!("{:?} {}", awakening, magic))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            }
+        }
+        "Skills" => format!("{} points", priority.skill_points()),
+        _ => format!("{} nuyen", priority.resources()),
+    }
+}
+
+fn assign_attributes(
+    display: &mut Display,
+    metatype: Metatype,
+    points: u8,
+) -> Result<Option<BTreeMap<String, u8>>, SharadError> {
+    let mut attributes = BTreeMap::new();
+    let mut remaining = points;
+    let mut has_maximum = false;
+
+    display.print_wrapped(
+        "Assign your attributes. Raising one above its minimum costs one point per level, and only one may reach its maximum.",
+        Color::Cyan,
+    );
+
+    for attribute in ATTRIBUTES {
+        let (minimum, maximum) = metatype.attribute_range(attribute);
+        loop {
+            let prompt = format!(
+                "{} ({}-{}), {} points left:",
+                attribute, minimum, maximum, remaining
+            );
+            let Some(input) = display.get_user_input(&prompt)? else {
+                return Ok(None);
+            };
+            let value = match input.parse::<u8>() {
+                Ok(value) if (minimum..=maximum).contains(&value) => value,
+                _ => {
+                    display.print_wrapped(
+                        &format!("Enter a number between {} and {}.", minimum, maximum),
+                        Color::Yellow,
+                    );
+                    continue;
+                }
+            };
+            let cost = value - minimum;
+            if cost > remaining {
+                display.print_wrapped("Not enough attribute points left.", Color::Yellow);
+                continue;
+            }
+            if value == maximum && has_maximum {
+                display.print_wrapped(
+                    "Another attribute is already at its maximum.",
+                    Color::Yellow,
+                );
+                continue;
+            }
+            has_maximum |= value == maximum;
+            remaining -= cost;
+            attributes.insert(attribute.to_string(), value);
+            break;
+        }
+    }
+
+    Ok(Some(attributes))
+}
+
+fn assign_edge(
+    display: &mut Display,
+    metatype: Metatype,
+    special_points: u8,
+) -> Result<Option<u8>, SharadError> {
+    let (minimum, maximum) = metatype.attribute_range("Edge");
+    let highest = maximum.min(minimum + special_points);
+    if highest == minimum {
+        return Ok(Some(minimum));
+    }
+
+    loop {
+        let prompt = format!(
+            "Edge ({}-{}, using your {} special attribute points):",
+            minimum, highest, special_points
+        );
+        let Some(input) = display.get_user_input(&prompt)? else {
+            return Ok(None);
+        };
+        match input.parse::<u8>() {
+            Ok(edge) if (minimum..=highest).contains(&edge) => return Ok(Some(edge)),
+            _ => display.print_wrapped(
+                &format!("Enter a number between {} and {}.", minimum, highest),
+                Color::Yellow,
+            ),
+        }
+    }
+}
+
+fn assign_skills(
+    display: &mut Display,
+    awakened: bool,
+    points: u8,
+) -> Result<Option<BTreeMap<String, u8>>, SharadError> {
+    let available: Vec<&str> = SKILLS
+        .iter()
+        .filter(|(_, attribute)| awakened || *attribute != "Magic")
+        .map(|(skill, _)| *skill)
+        .collect();
+    display.print_wrapped(
+        &format!("Available skills: {}", available.join(", ")),
+        Color::Cyan,
+    );
+
+    let mut skills: BTreeMap<String, u8> = BTreeMap::new();
+    loop {
+        let spent: u8 = skills.values().sum();
+        let prompt = format!(
+            "Skill and rating (e.g. 'Pistols 4'), {} points left. Press Enter when done:",
+            points - spent
+        );
+        let Some(input) = display.get_user_input(&prompt)? else {
+            return Ok(None);
+        };
+        if input.is_empty() {
+            return Ok(Some(skills));
+        }
+
+        let Some((name, rating)) = input.rsplit_once(' ') else {
+            display.print_wrapped("Write the skill name followed by a rating.", Color::Yellow);
+            continue;
+        };
+        let Some(skill) = available
+            .iter()
+            .find(|skill| skill.eq_ignore_ascii_case(name.trim()))
+        else {
+            display.print_wrapped(&format!("Unknown skill: {}", name.trim()), Color::Yellow);
+            continue;
+        };
+        let rating = match rating.parse::<u8>() {
+            Ok(rating) if rating <= MAX_SKILL_RATING_AT_CREATION => rating,
+            _ => {
+                display.print_wrapped(
+                    &format!(
+                        "Ratings go from 0 to {} at creation.",
+                        MAX_SKILL_RATING_AT_CREATION
+                    ),
+                    Color::Yellow,
+                );
+                continue;
+            }
+        };
+
+        let current = skills.get(*skill).copied().unwrap_or(0);
+        if spent - current + rating > points {
+            display.print_wrapped("Not enough skill points left.", Color::Yellow);
+            continue;
+        }
+        if rating == 0 {
+            skills.remove(*skill);
+        } else {
+            skills.insert(skill.to_string(), rating);
+        }
+        display.print_wrapped(
+            &format!(
+                "{} {} (linked to {})",
+                skill,
+                rating,
+                linked_attribute(skill).unwrap_or("-")
+            ),
+            Color::Green,
+        );
+    }
+}
+
+fn buy_gear(display: &mut Display, budget: u32) -> Result<Option<Vec<GearItem>>, SharadError> {
+    let mut gear: Vec<GearItem> = Vec::new();
+    display.print_wrapped(
+        &format!(
+            "Buy your starting gear. Up to {} unspent nuyen are kept as cash.",
+            MAX_STARTING_NUYEN
+        ),
+        Color::Cyan,
+    );
+
+    loop {
+        let spent: u32 = gear.iter().map(|item| item.cost).sum();
+        let prompt = format!(
+            "Item and price (e.g. 'Ares Predator V 725'), {} nuyen left. Press Enter when done:",
+            budget - spent
+        );
+        let Some(input) = display.get_user_input(&prompt)? else {
+            return Ok(None);
+        };
+        if input.is_empty() {
+            return Ok(Some(gear));
+        }
+
+        let parsed = input
+            .rsplit_once(' ')
+            .and_then(|(name, cost)| cost.parse::<u32>().ok().map(|cost| (name.trim(), cost)));
+        match parsed {
+            Some((name, cost)) if !name.is_empty() => {
+                if spent.checked_add(cost).is_none_or(|total| total > budget) {
+                    display.print_wrapped("You can't afford that.", Color::Yellow);
+                    continue;
+                }
+                gear.push(GearItem {
+                    name: name.to_string(),
+                    cost,
+                });
+            }
+            _ => display.print_wrapped("Write the item name followed by its price.", Color::Yellow),
+        }
+    }
+}
+
+/// Where a new character's portrait is set.
+pub struct PortraitScene {
+    pub location: &'static str,
+    pub ambiance: &'static str,
+    pub environment: &'static str,
+}
+
+/// Offers to generate a portrait of a new character. Returns its path, or `None` when the
+/// player declined or the generation failed, which the player is told about.
+pub async fn create_portrait(
+    display: &mut Display,
+    name: &str,
+    image_generation_prompt: String,
+    scene: PortraitScene,
+) -> Result<Option<String>, SharadError> {
+    let Some(character_info) = ask_appearance(display, name, image_generation_prompt)? else {
+        return Ok(None);
+    };
+    let character_info = CharacterInfo {
+        location: scene.location.to_string(),
+        ambiance: scene.ambiance.to_string(),
+        environment: scene.environment.to_string(),
+        ..character_info
+    };
+    display.print_wrapped("Generating your portrait...", Color::Yellow);
+    match generate_character_image(character_info).await {
+        Ok(path) => Ok(Some(path)),
+        Err(e) => {
+            display.print_wrapped(
+                &format!("Failed to generate the portrait: {}", e),
+                Color::Yellow,
+            );
+            Ok(None)
+        }
+    }
+}
+
+/// Asks for the details of a portrait. The scene fields are left for the caller to fill in.
+fn ask_appearance(
+    display: &mut Display,
+    name: &str,
+    image_generation_prompt: String,
+) -> Result<Option<CharacterInfo>, SharadError> {
+    match display
+        .get_user_input("Generate a portrait of your character? (y/n)")?
+        .map(|s| s.to_lowercase())
+        .as_deref()
+    {
+        Some("y") | Some("yes") => {}
+        _ => return Ok(None),
+    }
+
+    display.print_wrapped(
+        "Describe your character's appearance, in English:",
+        Color::Cyan,
+    );
+    let mut answers = Vec::new();
+    for label in ["Gender", "Age", "Height", "Build", "Hair", "Eyes", "Skin"] {
+        match display.get_user_input(&format!("{}:", label))? {
+            Some(answer) => answers.push(answer),
+            None => return Ok(None),
+        }
+    }
+    let Some(details) = display.get_user_input(
+        "Distinctive features and clothing, separated by commas (press Enter to skip):",
+    )?
+    else {
+        return Ok(None);
+    };
+
+    Ok(Some(CharacterInfo {
+        name: name.to_string(),
+        appearance: Appearance {
+            gender: answers[0].clone(),
+            age: answers[1].clone(),
+            height: answers[2].clone(),
+            build: answers[3].clone(),
+            hair: answers[4].clone(),
+            eyes: answers[5].clone(),
+            skin: answers[6].clone(),
+        },
+        distinctive_signs: details
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect(),
+        accessories: Vec::new(),
+        location: String::new(),
+        ambiance: String::new(),
+        environment: String::new(),
+        image_generation_prompt,
+    }))
+}
+
+pub fn ask_non_empty(display: &mut Display, prompt: &str) -> Result<Option<String>, SharadError> {
+    loop {
+        match display.get_user_input(prompt)? {
+            Some(answer) if !answer.trim().is_empty() => return Ok(Some(answer)),
+            Some(_) => display.print_wrapped("This cannot be empty.", Color::Yellow),
+            None => return Ok(None),
+        }
+    }
+}
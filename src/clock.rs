@@ -0,0 +1,333 @@
+use crate::combat::DamageType;
+use crate::error::SharadError;
+use crate::journal::{active_quest, Journal};
+use async_openai::types::FunctionObject;
+use chrono::{Duration, NaiveDateTime, Timelike};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+const DATE_FORMAT: &str = "%Y-%m-%d %H:%M";
+pub const DEFAULT_START: &str = "2075-01-01 20:00";
+/// Longest single jump of the clock or delay of an event, so a typo cannot skip years of campaign.
+const MAX_ADVANCE_MINUTES: u64 = 60 * 24 * 90;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum EventKind {
+    Deadline,
+    Healing,
+    Callback,
+    #[default]
+    Other,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScheduledEvent {
+    /// Minutes since the start of the campaign.
+    pub at: u64,
+    #[serde(default)]
+    pub kind: EventKind,
+    pub description: String,
+    /// Damage healed on the player character when a healing event fires.
+    #[serde(default)]
+    pub heal: u8,
+    #[serde(default)]
+    pub track: Option<DamageType>,
+    /// Title of the journal quest a deadline belongs to.
+    #[serde(default)]
+    pub quest: Option<String>,
+}
+
+/// In-world time, kept as minutes elapsed since the campaign's start date.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GameClock {
+    pub start: String,
+    #[serde(default)]
+    pub elapsed_minutes: u64,
+    #[serde(default)]
+    pub events: Vec<ScheduledEvent>,
+    /// Events that came due and have not been shown to the Game Master yet, neither in the
+    /// output of `advance_time` nor in a turn's context.
+    #[serde(default)]
+    pub fired: Vec<ScheduledEvent>,
+}
+
+impl Default for GameClock {
+    fn default() -> Self {
+        GameClock::new(DEFAULT_START)
+    }
+}
+
+impl GameClock {
+    pub fn new(start: &str) -> Self {
+        let start = match NaiveDateTime::parse_from_str(start, DATE_FORMAT) {
+            Ok(_) => start.to_string(),
+            Err(_) => DEFAULT_START.to_string(),
+        };
+        GameClock {
+            start,
+            elapsed_minutes: 0,
+            events: Vec::new(),
+            fired: Vec::new(),
+        }
+    }
+
+    fn start(&self) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(&self.start, DATE_FORMAT)
+            .or_else(|_| NaiveDateTime::parse_from_str(DEFAULT_START, DATE_FORMAT))
+            .unwrap_or_default()
+    }
+
+    /// The date `minutes` into the campaign, or the last representable one past it.
+    fn at(&self, minutes: u64) -> NaiveDateTime {
+        i64::try_from(minutes)
+            .ok()
+            .and_then(Duration::try_minutes)
+            .and_then(|offset| self.start().checked_add_signed(offset))
+            .unwrap_or(NaiveDateTime::MAX)
+    }
+
+    pub fn now(&self) -> NaiveDateTime {
+        self.at(self.elapsed_minutes)
+    }
+
+    /// Days since the start of the campaign, counting the first as day 1.
+    pub fn day(&self) -> i64 {
+        (self.now().date() - self.start().date()).num_days() + 1
+    }
+
+    pub fn time_of_day(&self) -> &'static str {
+        match self.now().hour() {
+            5..=11 => "morning",
+            12..=17 => "afternoon",
+            18..=21 => "evening",
+            _ => "night",
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "Day {} - {} ({})",
+            self.day(),
+            self.now().format("%a %d %b %Y, %H:%M"),
+            self.time_of_day()
+        )
+    }
+
+    fn describe_event(&self, event: &ScheduledEvent) -> Value {
+        let mut description = json!({
+            "kind": event.kind,
+            "description": event.description,
+            "at": self.at(event.at).format(DATE_FORMAT).to_string(),
+        });
+        if let Some(quest) = &event.quest {
+            description["quest"] = json!(quest);
+        }
+        description
+    }
+
+    /// Moves the clock forward and returns the events that came due, in order.
+    pub fn advance(&mut self, minutes: u64) -> Vec<ScheduledEvent> {
+        self.elapsed_minutes = self.elapsed_minutes.saturating_add(minutes);
+        let now = self.elapsed_minutes;
+        let (mut due, pending): (Vec<_>, Vec<_>) =
+            self.events.drain(..).partition(|event| event.at <= now);
+        self.events = pending;
+        due.sort_by_key(|event| event.at);
+        self.fired.extend(due.iter().cloned());
+        due
+    }
+
+    pub fn next_event(&self) -> Option<&ScheduledEvent> {
+        self.events.iter().min_by_key(|event| event.at)
+    }
+
+    /// Forgets the fired events once the Game Master has been told about them.
+    pub fn clear_fired(&mut self) {
+        self.fired.clear();
+    }
+
+    pub fn to_context(&self) -> Value {
+        let mut upcoming: Vec<&ScheduledEvent> = self.events.iter().collect();
+        upcoming.sort_by_key(|event| event.at);
+        json!({
+            "now": self.now().format(DATE_FORMAT).to_string(),
+            "day": self.day(),
+            "time_of_day": self.time_of_day(),
+            "upcoming_events": upcoming
+                .into_iter()
+                .map(|event| self.describe_event(event))
+                .collect::<Vec<_>>(),
+            "events_due": self
+                .fired
+                .iter()
+                .map(|event| self.describe_event(event))
+                .collect::<Vec<_>>(),
+        })
+    }
+
+    pub fn status_line(&self) -> String {
+        match self.next_event() {
+            Some(event) => format!(
+                "{}\nNext: {} ({})",
+                self.describe(),
+                event.description,
+                self.at(event.at).format("%a %H:%M")
+            ),
+            None => self.describe(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Span {
+    #[serde(default)]
+    minutes: u64,
+    #[serde(default)]
+    hours: u64,
+    #[serde(default)]
+    days: u64,
+}
+
+impl Span {
+    fn minutes(&self) -> Option<u64> {
+        self.hours
+            .checked_mul(60)
+            .and_then(|hours| self.days.checked_mul(60 * 24)?.checked_add(hours))
+            .and_then(|total| total.checked_add(self.minutes))
+    }
+
+    /// The span in minutes, if it is between 1 minute and the longest jump of the clock.
+    fn checked_minutes(&self) -> Result<u64, SharadError> {
+        self.minutes()
+            .filter(|minutes| (1..=MAX_ADVANCE_MINUTES).contains(minutes))
+            .ok_or_else(|| {
+                SharadError::Message(format!(
+                    "Time spans must be between 1 minute and {} days.",
+                    MAX_ADVANCE_MINUTES / (60 * 24)
+                ))
+            })
+    }
+}
+
+#[derive(Deserialize)]
+struct AdvanceTimeArgs {
+    #[serde(flatten)]
+    span: Span,
+}
+
+#[derive(Deserialize)]
+struct ScheduleEventArgs {
+    description: String,
+    #[serde(default)]
+    kind: EventKind,
+    #[serde(flatten)]
+    span: Span,
+    #[serde(default)]
+    heal: u8,
+    track: Option<DamageType>,
+    quest: Option<String>,
+}
+
+/// Advances the clock. Returns the fired events so the caller can apply their effects.
+pub fn advance_time(
+    clock: &mut GameClock,
+    args: Value,
+) -> Result<(Value, Vec<ScheduledEvent>), SharadError> {
+    let args: AdvanceTimeArgs = serde_json::from_value(args)?;
+    let minutes = args.span.checked_minutes()?;
+    let fired = clock.advance(minutes);
+    // Delivered here, so the next turn's context does not report them again
+    let due = std::mem::take(&mut clock.fired);
+    let output = json!({
+        "now": clock.describe(),
+        "events_due": due
+            .iter()
+            .map(|event| clock.describe_event(event))
+            .collect::<Vec<_>>(),
+    });
+    Ok((output, fired))
+}
+
+/// Schedules an event. A deadline can name a journal quest, whose deadline it then sets.
+pub fn schedule_event(
+    clock: &mut GameClock,
+    journal: &mut Journal,
+    args: Value,
+) -> Result<Value, SharadError> {
+    let args: ScheduleEventArgs = serde_json::from_value(args)?;
+    let at = clock
+        .elapsed_minutes
+        .checked_add(args.span.checked_minutes()?)
+        .ok_or_else(|| SharadError::Message("The event is too far in the future.".into()))?;
+    let quest = match &args.quest {
+        Some(title) => {
+            let quest = active_quest(journal, title)?;
+            quest.deadline = Some(clock.at(at).format(DATE_FORMAT).to_string());
+            Some(quest.title.clone())
+        }
+        None => None,
+    };
+    let event = ScheduledEvent {
+        at,
+        kind: args.kind,
+        description: args.description,
+        heal: args.heal,
+        track: args.track,
+        quest,
+    };
+    let output = clock.describe_event(&event);
+    clock.events.push(event);
+    Ok(output)
+}
+
+pub fn tool_definitions() -> Vec<FunctionObject> {
+    vec![
+        FunctionObject {
+            name: "advance_time".to_string(),
+            description: Some(
+                "Move the in-game clock forward when time passes in the story (travel, rest, legwork, waiting). Returns any scheduled events that came due"
+                    .to_string(),
+            ),
+            parameters: Some(json!({
+                "type": "object",
+                "properties": {
+                    "minutes": { "type": "integer" },
+                    "hours": { "type": "integer" },
+                    "days": { "type": "integer" }
+                }
+            })),
+        },
+        FunctionObject {
+            name: "schedule_event".to_string(),
+            description: Some(
+                "Schedule something to happen after a delay: a job deadline, the end of a healing period or a contact calling back"
+                    .to_string(),
+            ),
+            parameters: Some(json!({
+                "type": "object",
+                "properties": {
+                    "description": { "type": "string" },
+                    "kind": { "type": "string", "enum": ["deadline", "healing", "callback", "other"] },
+                    "minutes": { "type": "integer" },
+                    "hours": { "type": "integer" },
+                    "days": { "type": "integer" },
+                    "heal": {
+                        "type": "integer",
+                        "description": "For healing events: damage healed on the player character when the event fires"
+                    },
+                    "track": {
+                        "type": "string",
+                        "enum": ["physical", "stun"],
+                        "description": "For healing events in Shadowrun: the condition monitor healed"
+                    },
+                    "quest": {
+                        "type": "string",
+                        "description": "For deadlines: title of the journal quest it belongs to, whose deadline it sets"
+                    }
+                },
+                "required": ["description", "kind"]
+            })),
+        },
+    ]
+}
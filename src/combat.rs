@@ -0,0 +1,531 @@
+use crate::character::CharacterSheet;
+use crate::error::SharadError;
+use async_openai::types::FunctionObject;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::cmp::Reverse;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DamageType {
+    Physical,
+    Stun,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ConditionMonitor {
+    pub physical_boxes: u8,
+    pub stun_boxes: u8,
+    pub physical_damage: u8,
+    pub stun_damage: u8,
+    #[serde(default)]
+    pub overflow: u8,
+}
+
+impl ConditionMonitor {
+    pub fn new(body: u8, willpower: u8) -> Self {
+        ConditionMonitor {
+            physical_boxes: 8 + body.div_ceil(2),
+            stun_boxes: 8 + willpower.div_ceil(2),
+            physical_damage: 0,
+            stun_damage: 0,
+            overflow: 0,
+        }
+    }
+
+    /// Applies damage, rolling excess Stun over into Physical and excess Physical into overflow.
+    pub fn apply(&mut self, amount: u8, damage_type: DamageType) {
+        let physical = match damage_type {
+            DamageType::Stun => {
+                let total = self.stun_damage as u16 + amount as u16;
+                self.stun_damage = total.min(self.stun_boxes as u16) as u8;
+                total.saturating_sub(self.stun_boxes as u16)
+            }
+            DamageType::Physical => amount as u16,
+        };
+
+        let total = self.physical_damage as u16 + physical;
+        self.physical_damage = total.min(self.physical_boxes as u16) as u8;
+        let overflow = self.overflow as u16 + total.saturating_sub(self.physical_boxes as u16);
+        self.overflow = overflow.min(u8::MAX as u16) as u8;
+    }
+
+    pub fn heal(&mut self, amount: u8, damage_type: DamageType) {
+        match damage_type {
+            DamageType::Stun => self.stun_damage = self.stun_damage.saturating_sub(amount),
+            DamageType::Physical => {
+                let healed_overflow = amount.min(self.overflow);
+                self.overflow -= healed_overflow;
+                self.physical_damage = self
+                    .physical_damage
+                    .saturating_sub(amount - healed_overflow);
+            }
+        }
+    }
+
+    /// -1 dice for every 3 boxes filled on each track.
+    pub fn wound_modifier(&self) -> i32 {
+        -((self.physical_damage / 3) as i32 + (self.stun_damage / 3) as i32)
+    }
+
+    pub fn is_incapacitated(&self) -> bool {
+        self.physical_damage >= self.physical_boxes || self.stun_damage >= self.stun_boxes
+    }
+
+    fn track(filled: u8, boxes: u8) -> String {
+        format!(
+            "[{}{}]",
+            "#".repeat(filled as usize),
+            ".".repeat(boxes.saturating_sub(filled) as usize)
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Combatant {
+    pub name: String,
+    #[serde(default)]
+    pub is_player: bool,
+    pub initiative_base: i32,
+    pub initiative_dice: u8,
+    pub initiative: i32,
+    pub condition: ConditionMonitor,
+}
+
+impl Combatant {
+    fn roll_initiative(&mut self) {
+        let mut rng = rand::thread_rng();
+        let dice: i32 = (0..self.initiative_dice)
+            .map(|_| rng.gen_range(1..=6))
+            .sum();
+        self.initiative = self.initiative_base + dice + self.condition.wound_modifier();
+    }
+
+    fn can_act(&self) -> bool {
+        self.initiative > 0 && !self.condition.is_incapacitated()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Encounter {
+    pub combat_turn: u32,
+    pub pass: u32,
+    pub combatants: Vec<Combatant>,
+}
+
+impl Encounter {
+    fn new(combatants: Vec<Combatant>) -> Self {
+        let mut encounter = Encounter {
+            combat_turn: 0,
+            pass: 0,
+            combatants,
+        };
+        encounter.new_combat_turn();
+        encounter
+    }
+
+    fn new_combat_turn(&mut self) {
+        self.combat_turn += 1;
+        self.pass = 1;
+        for combatant in &mut self.combatants {
+            combatant.roll_initiative();
+        }
+        self.sort();
+    }
+
+    /// Every initiative pass costs 10 points; once nobody is left above 0 a new combat turn starts.
+    fn next_pass(&mut self) {
+        for combatant in &mut self.combatants {
+            combatant.initiative -= 10;
+        }
+        if self.combatants.iter().any(Combatant::can_act) {
+            self.pass += 1;
+            self.sort();
+        } else {
+            self.new_combat_turn();
+        }
+    }
+
+    fn sort(&mut self) {
+        self.combatants.sort_by_key(|c| Reverse(c.initiative));
+    }
+
+    fn find_mut(&mut self, name: &str) -> Option<&mut Combatant> {
+        self.combatants
+            .iter_mut()
+            .find(|c| c.name.eq_ignore_ascii_case(name))
+    }
+
+    /// The player's condition as the fight tracks it, which the sheet only gets when it ends.
+    pub fn player_condition(&self) -> Option<&ConditionMonitor> {
+        self.combatants
+            .iter()
+            .find(|c| c.is_player)
+            .map(|c| &c.condition)
+    }
+
+    /// Applies damage the player took outside the combat tools, such as Drain.
+    pub fn damage_player(&mut self, amount: u8, damage_type: DamageType) {
+        if let Some(player) = self.combatants.iter_mut().find(|c| c.is_player) {
+            player.condition.apply(amount, damage_type);
+        }
+    }
+
+    /// Heals the player's combatant, for healing that comes due during the fight. Returns
+    /// whether the fight has a player to heal.
+    pub fn heal_player(&mut self, amount: u8, damage_type: DamageType) -> bool {
+        match self.combatants.iter_mut().find(|c| c.is_player) {
+            Some(player) => {
+                player.condition.heal(amount, damage_type);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn turn_order(&self) -> Vec<&str> {
+        self.combatants
+            .iter()
+            .filter(|c| c.can_act())
+            .map(|c| c.name.as_str())
+            .collect()
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "combat_turn": self.combat_turn,
+            "initiative_pass": self.pass,
+            "turn_order": self.turn_order(),
+            "combatants": self.combatants.iter().map(|c| json!({
+                "name": c.name,
+                "is_player": c.is_player,
+                "initiative": c.initiative,
+                "physical_damage": format!("{}/{}", c.condition.physical_damage, c.condition.physical_boxes),
+                "stun_damage": format!("{}/{}", c.condition.stun_damage, c.condition.stun_boxes),
+                "overflow": c.condition.overflow,
+                "wound_modifier": c.condition.wound_modifier(),
+                "incapacitated": c.condition.is_incapacitated(),
+            })).collect::<Vec<_>>(),
+        })
+    }
+
+    pub fn status_block(&self) -> String {
+        let name_width = self
+            .combatants
+            .iter()
+            .map(|c| c.name.chars().count())
+            .max()
+            .unwrap_or(0)
+            .max(4);
+
+        let mut lines = vec![format!(
+            "Combat - Turn {}, Pass {}",
+            self.combat_turn, self.pass
+        )];
+        for combatant in &self.combatants {
+            let marker = if combatant.condition.is_incapacitated() {
+                "x"
+            } else if combatant.can_act() {
+                ">"
+            } else {
+                " "
+            };
+            lines.push(format!(
+                "{} {:<width$} Init {:>3}  P{} S{} {:>3}",
+                marker,
+                combatant.name,
+                combatant.initiative,
+                ConditionMonitor::track(
+                    combatant.condition.physical_damage,
+                    combatant.condition.physical_boxes
+                ),
+                ConditionMonitor::track(
+                    combatant.condition.stun_damage,
+                    combatant.condition.stun_boxes
+                ),
+                combatant.condition.wound_modifier(),
+                width = name_width,
+            ));
+        }
+        lines.join("\n")
+    }
+}
+
+#[derive(Deserialize)]
+struct CombatantArgs {
+    name: String,
+    #[serde(default)]
+    is_player: bool,
+    initiative_base: i32,
+    #[serde(default = "default_initiative_dice")]
+    initiative_dice: u8,
+    #[serde(default = "default_attribute")]
+    body: u8,
+    #[serde(default = "default_attribute")]
+    willpower: u8,
+}
+
+fn default_initiative_dice() -> u8 {
+    1
+}
+
+fn default_attribute() -> u8 {
+    3
+}
+
+#[derive(Deserialize)]
+struct StartCombatArgs {
+    combatants: Vec<CombatantArgs>,
+}
+
+#[derive(Deserialize)]
+struct ApplyDamageArgs {
+    name: String,
+    amount: u8,
+    damage_type: DamageType,
+}
+
+/// The player's combatant starts with the damage already on their character sheet.
+pub fn start_combat(
+    combat: &mut Option<Encounter>,
+    character: Option<&CharacterSheet>,
+    args: Value,
+) -> Result<Value, SharadError> {
+    if combat.is_some() {
+        return Err(SharadError::Message(
+            "A combat is already running. End it before starting a new one.".into(),
+        ));
+    }
+    let args: StartCombatArgs = serde_json::from_value(args)?;
+    if args.combatants.is_empty() {
+        return Err(SharadError::Message(
+            "A combat needs at least one combatant.".into(),
+        ));
+    }
+
+    let combatants = args
+        .combatants
+        .into_iter()
+        .map(|c| {
+            let condition = match character {
+                Some(character) if c.is_player => character.condition.clone(),
+                _ => ConditionMonitor::new(c.body, c.willpower),
+            };
+            Combatant {
+                name: c.name,
+                is_player: c.is_player,
+                initiative_base: c.initiative_base,
+                initiative_dice: c.initiative_dice.clamp(1, 5),
+                initiative: 0,
+                condition,
+            }
+        })
+        .collect();
+
+    let encounter = Encounter::new(combatants);
+    let output = encounter.to_json();
+    *combat = Some(encounter);
+    Ok(output)
+}
+
+pub fn apply_damage(combat: &mut Option<Encounter>, args: Value) -> Result<Value, SharadError> {
+    let encounter = active_encounter(combat)?;
+    let args: ApplyDamageArgs = serde_json::from_value(args)?;
+    let combatant = encounter
+        .find_mut(&args.name)
+        .ok_or_else(|| SharadError::Message(format!("No combatant named '{}'.", args.name)))?;
+
+    combatant.condition.apply(args.amount, args.damage_type);
+    Ok(json!({
+        "name": combatant.name,
+        "physical_damage": format!("{}/{}", combatant.condition.physical_damage, combatant.condition.physical_boxes),
+        "stun_damage": format!("{}/{}", combatant.condition.stun_damage, combatant.condition.stun_boxes),
+        "overflow": combatant.condition.overflow,
+        "wound_modifier": combatant.condition.wound_modifier(),
+        "incapacitated": combatant.condition.is_incapacitated(),
+    }))
+}
+
+pub fn next_pass(combat: &mut Option<Encounter>) -> Result<Value, SharadError> {
+    let encounter = active_encounter(combat)?;
+    encounter.next_pass();
+    Ok(encounter.to_json())
+}
+
+/// Damage taken and healed by the player during the fight carries over to their character
+/// sheet, whose boxes may have changed since it started.
+pub fn end_combat(
+    combat: &mut Option<Encounter>,
+    character: Option<&mut CharacterSheet>,
+) -> Result<Value, SharadError> {
+    let encounter = combat
+        .take()
+        .ok_or_else(|| SharadError::Message("No combat is running.".into()))?;
+    if let (Some(character), Some(player)) =
+        (character, encounter.combatants.iter().find(|c| c.is_player))
+    {
+        let condition = &mut character.condition;
+        condition.physical_damage = player
+            .condition
+            .physical_damage
+            .min(condition.physical_boxes);
+        condition.stun_damage = player.condition.stun_damage.min(condition.stun_boxes);
+        condition.overflow = player.condition.overflow;
+    }
+    Ok(json!({
+        "status": "Combat ended",
+        "combat_turns": encounter.combat_turn,
+        "combatants": encounter.to_json()["combatants"],
+    }))
+}
+
+/// Wound modifier of the player's character, from the fight's condition monitor while one is
+/// running.
+pub fn wound_modifier(sheet: &CharacterSheet, combat: Option<&Encounter>) -> i32 {
+    combat
+        .and_then(Encounter::player_condition)
+        .unwrap_or(&sheet.condition)
+        .wound_modifier()
+}
+
+fn active_encounter(combat: &mut Option<Encounter>) -> Result<&mut Encounter, SharadError> {
+    combat.as_mut().ok_or_else(|| {
+        SharadError::Message("No combat is running. Call start_combat first.".into())
+    })
+}
+
+pub fn tool_definitions() -> Vec<FunctionObject> {
+    vec![
+        FunctionObject {
+            name: "start_combat".to_string(),
+            description: Some("Start a combat encounter, rolling initiative for every combatant including the player".to_string()),
+            parameters: Some(json!({
+                "type": "object",
+                "properties": {
+                    "combatants": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "name": { "type": "string" },
+                                "is_player": { "type": "boolean" },
+                                "initiative_base": {
+                                    "type": "integer",
+                                    "description": "Reaction + Intuition, plus any bonus"
+                                },
+                                "initiative_dice": {
+                                    "type": "integer",
+                                    "description": "Number of initiative dice (1 to 5)"
+                                },
+                                "body": { "type": "integer" },
+                                "willpower": { "type": "integer" }
+                            },
+                            "required": ["name", "initiative_base"]
+                        }
+                    }
+                },
+                "required": ["combatants"]
+            })),
+        },
+        FunctionObject {
+            name: "apply_damage".to_string(),
+            description: Some("Apply damage to a combatant's condition monitor after resistance".to_string()),
+            parameters: Some(json!({
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string", "description": "The combatant's name" },
+                    "amount": { "type": "integer", "description": "Boxes of damage after soak" },
+                    "damage_type": { "type": "string", "enum": ["physical", "stun"] }
+                },
+                "required": ["name", "amount", "damage_type"]
+            })),
+        },
+        FunctionObject {
+            name: "next_pass".to_string(),
+            description: Some("Move to the next initiative pass, starting a new combat turn when nobody can act anymore".to_string()),
+            parameters: Some(json!({ "type": "object", "properties": {} })),
+        },
+        FunctionObject {
+            name: "end_combat".to_string(),
+            description: Some("End the current combat encounter".to_string()),
+            parameters: Some(json!({ "type": "object", "properties": {} })),
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn combatant(name: &str, initiative_base: i32) -> Combatant {
+        Combatant {
+            name: name.to_string(),
+            is_player: false,
+            initiative_base,
+            initiative_dice: 1,
+            initiative: 0,
+            condition: ConditionMonitor::new(3, 3),
+        }
+    }
+
+    #[test]
+    fn passes_go_by_initiative_until_nobody_can_act() {
+        // With one die each, initiatives are 31-36, 13-18 and 3-8
+        let mut encounter = Encounter::new(vec![
+            combatant("Ganger", 2),
+            combatant("Kira", 30),
+            combatant("Drone", 12),
+        ]);
+        assert_eq!(encounter.turn_order(), ["Kira", "Drone", "Ganger"]);
+
+        encounter.next_pass();
+        assert_eq!((encounter.combat_turn, encounter.pass), (1, 2));
+        assert_eq!(encounter.turn_order(), ["Kira", "Drone"]);
+        encounter.next_pass();
+        assert_eq!(encounter.turn_order(), ["Kira"]);
+        encounter.next_pass();
+        assert_eq!((encounter.combat_turn, encounter.pass), (1, 4));
+        assert_eq!(encounter.turn_order(), ["Kira"]);
+
+        encounter.next_pass();
+        assert_eq!((encounter.combat_turn, encounter.pass), (2, 1));
+        assert_eq!(encounter.turn_order(), ["Kira", "Drone", "Ganger"]);
+    }
+
+    #[test]
+    fn incapacitated_combatants_lose_their_turn() {
+        let mut encounter = Encounter::new(vec![combatant("Kira", 30), combatant("Drone", 12)]);
+        encounter
+            .find_mut("drone")
+            .unwrap()
+            .condition
+            .apply(10, DamageType::Physical);
+        assert_eq!(encounter.turn_order(), ["Kira"]);
+    }
+
+    #[test]
+    fn damage_overflows_from_stun_to_physical_to_overflow() {
+        // Body 3 and Willpower 3 give 10 boxes on each track
+        let mut condition = ConditionMonitor::new(3, 3);
+        condition.apply(13, DamageType::Stun);
+        assert_eq!((condition.stun_damage, condition.physical_damage), (10, 3));
+        assert_eq!(condition.wound_modifier(), -4);
+
+        condition.apply(9, DamageType::Physical);
+        assert_eq!((condition.physical_damage, condition.overflow), (10, 2));
+        assert!(condition.is_incapacitated());
+
+        // Healing takes off overflow before the physical track
+        condition.heal(3, DamageType::Physical);
+        assert_eq!((condition.physical_damage, condition.overflow), (9, 0));
+    }
+
+    #[test]
+    fn overflow_saturates() {
+        let mut condition = ConditionMonitor::new(1, 1);
+        condition.apply(u8::MAX, DamageType::Physical);
+        condition.apply(u8::MAX, DamageType::Stun);
+        assert_eq!(condition.overflow, u8::MAX);
+    }
+}
@@ -0,0 +1,243 @@
+use crate::assistant::rewind_thread;
+use crate::display::Display;
+use crate::error::SharadError;
+use crate::game_state::GameState;
+use crate::karma::advancement_screen;
+use crate::rules::Character;
+use crate::save::{Save, QUICKSAVE_NAME};
+use crate::shop::shop_screen;
+use crate::tables::TableLibrary;
+use crate::utils::open_image;
+use async_openai::Client;
+use crossterm::style::Color;
+
+pub const COMMANDS: [(&str, &str); 9] = [
+    (
+        "/contacts [name]",
+        "List your contacts and known NPCs, or show one in detail",
+    ),
+    ("/sheet", "Show your character sheet"),
+    ("/advance", "Spend karma on attributes and skills"),
+    ("/shop", "Buy gear from the catalog"),
+    ("/journal", "Show your current runs and past jobs"),
+    ("/table [name]", "List the random tables, or roll on one"),
+    ("/quicksave", "Save to the quicksave slot (F5)"),
+    ("/quickload", "Go back to the last quicksave (F9)"),
+    ("/help", "Show this list"),
+];
+
+/// Handles a `/command` typed in place of a player action. Commands never reach the Game Master.
+pub async fn handle_command(
+    command: &str,
+    save: &mut Save,
+    display: &mut Display,
+) -> Result<(), SharadError> {
+    let (name, argument) = match command.trim().split_once(' ') {
+        Some((name, argument)) => (name, argument.trim()),
+        None => (command.trim(), ""),
+    };
+
+    match name.to_lowercase().as_str() {
+        "contacts" => show_contacts(&save.game_state, argument, display),
+        "sheet" => show_sheet(&save.game_state, display),
+        "advance" => {
+            if advance(&mut save.game_state, display)? {
+                save.write()?;
+            }
+        }
+        "shop" => {
+            if shop_screen(&mut save.game_state, display)? {
+                save.write()?;
+            }
+        }
+        "journal" => show_journal(&save.game_state, display),
+        "table" => roll_table(&mut save.game_state, argument, display),
+        "quicksave" => {
+            save.write_copy(QUICKSAVE_NAME)?;
+            display.print_wrapped("Quicksaved.", Color::Green);
+        }
+        "quickload" => quickload(save, display).await?,
+        "help" => show_help(display),
+        _ => display.print_wrapped(
+            &format!("Unknown command '/{}'. Type /help to list commands.", name),
+            Color::Yellow,
+        ),
+    }
+    Ok(())
+}
+
+/// Replaces the game with the quicksave, which then also becomes the save's own state.
+async fn quickload(save: &mut Save, display: &mut Display) -> Result<(), SharadError> {
+    let mut quicksave = match Save::read(QUICKSAVE_NAME) {
+        Ok(quicksave) => quicksave,
+        Err(_) => {
+            display.print_wrapped(
+                "There is no quicksave yet. Press F5 to make one.",
+                Color::Yellow,
+            );
+            return Ok(());
+        }
+    };
+    if !quicksave.same_game(save) {
+        display.print_wrapped("The quicksave belongs to another game.", Color::Yellow);
+        return Ok(());
+    }
+
+    // Time spent playing past the quicksave still counts
+    quicksave.name = save.name.take();
+    quicksave.metadata.playtime_seconds = save.metadata.playtime_seconds;
+    quicksave.start_session();
+    *save = quicksave;
+    rewind_thread(&Client::new(), save, display).await?;
+    save.write()?;
+    display.print_wrapped("Quickloaded.", Color::Green);
+    if let Some(status) = save.game_state.status_block() {
+        display.print_block(&status, Color::Cyan);
+    }
+    Ok(())
+}
+
+fn show_help(display: &mut Display) {
+    display.print_header("Commands");
+    for (usage, description) in COMMANDS {
+        display.print_wrapped(&format!("{} - {}", usage, description), Color::White);
+    }
+    display.print_separator(Color::Yellow);
+}
+
+fn show_sheet(game_state: &GameState, display: &mut Display) {
+    match &game_state.character {
+        Some(character) => {
+            display.print_header("Character sheet");
+            display.print_block(&game_state.rules().render_sheet(character), Color::Cyan);
+            display.print_separator(Color::Yellow);
+        }
+        None => display.print_wrapped(
+            "This game was started without a character sheet.",
+            Color::Yellow,
+        ),
+    }
+}
+
+/// Opens the karma advancement screen. Returns whether the sheet changed.
+pub fn advance(game_state: &mut GameState, display: &mut Display) -> Result<bool, SharadError> {
+    let date = game_state.clock.describe();
+    match game_state
+        .character
+        .as_mut()
+        .and_then(Character::as_shadowrun_mut)
+    {
+        Some(sheet) => advancement_screen(display, sheet, Some(date)),
+        None => {
+            display.print_wrapped(
+                "Karma advancement needs a Shadowrun character sheet.",
+                Color::Yellow,
+            );
+            Ok(false)
+        }
+    }
+}
+
+fn show_contacts(game_state: &GameState, name: &str, display: &mut Display) {
+    if !name.is_empty() {
+        match game_state.npcs.find(name) {
+            Some(npc) => {
+                display.print_header(&npc.name);
+                display.print_wrapped(&npc.summary(), Color::Green);
+                display.print_wrapped(&npc.description, Color::White);
+                for note in &npc.notes {
+                    display.print_wrapped(&format!("- {}", note), Color::White);
+                }
+                if let Some(portrait) = &npc.portrait {
+                    if let Err(e) = open_image(portrait) {
+                        display.print_wrapped(
+                            &format!("Failed to open portrait: {}", e),
+                            Color::Yellow,
+                        );
+                    }
+                }
+                display.print_separator(Color::Yellow);
+            }
+            None => display.print_wrapped(
+                &format!("You don't know anyone called '{}'.", name),
+                Color::Yellow,
+            ),
+        }
+        return;
+    }
+
+    display.print_header("Contacts");
+    let mut contacts = game_state.npcs.contacts().peekable();
+    if contacts.peek().is_none() {
+        display.print_wrapped("You have no contacts yet.", Color::Yellow);
+    }
+    for npc in contacts {
+        display.print_wrapped(&npc.summary(), Color::Green);
+    }
+
+    let others: Vec<_> = game_state
+        .npcs
+        .npcs
+        .iter()
+        .filter(|npc| !npc.is_contact())
+        .collect();
+    if !others.is_empty() {
+        display.print_separator(Color::Cyan);
+        display.print_wrapped("Other people you've met:", Color::Yellow);
+        for npc in others {
+            display.print_wrapped(&npc.summary(), Color::White);
+        }
+    }
+    display.print_separator(Color::Yellow);
+}
+
+fn show_journal(game_state: &GameState, display: &mut Display) {
+    display.print_header("Journal");
+    let journal = &game_state.journal;
+    if journal.is_empty() {
+        display.print_wrapped("Your journal is empty.", Color::Yellow);
+    }
+    for quest in journal.active() {
+        display.print_wrapped(&quest.describe(), Color::Green);
+        display.print_separator(Color::Cyan);
+    }
+
+    let history: Vec<_> = journal.history().collect();
+    if !history.is_empty() {
+        display.print_wrapped("Past jobs:", Color::Yellow);
+        for quest in history {
+            display.print_wrapped(&quest.describe(), Color::White);
+        }
+    }
+    display.print_separator(Color::Yellow);
+}
+
+fn roll_table(game_state: &mut GameState, name: &str, display: &mut Display) {
+    let library = match TableLibrary::load(&game_state.cartridge) {
+        Ok(library) => library,
+        Err(e) => {
+            display.print_wrapped(&e.to_string(), Color::Red);
+            return;
+        }
+    };
+
+    if name.is_empty() {
+        display.print_header("Random tables");
+        if library.is_empty() {
+            display.print_wrapped("This cartridge has no random tables.", Color::Yellow);
+        }
+        for table in library.tables() {
+            display.print_wrapped(
+                &format!("{} - {}", table.name, table.description),
+                Color::White,
+            );
+        }
+        display.print_separator(Color::Yellow);
+        return;
+    }
+
+    match library.roll(name, &mut game_state.rng) {
+        Ok(result) => display.print_wrapped(&result, Color::Green),
+        Err(e) => display.print_wrapped(&e.to_string(), Color::Yellow),
+    }
+}
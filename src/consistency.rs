@@ -0,0 +1,209 @@
+use crate::combat::Encounter;
+use crate::game_state::GameState;
+use crate::rules::Character;
+use crate::shop::LedgerEntry;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+/// Times the Game Master is asked to fix a contradicting turn before the player is warned instead.
+pub const MAX_REVISIONS: usize = 1;
+
+/// Instructions describing the `state_changes` tag the guard reads from each Game Master turn.
+pub const STATE_CHANGES_INSTRUCTIONS: &str = "Also write a JSON \"state_changes\" tag listing what this turn changed that no tool recorded: \"location\" (where the player is at the end of the turn), \"items_used\" (items consumed, lost or given away), \"items_gained\", \"money\" ({\"amount\", \"reason\"}, negative when the player pays) and \"npcs_present\" (named NPCs in the scene). Leave out anything that did not change.";
+
+#[derive(Deserialize)]
+struct MoneyChange {
+    amount: i64,
+    #[serde(default)]
+    reason: String,
+}
+
+/// Changes the Game Master proposes in the `state_changes` tag of its response.
+#[derive(Deserialize, Default)]
+pub struct StateChanges {
+    #[serde(default)]
+    location: Option<String>,
+    #[serde(default)]
+    items_used: Vec<String>,
+    #[serde(default)]
+    items_gained: Vec<String>,
+    #[serde(default)]
+    money: Option<MoneyChange>,
+    #[serde(default)]
+    npcs_present: Vec<String>,
+}
+
+/// What the game state looked like before the Game Master's turn.
+pub struct TurnStart {
+    elapsed_minutes: u64,
+    incapacitated: bool,
+}
+
+impl TurnStart {
+    pub fn capture(game_state: &GameState) -> Self {
+        // During a fight, wounds are only tracked on the player's combatant
+        let incapacitated = match game_state
+            .combat
+            .as_ref()
+            .and_then(Encounter::player_condition)
+        {
+            Some(condition) => condition.is_incapacitated(),
+            None => game_state
+                .character
+                .as_ref()
+                .is_some_and(Character::is_incapacitated),
+        };
+        TurnStart {
+            elapsed_minutes: game_state.clock.elapsed_minutes,
+            incapacitated,
+        }
+    }
+}
+
+/// Compares the turn's proposed changes with the stored state.
+/// Returns the changes to apply, or every contradiction found.
+pub fn check_turn(
+    game_state: &GameState,
+    start: &TurnStart,
+    response: &Value,
+) -> Result<StateChanges, Vec<String>> {
+    let changes: StateChanges = match response.get("state_changes") {
+        None | Some(Value::Null) => return Ok(StateChanges::default()),
+        Some(changes) => serde_json::from_value(changes.clone())
+            .map_err(|e| vec![format!("The state_changes tag is malformed: {}.", e)])?,
+    };
+    let mut issues = Vec::new();
+
+    let moved = changes.location.as_deref().is_some_and(|location| {
+        game_state
+            .location
+            .as_deref()
+            .is_none_or(|current| !current.eq_ignore_ascii_case(location.trim()))
+    });
+    if moved && game_state.location.is_some() {
+        if game_state.combat.is_some() {
+            issues.push(
+                "The player changed location during a running combat. Keep the scene where it is until the combat has ended."
+                    .to_string(),
+            );
+        }
+        if game_state.clock.elapsed_minutes == start.elapsed_minutes {
+            issues.push(
+                "The player moved to a new location but no time passed. Keep them where they are in this response."
+                    .to_string(),
+            );
+        }
+    }
+
+    if let Some(character) = &game_state.character {
+        let acted = moved || !changes.items_used.is_empty() || !changes.items_gained.is_empty();
+        if start.incapacitated && acted {
+            issues.push(format!(
+                "{} is incapacitated by their wounds and cannot act.",
+                character.name()
+            ));
+        }
+
+        // Each used item must be a different carried item
+        let mut remaining = character.clone();
+        for item in &changes.items_used {
+            match remaining.find_gear(item) {
+                Some(index) => remaining.remove_gear(index),
+                None => issues.push(format!(
+                    "The player does not carry '{}'. Leave it out of this response; their gear is on the character sheet.",
+                    item
+                )),
+            }
+        }
+
+        if let Some(money) = &changes.money {
+            let funds = character.funds();
+            match money.amount.checked_add(funds.into()) {
+                Some(total) if total < 0 => issues.push(format!(
+                    "The player cannot pay {} {}; they only have {}.",
+                    money.amount.unsigned_abs(),
+                    game_state.rules().currency(),
+                    funds
+                )),
+                Some(total) if total <= u32::MAX.into() => {}
+                _ => issues.push(format!(
+                    "A change of {} {} is more money than the game can track.",
+                    money.amount,
+                    game_state.rules().currency()
+                )),
+            }
+        }
+    } else if !changes.items_used.is_empty() || changes.money.is_some() {
+        issues.push("The player has no character sheet to take items or money from.".to_string());
+    }
+
+    for name in &changes.npcs_present {
+        if game_state.npcs.find(name).is_none() {
+            issues.push(format!(
+                "{} has not been introduced. Leave them out of this response, or use an NPC the player already knows.",
+                name
+            ));
+        }
+    }
+
+    if issues.is_empty() {
+        Ok(changes)
+    } else {
+        Err(issues)
+    }
+}
+
+/// Applies changes that passed the check to the stored state.
+pub fn apply_changes(game_state: &mut GameState, changes: StateChanges) {
+    if let Some(location) = changes.location {
+        let location = location.trim().to_string();
+        if !location.is_empty() {
+            game_state.location = Some(location);
+        }
+    }
+    let date = game_state.clock.describe();
+    let Some(character) = &mut game_state.character else {
+        return;
+    };
+
+    for item in &changes.items_used {
+        if let Some(index) = character.find_gear(item) {
+            character.remove_gear(index);
+        }
+    }
+    for item in &changes.items_gained {
+        character.add_gear(item.trim(), 0);
+    }
+    if let Some(money) = changes.money.filter(|money| money.amount != 0) {
+        // check_turn rejected amounts past what the funds can hold
+        let Ok(amount) = u32::try_from(money.amount.unsigned_abs()) else {
+            return;
+        };
+        if money.amount > 0 {
+            character.receive(amount);
+        } else if character.spend(amount).is_err() {
+            return;
+        }
+        let description = match money.reason.trim() {
+            "" if money.amount > 0 => "Received".to_string(),
+            "" => "Paid".to_string(),
+            reason => reason.to_string(),
+        };
+        game_state.ledger.entries.push(LedgerEntry {
+            amount: money.amount,
+            description,
+            date: Some(date),
+        });
+    }
+}
+
+/// Message asking the Game Master to rewrite a turn that contradicts the game state. The
+/// revision runs without tools, since those of the first answer were already applied, so every
+/// issue asks for a change to the narration only.
+pub fn revision_request(issues: &[String]) -> String {
+    json!({
+        "consistency_check": issues,
+        "instructions": "Your last response contradicts the stored game state. The tool calls you made have already been applied and no tools are available now, so fix only the narration: describe what happens within the game state as it is, and leave out anything that would need a tool to record. Rewrite the whole response in the same JSON format, including the reasoning, narration and state_changes tags.",
+    })
+    .to_string()
+}
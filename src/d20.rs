@@ -0,0 +1,869 @@
+use crate::character_creation::{ask_non_empty, create_portrait, PortraitScene};
+use crate::display::Display;
+use crate::error::SharadError;
+use crate::game_state::GameState;
+use crate::intent::{classify, ProposedTest, SkillKeywords};
+use crate::menu::select_from_list;
+use crate::rules::{Character, CharacterCreation, RuleSystem};
+use crate::utils::{d20_roll, RollMode};
+use async_openai::types::FunctionObject;
+use crossterm::style::Color;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+
+pub const ABILITIES: [&str; 6] = [
+    "Strength",
+    "Dexterity",
+    "Constitution",
+    "Intelligence",
+    "Wisdom",
+    "Charisma",
+];
+
+/// Scores assigned to the abilities at creation, in any order.
+pub const STANDARD_ARRAY: [u8; 6] = [15, 14, 13, 12, 10, 8];
+
+/// Skills and the ability they are rolled with.
+pub const SKILLS: [(&str, &str); 18] = [
+    ("Athletics", "Strength"),
+    ("Acrobatics", "Dexterity"),
+    ("Sleight of Hand", "Dexterity"),
+    ("Stealth", "Dexterity"),
+    ("Arcana", "Intelligence"),
+    ("History", "Intelligence"),
+    ("Investigation", "Intelligence"),
+    ("Nature", "Intelligence"),
+    ("Religion", "Intelligence"),
+    ("Animal Handling", "Wisdom"),
+    ("Insight", "Wisdom"),
+    ("Medicine", "Wisdom"),
+    ("Perception", "Wisdom"),
+    ("Survival", "Wisdom"),
+    ("Deception", "Charisma"),
+    ("Intimidation", "Charisma"),
+    ("Performance", "Charisma"),
+    ("Persuasion", "Charisma"),
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum D20Class {
+    Fighter,
+    Rogue,
+    Wizard,
+    Cleric,
+}
+
+impl D20Class {
+    pub const ALL: [D20Class; 4] = [
+        D20Class::Fighter,
+        D20Class::Rogue,
+        D20Class::Wizard,
+        D20Class::Cleric,
+    ];
+
+    pub fn hit_die(&self) -> i32 {
+        match self {
+            D20Class::Fighter => 10,
+            D20Class::Rogue | D20Class::Cleric => 8,
+            D20Class::Wizard => 6,
+        }
+    }
+
+    pub fn skill_choices(&self) -> usize {
+        match self {
+            D20Class::Rogue => 4,
+            _ => 2,
+        }
+    }
+
+    /// Armor class from starting armor, before the Dexterity modifier where it applies.
+    fn armor_class(&self, dexterity_modifier: i32) -> i32 {
+        match self {
+            D20Class::Fighter => 16,
+            D20Class::Cleric => 14 + dexterity_modifier.min(2),
+            D20Class::Rogue => 11 + dexterity_modifier,
+            D20Class::Wizard => 10 + dexterity_modifier,
+        }
+    }
+
+    fn starting_gear(&self) -> Vec<String> {
+        let gear: &[&str] = match self {
+            D20Class::Fighter => &["Chain mail", "Longsword", "Shield", "Explorer's pack"],
+            D20Class::Rogue => &["Leather armor", "Shortsword", "Shortbow", "Thieves' tools"],
+            D20Class::Wizard => &["Quarterstaff", "Spellbook", "Component pouch"],
+            D20Class::Cleric => &["Scale mail", "Mace", "Holy symbol", "Priest's pack"],
+        };
+        gear.iter().map(|item| item.to_string()).collect()
+    }
+}
+
+impl fmt::Display for D20Class {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct D20Sheet {
+    pub name: String,
+    pub class: D20Class,
+    pub level: u8,
+    #[serde(default)]
+    pub background: String,
+    pub abilities: BTreeMap<String, u8>,
+    #[serde(default)]
+    pub proficiencies: Vec<String>,
+    pub hit_points: i32,
+    pub max_hit_points: i32,
+    pub armor_class: i32,
+    #[serde(default)]
+    pub gold: u32,
+    #[serde(default)]
+    pub gear: Vec<String>,
+    #[serde(default)]
+    pub portrait: Option<String>,
+}
+
+impl D20Sheet {
+    pub fn modifier(&self, ability: &str) -> i32 {
+        let score = self
+            .abilities
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(ability))
+            .map(|(_, &score)| score as i32)
+            .unwrap_or(10);
+        (score - 10).div_euclid(2)
+    }
+
+    pub fn proficiency_bonus(&self) -> i32 {
+        2 + (self.level.max(1) as i32 - 1) / 4
+    }
+
+    pub fn is_proficient(&self, skill: &str) -> bool {
+        self.proficiencies
+            .iter()
+            .any(|proficiency| proficiency.eq_ignore_ascii_case(skill))
+    }
+
+    pub fn render(&self) -> String {
+        let mut lines = vec![
+            format!("{} - level {} {}", self.name, self.level, self.class),
+            String::new(),
+        ];
+        for ability in ABILITIES {
+            lines.push(format!(
+                "{:<13} {:>2} ({:+})",
+                ability,
+                self.abilities.get(ability).copied().unwrap_or(10),
+                self.modifier(ability)
+            ));
+        }
+        lines.push(String::new());
+        lines.push(format!(
+            "Hit points  {}/{}    Armor class {}",
+            self.hit_points, self.max_hit_points, self.armor_class
+        ));
+        lines.push(format!("Proficiency {:+}", self.proficiency_bonus()));
+        if !self.proficiencies.is_empty() {
+            lines.push(format!("Skills      {}", self.proficiencies.join(", ")));
+        }
+        lines.push(format!("Gold        {}", self.gold));
+        if !self.gear.is_empty() {
+            lines.push("Gear".to_string());
+            for item in &self.gear {
+                lines.push(format!("  {}", item));
+            }
+        }
+        lines.join("\n")
+    }
+}
+
+fn skill_ability(skill: &str) -> Option<&'static str> {
+    SKILLS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(skill))
+        .map(|(_, ability)| *ability)
+}
+
+/// Skills the player's actions are classified into.
+const INTENT_KEYWORDS: &SkillKeywords = &[
+    (
+        "Athletics",
+        &[
+            "climb",
+            "climbs",
+            "climbing",
+            "jump",
+            "jumps",
+            "jumping",
+            "leap",
+            "leaps",
+            "leaping",
+            "swim",
+            "swims",
+            "swimming",
+            "shove",
+            "shoves",
+            "grapple",
+            "grapples",
+            "grappling",
+            "grimpe",
+            "grimper",
+            "saute",
+            "sauter",
+            "nage",
+            "nager",
+            "escalade",
+            "escalader",
+        ],
+    ),
+    (
+        "Acrobatics",
+        &[
+            "tumble",
+            "tumbles",
+            "balance",
+            "flip",
+            "flips",
+            "roll away",
+            "acrobatics",
+            "acrobatie",
+            "équilibre",
+        ],
+    ),
+    (
+        "Sleight of Hand",
+        &[
+            "pickpocket",
+            "pickpockets",
+            "steal",
+            "steals",
+            "stealing",
+            "palm",
+            "palms",
+            "lockpick",
+            "lockpicks",
+            "pick the lock",
+            "crochète",
+            "crocheter",
+            "serrure",
+            "vole",
+            "dérobe",
+            "dérober",
+            "subtilise",
+            "subtiliser",
+        ],
+    ),
+    (
+        "Stealth",
+        &[
+            "sneak",
+            "sneaks",
+            "sneaking",
+            "snuck",
+            "hide",
+            "hides",
+            "hiding",
+            "stealth",
+            "stealthily",
+            "creep",
+            "creeps",
+            "creeping",
+            "tiptoe",
+            "tiptoes",
+            "tiptoeing",
+            "faufile",
+            "faufiler",
+            "cache",
+            "cacher",
+            "discrètement",
+            "furtivement",
+        ],
+    ),
+    (
+        "Arcana",
+        &[
+            "arcane",
+            "magic",
+            "rune",
+            "runes",
+            "enchant",
+            "enchants",
+            "enchantment",
+            "magie",
+        ],
+    ),
+    (
+        "History",
+        &[
+            "history",
+            "legend",
+            "legends",
+            "recall",
+            "histoire",
+            "légende",
+            "légendes",
+        ],
+    ),
+    (
+        "Investigation",
+        &[
+            "investigate",
+            "investigates",
+            "investigating",
+            "deduce",
+            "clue",
+            "clues",
+            "enquête",
+            "enquêter",
+            "indice",
+            "indices",
+        ],
+    ),
+    (
+        "Nature",
+        &[
+            "plant", "plants", "herb", "herbs", "beast", "beasts", "weather", "plante", "plantes",
+            "herbe", "herbes",
+        ],
+    ),
+    (
+        "Religion",
+        &[
+            "pray", "prays", "praying", "deity", "temple", "ritual", "prie", "prier", "dieu",
+            "rituel",
+        ],
+    ),
+    (
+        "Animal Handling",
+        &[
+            "tame",
+            "tames",
+            "calm the",
+            "ride",
+            "rides",
+            "riding",
+            "soothe",
+            "soothes",
+            "apprivoise",
+            "apprivoiser",
+            "chevauche",
+            "chevaucher",
+        ],
+    ),
+    (
+        "Insight",
+        &[
+            "insight",
+            "sense motive",
+            "suspect",
+            "suspects",
+            "intention",
+            "intentions",
+            "lis ses",
+            "devine",
+            "deviner",
+        ],
+    ),
+    (
+        "Medicine",
+        &[
+            "bandage",
+            "bandages",
+            "heal",
+            "heals",
+            "healing",
+            "treat",
+            "treats",
+            "treating",
+            "stabilize",
+            "stabilizes",
+            "soigne",
+            "soigner",
+            "stabilise",
+            "stabiliser",
+        ],
+    ),
+    (
+        "Perception",
+        &[
+            "search",
+            "searches",
+            "searching",
+            "look around",
+            "listen",
+            "listens",
+            "listening",
+            "notice",
+            "notices",
+            "examine",
+            "examines",
+            "examining",
+            "inspect",
+            "inspects",
+            "inspecting",
+            "observe",
+            "observes",
+            "observing",
+            "fouille",
+            "fouiller",
+            "écoute",
+            "écouter",
+            "regarde",
+            "regarder",
+        ],
+    ),
+    (
+        "Survival",
+        &[
+            "track",
+            "tracks",
+            "tracking",
+            "forage",
+            "forages",
+            "foraging",
+            "hunt",
+            "hunts",
+            "hunting",
+            "navigate",
+            "navigates",
+            "piste",
+            "pister",
+            "traque",
+            "traquer",
+            "chasse",
+            "chasser",
+        ],
+    ),
+    (
+        "Deception",
+        &[
+            "lie",
+            "lies",
+            "lying",
+            "bluff",
+            "bluffs",
+            "bluffing",
+            "trick",
+            "tricks",
+            "tricking",
+            "disguise",
+            "disguises",
+            "pretend",
+            "pretends",
+            "pretending",
+            "mens",
+            "mentir",
+            "déguise",
+            "déguiser",
+            "ruse",
+        ],
+    ),
+    (
+        "Intimidation",
+        &[
+            "intimidate",
+            "intimidates",
+            "intimidating",
+            "threaten",
+            "threatens",
+            "threatening",
+            "menace",
+            "menacer",
+            "intimide",
+            "intimider",
+        ],
+    ),
+    (
+        "Performance",
+        &[
+            "perform",
+            "performs",
+            "performing",
+            "sing",
+            "sings",
+            "singing",
+            "dance",
+            "dances",
+            "dancing",
+            "play the",
+            "chante",
+            "chanter",
+            "danse",
+            "danser",
+        ],
+    ),
+    (
+        "Persuasion",
+        &[
+            "persuade",
+            "persuades",
+            "persuading",
+            "convince",
+            "convinces",
+            "convincing",
+            "negotiate",
+            "negotiates",
+            "negotiating",
+            "haggle",
+            "haggles",
+            "haggling",
+            "bargain",
+            "bargains",
+            "bargaining",
+            "persuader",
+            "convaincs",
+            "convaincre",
+            "négocie",
+            "négocier",
+            "marchande",
+            "marchander",
+        ],
+    ),
+];
+
+/// A lightweight d20 system: ability checks against a difficulty class, hit points and armor class.
+pub struct D20;
+
+#[derive(Deserialize)]
+struct RollCheckArgs {
+    ability: Option<String>,
+    skill: Option<String>,
+    #[serde(default)]
+    modifier: i32,
+    difficulty: i32,
+    #[serde(default)]
+    advantage: RollMode,
+}
+
+#[derive(Deserialize)]
+struct AdjustHitPointsArgs {
+    amount: i32,
+}
+
+impl RuleSystem for D20 {
+    fn name(&self) -> &'static str {
+        "d20"
+    }
+
+    fn character_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string" },
+                "class": { "type": "string", "enum": ["Fighter", "Rogue", "Wizard", "Cleric"] },
+                "level": { "type": "integer" },
+                "background": { "type": "string" },
+                "abilities": {
+                    "type": "object",
+                    "description": "Ability scores; the modifier is (score - 10) / 2 rounded down",
+                    "additionalProperties": { "type": "integer" }
+                },
+                "proficiencies": {
+                    "type": "array",
+                    "description": "Skills that add the proficiency bonus",
+                    "items": { "type": "string" }
+                },
+                "hit_points": { "type": "integer" },
+                "max_hit_points": { "type": "integer" },
+                "armor_class": { "type": "integer" },
+                "gold": { "type": "integer" },
+                "gear": { "type": "array", "items": { "type": "string" } }
+            }
+        })
+    }
+
+    fn create_character<'a>(&'a self, display: &'a mut Display) -> CharacterCreation<'a> {
+        Box::pin(async move {
+            let Some(mut sheet) = build_sheet(display)? else {
+                return Ok(None);
+            };
+
+            let portrait_prompt = format!(
+                "Character portrait of a {} adventurer in a heroic fantasy setting.",
+                sheet.class.to_string().to_lowercase()
+            );
+            let scene = PortraitScene {
+                location: "a torch-lit tavern",
+                ambiance: "heroic fantasy",
+                environment: "stone walls, wooden beams and flickering candles",
+            };
+            sheet.portrait = create_portrait(display, &sheet.name, portrait_prompt, scene).await?;
+
+            Ok(Some(Character::D20(sheet)))
+        })
+    }
+
+    fn resolve_test(
+        &self,
+        character: Option<&Character>,
+        args: Value,
+    ) -> Result<Value, SharadError> {
+        let args: RollCheckArgs = serde_json::from_value(args)?;
+        let sheet = match character {
+            Some(Character::D20(sheet)) => Some(sheet),
+            _ => None,
+        };
+
+        let ability = match (&args.ability, &args.skill) {
+            (Some(ability), _) => Some(ability.as_str()),
+            (None, Some(skill)) => Some(
+                skill_ability(skill)
+                    .ok_or_else(|| SharadError::Message(format!("Unknown skill '{}'.", skill)))?,
+            ),
+            (None, None) => None,
+        };
+        let mut modifier = args.modifier;
+        if let Some(sheet) = sheet {
+            modifier += ability.map_or(0, |ability| sheet.modifier(ability));
+            if args
+                .skill
+                .as_deref()
+                .is_some_and(|skill| sheet.is_proficient(skill))
+            {
+                modifier += sheet.proficiency_bonus();
+            }
+        }
+
+        Ok(json!(d20_roll(modifier, args.difficulty, args.advantage)))
+    }
+
+    /// Ability modifier plus proficiency. The Game Master sets the difficulty class, so the
+    /// result only holds the rolled total.
+    fn propose_test(&self, game_state: &GameState, action: &str) -> Option<ProposedTest> {
+        let Some(Character::D20(sheet)) = &game_state.character else {
+            return None;
+        };
+        let skill = classify(action, INTENT_KEYWORDS)?;
+        let ability = skill_ability(skill)?;
+        let proficient = sheet.is_proficient(skill);
+        let mut modifier = sheet.modifier(ability);
+        if proficient {
+            modifier += sheet.proficiency_bonus();
+        }
+
+        let mut roll = json!(d20_roll(modifier, 0, RollMode::Normal));
+        if let Some(roll) = roll.as_object_mut() {
+            roll.remove("difficulty");
+            roll.remove("is_successful");
+        }
+        let summary = format!(
+            "{} ({}): d20 {:+} -> {}",
+            skill, ability, modifier, roll["total"]
+        );
+        Some(ProposedTest {
+            summary,
+            result: json!({
+                "skill": skill,
+                "ability": ability,
+                "proficient": proficient,
+                "roll": roll,
+            }),
+        })
+    }
+
+    /// Persuasion check against DC 10 + Availability.
+    fn availability_test(&self, character: Option<&Character>, availability: u8) -> Value {
+        let modifier = match character {
+            Some(Character::D20(sheet)) if sheet.is_proficient("Persuasion") => {
+                sheet.modifier("Charisma") + sheet.proficiency_bonus()
+            }
+            Some(Character::D20(sheet)) => sheet.modifier("Charisma"),
+            _ => 0,
+        };
+        json!(d20_roll(
+            modifier,
+            10 + availability as i32,
+            RollMode::Normal
+        ))
+    }
+
+    fn currency(&self) -> &'static str {
+        "gold"
+    }
+
+    fn tool_definitions(&self) -> Vec<FunctionObject> {
+        vec![
+            FunctionObject {
+                name: "roll_check".to_string(),
+                description: Some(
+                    "Roll a d20 check for the player character against a difficulty class. Ability and proficiency modifiers are added from the character sheet"
+                        .to_string(),
+                ),
+                parameters: Some(json!({
+                    "type": "object",
+                    "properties": {
+                        "ability": {
+                            "type": "string",
+                            "enum": ABILITIES,
+                            "description": "Ability to roll, if not implied by the skill"
+                        },
+                        "skill": {
+                            "type": "string",
+                            "enum": SKILLS.iter().map(|(skill, _)| *skill).collect::<Vec<_>>()
+                        },
+                        "modifier": {
+                            "type": "integer",
+                            "description": "Situational bonus or penalty"
+                        },
+                        "difficulty": {
+                            "type": "integer",
+                            "description": "Difficulty class: 10 easy, 15 medium, 20 hard, 25 very hard"
+                        },
+                        "advantage": {
+                            "type": "string",
+                            "enum": ["normal", "advantage", "disadvantage"]
+                        }
+                    },
+                    "required": ["difficulty"]
+                })),
+            },
+            FunctionObject {
+                name: "adjust_hit_points".to_string(),
+                description: Some(
+                    "Apply damage (negative amount) or healing (positive amount) to the player character"
+                        .to_string(),
+                ),
+                parameters: Some(json!({
+                    "type": "object",
+                    "properties": {
+                        "amount": { "type": "integer" }
+                    },
+                    "required": ["amount"]
+                })),
+            },
+        ]
+    }
+
+    fn handle_tool_call(
+        &self,
+        name: &str,
+        args: Value,
+        game_state: &mut GameState,
+    ) -> Option<Result<Value, SharadError>> {
+        let result = match name {
+            "roll_check" => self.resolve_test(game_state.character.as_ref(), args),
+            "adjust_hit_points" => adjust_hit_points(game_state, args),
+            _ => return None,
+        };
+        Some(result)
+    }
+
+    fn gm_instructions(&self) -> &'static str {
+        "Resolve uncertain actions with roll_check against a difficulty class, and track wounds and healing with adjust_hit_points."
+    }
+
+    fn render_sheet(&self, character: &Character) -> String {
+        match character {
+            Character::D20(sheet) => sheet.render(),
+            other => serde_json::to_string_pretty(other).unwrap_or_default(),
+        }
+    }
+}
+
+fn adjust_hit_points(game_state: &mut GameState, args: Value) -> Result<Value, SharadError> {
+    let args: AdjustHitPointsArgs = serde_json::from_value(args)?;
+    let sheet = game_state
+        .character
+        .as_mut()
+        .and_then(Character::as_d20_mut)
+        .ok_or_else(|| SharadError::Message("The player has no d20 character sheet.".into()))?;
+
+    sheet.hit_points = (sheet.hit_points + args.amount).clamp(0, sheet.max_hit_points);
+    Ok(json!({
+        "hit_points": sheet.hit_points,
+        "max_hit_points": sheet.max_hit_points,
+        "unconscious": sheet.hit_points == 0,
+    }))
+}
+
+fn build_sheet(display: &mut Display) -> Result<Option<D20Sheet>, SharadError> {
+    loop {
+        let Some(name) = ask_non_empty(display, "What is your character's name?")? else {
+            return Ok(None);
+        };
+
+        let items: Vec<String> = D20Class::ALL
+            .iter()
+            .map(|class| format!("{} (d{} hit die)", class, class.hit_die()))
+            .collect();
+        let Some(index) = select_from_list(display, "Choose your class", &items)? else {
+            return Ok(None);
+        };
+        let class = D20Class::ALL[index];
+
+        let mut remaining: Vec<&str> = ABILITIES.to_vec();
+        let mut abilities = BTreeMap::new();
+        for score in STANDARD_ARRAY {
+            let items: Vec<String> = remaining.iter().map(|a| a.to_string()).collect();
+            let Some(index) = select_from_list(
+                display,
+                &format!("Which ability gets a score of {}?", score),
+                &items,
+            )?
+            else {
+                return Ok(None);
+            };
+            abilities.insert(remaining.remove(index).to_string(), score);
+        }
+
+        let mut remaining: Vec<&str> = SKILLS.iter().map(|(skill, _)| *skill).collect();
+        let mut proficiencies = Vec::new();
+        for pick in 1..=class.skill_choices() {
+            let items: Vec<String> = remaining.iter().map(|s| s.to_string()).collect();
+            let Some(index) = select_from_list(
+                display,
+                &format!(
+                    "Choose a skill proficiency ({} of {})",
+                    pick,
+                    class.skill_choices()
+                ),
+                &items,
+            )?
+            else {
+                return Ok(None);
+            };
+            proficiencies.push(remaining.remove(index).to_string());
+        }
+
+        let Some(background) = ask_non_empty(
+            display,
+            "Describe your character's background and motivations:",
+        )?
+        else {
+            return Ok(None);
+        };
+
+        let mut sheet = D20Sheet {
+            name,
+            class,
+            level: 1,
+            background,
+            abilities,
+            proficiencies,
+            hit_points: 0,
+            max_hit_points: 0,
+            armor_class: 0,
+            gold: 10,
+            gear: class.starting_gear(),
+            portrait: None,
+        };
+        sheet.max_hit_points = (class.hit_die() + sheet.modifier("Constitution")).max(1);
+        sheet.hit_points = sheet.max_hit_points;
+        sheet.armor_class = class.armor_class(sheet.modifier("Dexterity"));
+
+        display.print_header("Your character");
+        display.print_block(&sheet.render(), Color::Cyan);
+        match display
+            .get_user_input("Play this character? (y/n)")?
+            .map(|s| s.to_lowercase())
+            .as_deref()
+        {
+            Some("y") | Some("yes") => return Ok(Some(sheet)),
+            Some(_) => display.print_wrapped("Let's start over.", Color::Yellow),
+            None => return Ok(None),
+        }
+    }
+}
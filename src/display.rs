@@ -1,4 +1,3 @@
-use crate::settings::load_settings;
 use std::io::Write;
 use textwrap::wrap;
 use unicode_width::UnicodeWidthStr;
@@ -14,6 +13,7 @@ use crossterm::{
 use std::io;
 use std::io::stdout;
 use std::time::Duration;
+use tracing::{debug, Level};
 
 #[derive(Clone)]
 pub struct Display {
@@ -178,11 +178,11 @@ impl Display {
         println!();
     }
 
+    /// Logs a debug message, and shows it too when the log filter lets debug events through.
     pub fn print_debug(&mut self, text: &str, color: Color) {
-        if let Ok(settings) = load_settings() {
-            if settings.debug_mode {
-                self.print_wrapped(text, color);
-            }
+        debug!("{}", text);
+        if tracing::enabled!(Level::DEBUG) {
+            self.print_wrapped(text, color);
         }
     }
 
@@ -303,6 +303,32 @@ impl Display {
         }
     }
 
+    /// Prints preformatted lines as a centered block, keeping their alignment intact.
+    pub fn print_block(&mut self, text: &str, color: Color) {
+        self.update_dimensions();
+        let lines: Vec<&str> = text.lines().collect();
+        let block_width = lines
+            .iter()
+            .map(|line| UnicodeWidthStr::width(*line))
+            .max()
+            .unwrap_or(0);
+        let padding = self.term_width.saturating_sub(block_width) / 2;
+
+        let start_y = self.ensure_space_for_lines(lines.len());
+        for (i, line) in lines.iter().enumerate() {
+            if execute!(
+                stdout(),
+                cursor::MoveTo(padding as u16, start_y + i as u16),
+                SetForegroundColor(color)
+            )
+            .is_ok()
+            {
+                println!("{}", line);
+                let _ = execute!(stdout(), ResetColor);
+            }
+        }
+    }
+
     fn apply_basic_formatting(&self, line: &str) -> String {
         let mut result = String::new();
         let mut chars = line.chars().peekable();
@@ -0,0 +1,539 @@
+use crate::archive::ZipWriter;
+use crate::error::SharadError;
+use crate::paths::export_dir;
+use crate::save::Save;
+use crate::transcript::{self, Role};
+use chrono::{DateTime, Duration, Local, Utc};
+use serde_json::Value;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A pause this long between two messages starts a new chapter.
+const SESSION_GAP_HOURS: i64 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Markdown,
+    Html,
+    Epub,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 3] = [
+        ExportFormat::Markdown,
+        ExportFormat::Html,
+        ExportFormat::Epub,
+    ];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "md",
+            ExportFormat::Html => "html",
+            ExportFormat::Epub => "epub",
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let label = match self {
+            ExportFormat::Markdown => "Markdown",
+            ExportFormat::Html => "HTML",
+            ExportFormat::Epub => "EPUB",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+enum Passage {
+    Action(String),
+    /// The test rolled for an action, or the Game Master's reasoning about the dice.
+    Dice(String),
+    Narration(String),
+}
+
+struct Chapter {
+    /// Day the session was played.
+    date: String,
+    passages: Vec<Passage>,
+}
+
+/// A character with a generated portrait.
+struct Portrait {
+    name: String,
+    description: String,
+    path: String,
+}
+
+struct Story {
+    title: String,
+    subtitle: String,
+    /// Name given to the player's actions.
+    actor: String,
+    language: &'static str,
+    cast: Vec<Portrait>,
+    chapters: Vec<Chapter>,
+}
+
+/// BCP 47 tag for the language setting, which players type freely.
+fn language_tag(language: &str) -> &'static str {
+    const TAGS: [(&str, &[&str]); 6] = [
+        ("en", &["english", "anglais"]),
+        ("fr", &["français", "francais", "french"]),
+        ("de", &["deutsch", "german", "allemand"]),
+        ("es", &["español", "espanol", "spanish", "espagnol"]),
+        ("it", &["italiano", "italian", "italien"]),
+        ("pt", &["português", "portugues", "portuguese", "portugais"]),
+    ];
+    let language = language.trim().to_lowercase();
+    TAGS.iter()
+        .find(|(_, names)| names.contains(&language.as_str()))
+        .map(|(tag, _)| *tag)
+        .unwrap_or("en")
+}
+
+fn passages(role: Role, content: &str, text: Option<String>, include_dice: bool) -> Vec<Passage> {
+    let json = serde_json::from_str::<Value>(content).ok();
+    let dice = json.as_ref().and_then(|json| match role {
+        Role::User => json.get("proposed_test")?.get("summary")?.as_str(),
+        Role::Assistant => json.get("reasoning")?.as_str(),
+    });
+
+    let mut passages = Vec::new();
+    match (role, text) {
+        (Role::User, Some(action)) => {
+            passages.push(Passage::Action(action));
+            if let Some(dice) = dice.filter(|_| include_dice) {
+                passages.push(Passage::Dice(dice.to_string()));
+            }
+        }
+        (Role::Assistant, Some(narration)) => {
+            if let Some(dice) = dice.filter(|_| include_dice) {
+                passages.push(Passage::Dice(dice.to_string()));
+            }
+            passages.push(Passage::Narration(narration));
+        }
+        (_, None) => {}
+    }
+    passages
+}
+
+fn story(save: &Save, language: &str, include_dice: bool) -> Result<Story, SharadError> {
+    let entries = transcript::read(&save.thread_id).map_err(|_| {
+        SharadError::Message(
+            "This save has no local transcript yet. Load it once to fetch its history.".into(),
+        )
+    })?;
+    let entries = transcript::until(entries, save.last_message_id.as_deref());
+
+    let mut chapters: Vec<Chapter> = Vec::new();
+    let mut last_message: Option<DateTime<Local>> = None;
+    for entry in &entries {
+        let timestamp = DateTime::parse_from_rfc3339(&entry.timestamp)
+            .ok()
+            .map(|timestamp| timestamp.with_timezone(&Local));
+        let new_session = match (last_message, timestamp) {
+            (Some(last), Some(timestamp)) => timestamp - last > Duration::hours(SESSION_GAP_HOURS),
+            _ => false,
+        };
+        last_message = timestamp.or(last_message);
+
+        let passages = passages(entry.role, &entry.content, entry.text(), include_dice);
+        if passages.is_empty() {
+            continue;
+        }
+        if new_session || chapters.is_empty() {
+            chapters.push(Chapter {
+                date: timestamp
+                    .map(|timestamp| timestamp.format("%Y-%m-%d").to_string())
+                    .unwrap_or_default(),
+                passages: Vec::new(),
+            });
+        }
+        if let Some(chapter) = chapters.last_mut() {
+            chapter.passages.extend(passages);
+        }
+    }
+    if chapters.is_empty() {
+        return Err(SharadError::Message(
+            "There is nothing to export yet.".into(),
+        ));
+    }
+
+    let game_state = &save.game_state;
+    let character = game_state.character.as_ref();
+    let mut cast: Vec<Portrait> = character
+        .and_then(|character| {
+            Some(Portrait {
+                name: character.name().to_string(),
+                description: String::new(),
+                path: character.portrait()?.to_string(),
+            })
+        })
+        .into_iter()
+        .collect();
+    cast.extend(game_state.npcs.npcs.iter().filter_map(|npc| {
+        Some(Portrait {
+            name: npc.name.clone(),
+            description: npc.description.clone(),
+            path: npc.portrait.clone()?,
+        })
+    }));
+    // Portraits whose file is gone are left out rather than shown broken
+    cast.retain(|portrait| Path::new(&portrait.path).is_file());
+
+    let actor = character
+        .map(|character| character.name().to_string())
+        .unwrap_or_else(|| "You".to_string());
+    Ok(Story {
+        title: save
+            .name
+            .clone()
+            .unwrap_or_else(|| game_state.cartridge.name.clone()),
+        subtitle: if game_state.cartridge.name.is_empty() {
+            actor.clone()
+        } else {
+            format!("{} - {}", actor, game_state.cartridge.name)
+        },
+        actor,
+        language: language_tag(language),
+        cast,
+        chapters,
+    })
+}
+
+/// Writes the save's story to the export directory and returns the file written.
+pub fn export_save(
+    save: &Save,
+    format: ExportFormat,
+    language: &str,
+    include_dice: bool,
+) -> Result<PathBuf, SharadError> {
+    let story = story(save, language, include_dice)?;
+    let contents = match format {
+        ExportFormat::Markdown => markdown(&story).into_bytes(),
+        ExportFormat::Html => html(&story)?.into_bytes(),
+        ExportFormat::Epub => epub(&story)?,
+    };
+
+    fs::create_dir_all(export_dir())?;
+    let path = export_dir().join(format!("{}.{}", story.title, format.extension()));
+    fs::write(&path, contents)?;
+    Ok(path)
+}
+
+fn paragraphs(text: &str) -> impl Iterator<Item = &str> {
+    text.lines().map(str::trim).filter(|line| !line.is_empty())
+}
+
+fn chapter_title(index: usize, chapter: &Chapter) -> String {
+    if chapter.date.is_empty() {
+        format!("Chapter {}", index + 1)
+    } else {
+        format!("Chapter {} - {}", index + 1, chapter.date)
+    }
+}
+
+fn markdown(story: &Story) -> String {
+    let mut markdown = format!("# {}\n\n*{}*\n\n", story.title, story.subtitle);
+
+    if !story.cast.is_empty() {
+        markdown.push_str("## Cast\n\n");
+        for portrait in &story.cast {
+            // Absolute paths, so the images still show wherever the file is opened from
+            let path = fs::canonicalize(&portrait.path)
+                .map(|path| path.display().to_string())
+                .unwrap_or_else(|_| portrait.path.clone());
+            markdown.push_str(&format!("![{}]({})\n\n", portrait.name, path));
+            if portrait.description.is_empty() {
+                markdown.push_str(&format!("**{}**\n\n", portrait.name));
+            } else {
+                markdown.push_str(&format!(
+                    "**{}** - {}\n\n",
+                    portrait.name, portrait.description
+                ));
+            }
+        }
+    }
+
+    for (index, chapter) in story.chapters.iter().enumerate() {
+        markdown.push_str(&format!("## {}\n\n", chapter_title(index, chapter)));
+        for passage in &chapter.passages {
+            match passage {
+                Passage::Action(action) => {
+                    for line in paragraphs(action) {
+                        markdown.push_str(&format!("> **{}:** *{}*\n", story.actor, line));
+                    }
+                    markdown.push('\n');
+                }
+                Passage::Dice(dice) => {
+                    for line in paragraphs(dice) {
+                        markdown.push_str(&format!("`{}`\n\n", line.replace('`', "'")));
+                    }
+                }
+                Passage::Narration(narration) => {
+                    for line in paragraphs(narration) {
+                        markdown.push_str(line);
+                        markdown.push_str("\n\n");
+                    }
+                }
+            }
+        }
+    }
+    markdown
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+const STYLESHEET: &str = "body { font-family: Georgia, serif; max-width: 40em; margin: 2em auto; padding: 0 1em; line-height: 1.6; color: #222; }
+h1, h2 { font-family: Helvetica, Arial, sans-serif; }
+.subtitle { font-style: italic; color: #555; }
+.cast figure { display: flex; align-items: center; gap: 1em; margin: 1em 0; }
+.cast img { width: 8em; height: 8em; object-fit: cover; border-radius: 0.5em; }
+.action { margin-left: 1.5em; padding-left: 0.8em; border-left: 3px solid #3a6ea5; color: #234; font-style: italic; }
+.actor { font-weight: bold; font-style: normal; }
+.dice { font-family: monospace; font-size: 0.85em; color: #666; background: #f3f3f3; padding: 0.3em 0.6em; }
+";
+
+fn media_type(path: &str) -> &'static str {
+    match Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_lowercase)
+        .as_deref()
+    {
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("webp") => "image/webp",
+        Some("gif") => "image/gif",
+        _ => "image/png",
+    }
+}
+
+/// XHTML for the cast, valid in both HTML and EPUB. `source` gives each image's `src`.
+fn cast_body(story: &Story, source: &dyn Fn(usize) -> String) -> String {
+    if story.cast.is_empty() {
+        return String::new();
+    }
+    let mut body = String::from("<section class=\"cast\">\n<h2>Cast</h2>\n");
+    for (index, portrait) in story.cast.iter().enumerate() {
+        body.push_str(&format!(
+            "<figure><img src=\"{}\" alt=\"{}\" /><figcaption><strong>{}</strong> {}</figcaption></figure>\n",
+            source(index),
+            escape(&portrait.name),
+            escape(&portrait.name),
+            escape(&portrait.description)
+        ));
+    }
+    body.push_str("</section>\n");
+    body
+}
+
+fn chapter_body(story: &Story, index: usize, chapter: &Chapter) -> String {
+    let mut body = format!(
+        "<section class=\"chapter\">\n<h2>{}</h2>\n",
+        escape(&chapter_title(index, chapter))
+    );
+    for passage in &chapter.passages {
+        match passage {
+            Passage::Action(action) => {
+                for line in paragraphs(action) {
+                    body.push_str(&format!(
+                        "<p class=\"action\"><span class=\"actor\">{}:</span> {}</p>\n",
+                        escape(&story.actor),
+                        escape(line)
+                    ));
+                }
+            }
+            Passage::Dice(dice) => {
+                for line in paragraphs(dice) {
+                    body.push_str(&format!("<p class=\"dice\">{}</p>\n", escape(line)));
+                }
+            }
+            Passage::Narration(narration) => {
+                for line in paragraphs(narration) {
+                    body.push_str(&format!("<p>{}</p>\n", escape(line)));
+                }
+            }
+        }
+    }
+    body.push_str("</section>\n");
+    body
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let group = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+        for position in 0..4 {
+            if position <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - 6 * position) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// A single HTML file, with the portraits embedded so it can be shared as is.
+fn html(story: &Story) -> Result<String, SharadError> {
+    let images = story
+        .cast
+        .iter()
+        .map(|portrait| {
+            Ok(format!(
+                "data:{};base64,{}",
+                media_type(&portrait.path),
+                base64(&fs::read(&portrait.path)?)
+            ))
+        })
+        .collect::<Result<Vec<_>, SharadError>>()?;
+
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html lang=\"{}\">\n<head>\n<meta charset=\"utf-8\" />\n<title>{}</title>\n<style>\n{}</style>\n</head>\n<body>\n<h1>{}</h1>\n<p class=\"subtitle\">{}</p>\n",
+        story.language,
+        escape(&story.title),
+        STYLESHEET,
+        escape(&story.title),
+        escape(&story.subtitle)
+    );
+    html.push_str(&cast_body(story, &|index| images[index].clone()));
+    for (index, chapter) in story.chapters.iter().enumerate() {
+        html.push_str(&chapter_body(story, index, chapter));
+    }
+    html.push_str("</body>\n</html>\n");
+    Ok(html)
+}
+
+fn xhtml_page(story: &Story, title: &str, body: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<!DOCTYPE html>\n<html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\" xml:lang=\"{}\" lang=\"{}\">\n<head>\n<meta charset=\"utf-8\" />\n<title>{}</title>\n<link rel=\"stylesheet\" type=\"text/css\" href=\"style.css\" />\n</head>\n<body>\n{}</body>\n</html>\n",
+        story.language,
+        story.language,
+        escape(title),
+        body
+    )
+}
+
+fn image_name(index: usize, portrait: &Portrait) -> String {
+    let extension = Path::new(&portrait.path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or("png");
+    format!("images/portrait-{}.{}", index + 1, extension)
+}
+
+/// An EPUB 3 book: a title page with the cast, then one file per chapter.
+fn epub(story: &Story) -> Result<Vec<u8>, SharadError> {
+    let mut zip = ZipWriter::new();
+    // Must come first, uncompressed, for readers to recognize the book
+    zip.add("mimetype", b"application/epub+zip")?;
+    zip.add(
+        "META-INF/container.xml",
+        b"<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<container version=\"1.0\" xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\">\n<rootfiles>\n<rootfile full-path=\"OEBPS/content.opf\" media-type=\"application/oebps-package+xml\" />\n</rootfiles>\n</container>\n",
+    )?;
+    zip.add("OEBPS/style.css", STYLESHEET.as_bytes())?;
+
+    let mut manifest = String::from(
+        "<item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\" />\n<item id=\"style\" href=\"style.css\" media-type=\"text/css\" />\n<item id=\"title\" href=\"title.xhtml\" media-type=\"application/xhtml+xml\" />\n",
+    );
+    let mut spine = String::from("<itemref idref=\"title\" />\n");
+    let mut navigation = String::from("<li><a href=\"title.xhtml\">Title</a></li>\n");
+
+    for (index, portrait) in story.cast.iter().enumerate() {
+        let name = image_name(index, portrait);
+        zip.add(&format!("OEBPS/{}", name), &fs::read(&portrait.path)?)?;
+        manifest.push_str(&format!(
+            "<item id=\"portrait-{}\" href=\"{}\" media-type=\"{}\" />\n",
+            index + 1,
+            name,
+            media_type(&portrait.path)
+        ));
+    }
+    let title_body = format!(
+        "<h1>{}</h1>\n<p class=\"subtitle\">{}</p>\n{}",
+        escape(&story.title),
+        escape(&story.subtitle),
+        cast_body(story, &|index| image_name(index, &story.cast[index]))
+    );
+    zip.add(
+        "OEBPS/title.xhtml",
+        xhtml_page(story, &story.title, &title_body).as_bytes(),
+    )?;
+
+    for (index, chapter) in story.chapters.iter().enumerate() {
+        let file = format!("chapter-{}.xhtml", index + 1);
+        let title = chapter_title(index, chapter);
+        zip.add(
+            &format!("OEBPS/{}", file),
+            xhtml_page(story, &title, &chapter_body(story, index, chapter)).as_bytes(),
+        )?;
+        manifest.push_str(&format!(
+            "<item id=\"chapter-{}\" href=\"{}\" media-type=\"application/xhtml+xml\" />\n",
+            index + 1,
+            file
+        ));
+        spine.push_str(&format!("<itemref idref=\"chapter-{}\" />\n", index + 1));
+        navigation.push_str(&format!(
+            "<li><a href=\"{}\">{}</a></li>\n",
+            file,
+            escape(&title)
+        ));
+    }
+
+    let nav_body = format!(
+        "<nav epub:type=\"toc\" id=\"toc\">\n<h1>{}</h1>\n<ol>\n{}</ol>\n</nav>\n",
+        escape(&story.title),
+        navigation
+    );
+    zip.add(
+        "OEBPS/nav.xhtml",
+        xhtml_page(story, &story.title, &nav_body).as_bytes(),
+    )?;
+
+    let package = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"book-id\">\n<metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n<dc:identifier id=\"book-id\">urn:uuid:{}</dc:identifier>\n<dc:title>{}</dc:title>\n<dc:creator>{}</dc:creator>\n<dc:language>{}</dc:language>\n<meta property=\"dcterms:modified\">{}</meta>\n</metadata>\n<manifest>\n{}</manifest>\n<spine>\n{}</spine>\n</package>\n",
+        uuid::Uuid::new_v4(),
+        escape(&story.title),
+        escape(&story.actor),
+        story.language,
+        Utc::now().format("%Y-%m-%dT%H:%M:%SZ"),
+        manifest,
+        spine
+    );
+    zip.add("OEBPS/content.opf", package.as_bytes())?;
+    zip.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_matches_rfc_4648() {
+        for (data, encoded) in [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ] {
+            assert_eq!(base64(data.as_bytes()), encoded);
+        }
+        assert_eq!(base64(&[0xFB, 0xFF]), "+/8=");
+    }
+}
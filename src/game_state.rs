@@ -0,0 +1,168 @@
+use crate::cartridge::Cartridge;
+use crate::clock::{EventKind, GameClock, ScheduledEvent};
+use crate::combat::DamageType;
+use crate::combat::Encounter;
+use crate::journal::Journal;
+use crate::matrix::MatrixRun;
+use crate::npc::NpcRegistry;
+use crate::reputation::Reputation;
+use crate::rules::{Character, RuleSystem};
+use crate::shop::Ledger;
+use crate::utils::SaveRng;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::sync::{Arc, Mutex};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct GameState {
+    #[serde(default)]
+    pub cartridge: Cartridge,
+    #[serde(default)]
+    pub character: Option<Character>,
+    /// Where the player was at the end of the last Game Master turn.
+    #[serde(default)]
+    pub location: Option<String>,
+    #[serde(default)]
+    pub combat: Option<Encounter>,
+    #[serde(default)]
+    pub matrix: Option<MatrixRun>,
+    #[serde(default)]
+    pub npcs: NpcRegistry,
+    #[serde(default)]
+    pub journal: Journal,
+    #[serde(default)]
+    pub reputation: Reputation,
+    #[serde(default)]
+    pub ledger: Ledger,
+    #[serde(default)]
+    pub clock: GameClock,
+    #[serde(default)]
+    pub rng: SaveRng,
+    /// `(character name, image path)` pairs produced by background image generation.
+    #[serde(skip)]
+    pub generated_portraits: Arc<Mutex<Vec<(String, String)>>>,
+}
+
+impl GameState {
+    pub fn rules(&self) -> &'static dyn RuleSystem {
+        self.cartridge.rule_system.rules()
+    }
+
+    /// Structured state sent to the Game Master along with each player action.
+    pub fn gm_context(&self) -> Value {
+        let mut context = Map::new();
+        context.insert("clock".to_string(), self.clock.to_context());
+        if let Some(location) = &self.location {
+            context.insert("location".to_string(), Value::String(location.clone()));
+        }
+        if let Some(character) = &self.character {
+            context.insert("character".to_string(), character.to_json());
+        }
+        if let Some(encounter) = &self.combat {
+            context.insert("combat".to_string(), encounter.to_json());
+        }
+        if let Some(run) = &self.matrix {
+            context.insert("matrix".to_string(), run.to_json());
+        }
+        if !self.npcs.is_empty() {
+            context.insert("known_npcs".to_string(), self.npcs.to_context());
+        }
+        if !self.reputation.is_empty() {
+            context.insert("reputation".to_string(), self.reputation.to_context());
+        }
+        if !self.ledger.is_empty() {
+            context.insert("recent_transactions".to_string(), self.ledger.to_context());
+        }
+        if !self.journal.is_empty() {
+            context.insert("journal".to_string(), self.journal.to_context());
+        }
+        Value::Object(context)
+    }
+
+    /// Compact summary shown to the player after each Game Master turn.
+    pub fn status_block(&self) -> Option<String> {
+        let blocks: Vec<String> = std::iter::once(self.clock.status_line())
+            .chain(self.combat.iter().map(Encounter::status_block))
+            .chain(self.matrix.iter().map(MatrixRun::status_block))
+            .collect();
+        Some(blocks.join("\n\n"))
+    }
+
+    /// Summary of the campaign so far, shown when a saved game is resumed.
+    pub fn campaign_recap(&self) -> Option<String> {
+        let lines = self.journal.recap();
+        if lines.is_empty() {
+            None
+        } else {
+            Some(lines.join("\n"))
+        }
+    }
+
+    /// Applies the effects of scheduled events that just came due.
+    pub fn apply_events(&mut self, events: &[ScheduledEvent]) {
+        for event in events {
+            if event.kind == EventKind::Healing && event.heal > 0 {
+                let track = event.track.unwrap_or(DamageType::Physical);
+                // During a fight, a Shadowrun character's wounds are on their combatant
+                let healed_in_fight = matches!(self.character, Some(Character::Shadowrun(_)))
+                    && self
+                        .combat
+                        .as_mut()
+                        .is_some_and(|combat| combat.heal_player(event.heal, track));
+                if let Some(character) = self.character.as_mut().filter(|_| !healed_in_fight) {
+                    character.heal(event.heal, track);
+                }
+            }
+        }
+    }
+
+    /// Image files this game refers to: the character's and NPCs' portraits.
+    pub fn media_files(&self) -> Vec<&str> {
+        self.character
+            .iter()
+            .filter_map(|character| character.portrait())
+            .chain(
+                self.npcs
+                    .npcs
+                    .iter()
+                    .filter_map(|npc| npc.portrait.as_deref()),
+            )
+            .collect()
+    }
+
+    /// Points the portraits to where their files were moved. `relocate` gives the new path of
+    /// a moved file, or `None` for files that stayed in place.
+    pub fn relocate_media(&mut self, relocate: impl Fn(&str) -> Option<String>) {
+        if let Some(character) = self.character.as_mut() {
+            if let Some(portrait) = character.portrait().and_then(&relocate) {
+                character.set_portrait(portrait);
+            }
+        }
+        for npc in &mut self.npcs.npcs {
+            if let Some(portrait) = npc.portrait.as_deref().and_then(&relocate) {
+                npc.portrait = Some(portrait);
+            }
+        }
+    }
+
+    /// Attaches finished portraits to the characters they were generated for.
+    pub fn collect_portraits(&mut self) {
+        let portraits: Vec<(String, String)> = match self.generated_portraits.lock() {
+            Ok(mut portraits) => portraits.drain(..).collect(),
+            Err(_) => return,
+        };
+
+        for (name, path) in portraits {
+            match &mut self.character {
+                Some(character) if character.name().eq_ignore_ascii_case(name.trim()) => {
+                    character.set_portrait(path)
+                }
+                _ => {
+                    if let Some(npc) = self.npcs.find_mut(&name) {
+                        npc.portrait = Some(path);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use crate::display::Display;
 use crate::error::SharadError;
+use crate::paths::image_dir;
 use crate::utils::open_image;
 use crate::Color;
 use async_openai::{
@@ -33,7 +34,7 @@ pub async fn generate_and_save_image(prompt: &str) -> Result<(), SharadError> {
     }
 
     let paths = response
-        .save("./data/logs")
+        .save(image_dir())
         .await
         .map_err(SharadError::OpenAI)?;
 
@@ -99,7 +100,7 @@ pub async fn generate_character_image(
         return Err("No image URLs received.".into());
     }
 
-    let paths = response.save("./data/logs").await?;
+    let paths = response.save(image_dir()).await?;
 
     if let Some(path) = paths.first() {
         let _ = handle_generated_image(path.to_str().unwrap(), &mut Display::new()).await;
@@ -0,0 +1,112 @@
+use serde_json::Value;
+
+/// Instructions explaining the `proposed_test` sent along with the player action.
+pub const PROPOSED_TEST_INSTRUCTIONS: &str = "When the player action comes with a \"proposed_test\", it was rolled locally from the character sheet for the skill the action seems to need. If that skill fits, use this result instead of rolling again and only decide the threshold or difficulty; if the action needs another test or none at all, ignore it and say so in your reasoning.";
+
+/// Words and phrases hinting at a skill, in English and French. They match whole words only,
+/// so each form the player may write is listed: "heal" must not match "health".
+pub type SkillKeywords = [(&'static str, &'static [&'static str])];
+
+/// A test rolled before the Game Master narrates the action.
+pub struct ProposedTest {
+    /// One line shown to the player, e.g. `Sneaking + Agility: 8 dice, limit 5 -> 3 hits`.
+    pub summary: String,
+    /// Structured result sent to the Game Master.
+    pub result: Value,
+}
+
+/// Finds the skill the action most likely calls for: the one whose keyword appears first.
+pub fn classify(action: &str, keywords: &SkillKeywords) -> Option<&'static str> {
+    let action = action.to_lowercase();
+    let words: Vec<&str> = action
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect();
+
+    (0..words.len()).find_map(|position| {
+        keywords.iter().find_map(|(skill, skill_keywords)| {
+            skill_keywords
+                .iter()
+                .any(|keyword| {
+                    let keyword: Vec<&str> = keyword.split(' ').collect();
+                    words.len() >= position + keyword.len()
+                        && keyword
+                            .iter()
+                            .zip(&words[position..])
+                            .all(|(keyword, word)| keyword == word)
+                })
+                .then_some(*skill)
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEYWORDS: &SkillKeywords = &[
+        ("First Aid", &["heal", "heals", "patch up"]),
+        ("Swimming", &["dive", "plonge"]),
+        ("Perception", &["search", "look around"]),
+        ("Sneaking", &["hide", "faufile"]),
+        ("Hacking", &["hack", "hacking"]),
+        ("Leadership", &["command"]),
+        ("Medicine", &["treat"]),
+        ("Nature", &["plant"]),
+        ("Palming", &["vole"]),
+        ("Con", &["lie"]),
+    ];
+
+    #[test]
+    fn matches_whole_words() {
+        assert_eq!(classify("I heal the decker", KEYWORDS), Some("First Aid"));
+        assert_eq!(classify("She heals him.", KEYWORDS), Some("First Aid"));
+        assert_eq!(
+            classify("I dive into the river", KEYWORDS),
+            Some("Swimming")
+        );
+        assert_eq!(classify("Je me faufile dehors", KEYWORDS), Some("Sneaking"));
+        assert_eq!(classify("Je PLONGE!", KEYWORDS), Some("Swimming"));
+        assert_eq!(
+            classify("I start hacking the node", KEYWORDS),
+            Some("Hacking")
+        );
+    }
+
+    #[test]
+    fn ignores_longer_words_starting_with_a_keyword() {
+        for action in [
+            "I check my health",
+            "We create a diversion",
+            "I do some research",
+            "What a hideous mask",
+            "I grab the hacksaw",
+            "The commando waits",
+            "They signed a treaty",
+            "I planted the tracker yesterday",
+            "Je veux voler",
+            "I salute the lieutenant",
+        ] {
+            assert_eq!(classify(action, KEYWORDS), None, "{}", action);
+        }
+    }
+
+    #[test]
+    fn matches_phrases_in_order() {
+        assert_eq!(classify("I patch up Rico", KEYWORDS), Some("First Aid"));
+        assert_eq!(classify("I look around", KEYWORDS), Some("Perception"));
+        assert_eq!(classify("I look at the door around me", KEYWORDS), None);
+    }
+
+    #[test]
+    fn picks_the_first_keyword_of_the_action() {
+        assert_eq!(
+            classify("I hide, then search the room", KEYWORDS),
+            Some("Sneaking")
+        );
+        assert_eq!(
+            classify("I search the room, then hide", KEYWORDS),
+            Some("Perception")
+        );
+    }
+}
@@ -0,0 +1,351 @@
+use crate::error::SharadError;
+use async_openai::types::FunctionObject;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum QuestStatus {
+    #[default]
+    Active,
+    Completed,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ObjectiveStatus {
+    #[default]
+    Pending,
+    Done,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Objective {
+    pub description: String,
+    #[serde(default)]
+    pub status: ObjectiveStatus,
+}
+
+impl Objective {
+    fn checkbox(&self) -> &'static str {
+        match self.status {
+            ObjectiveStatus::Pending => "[ ]",
+            ObjectiveStatus::Done => "[x]",
+            ObjectiveStatus::Failed => "[!]",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Quest {
+    pub title: String,
+    #[serde(default)]
+    pub johnson: Option<String>,
+    #[serde(default)]
+    pub objectives: Vec<Objective>,
+    #[serde(default)]
+    pub payment: Option<String>,
+    #[serde(default)]
+    pub deadline: Option<String>,
+    #[serde(default)]
+    pub status: QuestStatus,
+    #[serde(default)]
+    pub outcome: Option<String>,
+}
+
+impl Quest {
+    pub fn to_json(&self) -> Value {
+        json!(self)
+    }
+
+    pub fn describe(&self) -> String {
+        let mut lines = vec![self.title.clone()];
+        if let Some(johnson) = &self.johnson {
+            lines.push(format!("Johnson: {}", johnson));
+        }
+        if let Some(payment) = &self.payment {
+            lines.push(format!("Payment: {}", payment));
+        }
+        if let Some(deadline) = &self.deadline {
+            lines.push(format!("Deadline: {}", deadline));
+        }
+        for objective in &self.objectives {
+            lines.push(format!(
+                "{} {}",
+                objective.checkbox(),
+                objective.description
+            ));
+        }
+        if let Some(outcome) = &self.outcome {
+            lines.push(format!("Outcome: {}", outcome));
+        }
+        lines.join("\n")
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Journal {
+    pub quests: Vec<Quest>,
+}
+
+impl Journal {
+    pub fn find_mut(&mut self, title: &str) -> Option<&mut Quest> {
+        self.quests
+            .iter_mut()
+            .find(|quest| quest.title.eq_ignore_ascii_case(title.trim()))
+    }
+
+    pub fn active(&self) -> impl Iterator<Item = &Quest> {
+        self.quests
+            .iter()
+            .filter(|quest| quest.status == QuestStatus::Active)
+    }
+
+    pub fn history(&self) -> impl Iterator<Item = &Quest> {
+        self.quests
+            .iter()
+            .filter(|quest| quest.status != QuestStatus::Active)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.quests.is_empty()
+    }
+
+    /// Active quests in full, finished ones reduced to their outcome.
+    pub fn to_context(&self) -> Value {
+        json!({
+            "active_quests": self.active().map(Quest::to_json).collect::<Vec<_>>(),
+            "quest_history": self.history().map(|quest| json!({
+                "title": quest.title,
+                "status": quest.status,
+                "outcome": quest.outcome,
+            })).collect::<Vec<_>>(),
+        })
+    }
+
+    pub fn recap(&self) -> Vec<String> {
+        self.history()
+            .map(|quest| {
+                let status = match quest.status {
+                    QuestStatus::Completed => "Completed",
+                    _ => "Failed",
+                };
+                match &quest.outcome {
+                    Some(outcome) => format!("{}: {} - {}", status, quest.title, outcome),
+                    None => format!("{}: {}", status, quest.title),
+                }
+            })
+            .chain(
+                self.active()
+                    .map(|quest| format!("In progress: {}", quest.title)),
+            )
+            .collect()
+    }
+}
+
+#[derive(Deserialize)]
+struct AddQuestArgs {
+    title: String,
+    johnson: Option<String>,
+    #[serde(default)]
+    objectives: Vec<String>,
+    payment: Option<String>,
+    deadline: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ObjectiveUpdate {
+    /// 1-based, as shown to the Game Master.
+    index: usize,
+    status: ObjectiveStatus,
+}
+
+#[derive(Deserialize)]
+struct UpdateQuestArgs {
+    title: String,
+    #[serde(default)]
+    add_objectives: Vec<String>,
+    #[serde(default)]
+    objective_updates: Vec<ObjectiveUpdate>,
+    payment: Option<String>,
+    deadline: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CloseQuestArgs {
+    title: String,
+    status: QuestStatus,
+    outcome: String,
+}
+
+pub fn add_quest(journal: &mut Journal, args: Value) -> Result<Value, SharadError> {
+    let args: AddQuestArgs = serde_json::from_value(args)?;
+    // A finished quest can be taken on again, like a Johnson hiring for the same job twice
+    if journal
+        .active()
+        .any(|quest| quest.title.eq_ignore_ascii_case(args.title.trim()))
+    {
+        return Err(SharadError::Message(format!(
+            "A quest titled '{}' is already active. Use update_quest instead.",
+            args.title
+        )));
+    }
+
+    let quest = Quest {
+        title: args.title.trim().to_string(),
+        johnson: args.johnson,
+        objectives: args
+            .objectives
+            .into_iter()
+            .map(|description| Objective {
+                description,
+                status: ObjectiveStatus::Pending,
+            })
+            .collect(),
+        payment: args.payment,
+        deadline: args.deadline,
+        status: QuestStatus::Active,
+        outcome: None,
+    };
+    let output = quest.to_json();
+    journal.quests.push(quest);
+    Ok(output)
+}
+
+pub fn update_quest(journal: &mut Journal, args: Value) -> Result<Value, SharadError> {
+    let args: UpdateQuestArgs = serde_json::from_value(args)?;
+    let quest = active_quest(journal, &args.title)?;
+
+    // Every index is checked before anything changes, so a bad call leaves the quest as it was
+    if let Some(update) = args
+        .objective_updates
+        .iter()
+        .find(|update| update.index == 0 || update.index > quest.objectives.len())
+    {
+        return Err(SharadError::Message(format!(
+            "Quest '{}' has no objective {}.",
+            quest.title, update.index
+        )));
+    }
+    for update in &args.objective_updates {
+        quest.objectives[update.index - 1].status = update.status;
+    }
+    quest.objectives.extend(
+        args.add_objectives
+            .into_iter()
+            .map(|description| Objective {
+                description,
+                status: ObjectiveStatus::Pending,
+            }),
+    );
+    if let Some(payment) = args.payment {
+        quest.payment = Some(payment);
+    }
+    if let Some(deadline) = args.deadline {
+        quest.deadline = Some(deadline);
+    }
+    Ok(quest.to_json())
+}
+
+pub fn close_quest(journal: &mut Journal, args: Value) -> Result<Value, SharadError> {
+    let args: CloseQuestArgs = serde_json::from_value(args)?;
+    if args.status == QuestStatus::Active {
+        return Err(SharadError::Message(
+            "A quest can only be closed as completed or failed.".into(),
+        ));
+    }
+    let quest = active_quest(journal, &args.title)?;
+    quest.status = args.status;
+    quest.outcome = Some(args.outcome);
+    Ok(quest.to_json())
+}
+
+/// The active quest with this title, which closed quests of the same title do not hide.
+pub fn active_quest<'a>(
+    journal: &'a mut Journal,
+    title: &str,
+) -> Result<&'a mut Quest, SharadError> {
+    let title = title.trim();
+    let active = journal.quests.iter().position(|quest| {
+        quest.status == QuestStatus::Active && quest.title.eq_ignore_ascii_case(title)
+    });
+    if let Some(index) = active {
+        return Ok(&mut journal.quests[index]);
+    }
+    match journal.find_mut(title) {
+        Some(quest) => Err(SharadError::Message(format!(
+            "Quest '{}' is already closed.",
+            quest.title
+        ))),
+        None => Err(SharadError::Message(format!(
+            "No quest titled '{}'. Use add_quest first.",
+            title
+        ))),
+    }
+}
+
+pub fn tool_definitions() -> Vec<FunctionObject> {
+    vec![
+        FunctionObject {
+            name: "add_quest".to_string(),
+            description: Some(
+                "Record a new run or quest in the player's journal when they accept a job"
+                    .to_string(),
+            ),
+            parameters: Some(json!({
+                "type": "object",
+                "properties": {
+                    "title": { "type": "string" },
+                    "johnson": { "type": "string", "description": "Who offered the job" },
+                    "objectives": { "type": "array", "items": { "type": "string" } },
+                    "payment": { "type": "string", "description": "Agreed payment, e.g. '15,000 nuyen'" },
+                    "deadline": { "type": "string" }
+                },
+                "required": ["title", "objectives"]
+            })),
+        },
+        FunctionObject {
+            name: "update_quest".to_string(),
+            description: Some(
+                "Update an active quest: objective progress, new objectives, payment or deadline"
+                    .to_string(),
+            ),
+            parameters: Some(json!({
+                "type": "object",
+                "properties": {
+                    "title": { "type": "string" },
+                    "add_objectives": { "type": "array", "items": { "type": "string" } },
+                    "objective_updates": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "index": { "type": "integer", "description": "1-based objective number" },
+                                "status": { "type": "string", "enum": ["pending", "done", "failed"] }
+                            },
+                            "required": ["index", "status"]
+                        }
+                    },
+                    "payment": { "type": "string" },
+                    "deadline": { "type": "string" }
+                },
+                "required": ["title"]
+            })),
+        },
+        FunctionObject {
+            name: "close_quest".to_string(),
+            description: Some("Close a quest as completed or failed".to_string()),
+            parameters: Some(json!({
+                "type": "object",
+                "properties": {
+                    "title": { "type": "string" },
+                    "status": { "type": "string", "enum": ["completed", "failed"] },
+                    "outcome": { "type": "string", "description": "One sentence on how it ended" }
+                },
+                "required": ["title", "status", "outcome"]
+            })),
+        },
+    ]
+}
@@ -0,0 +1,384 @@
+use crate::augmentation::magic_loss;
+use crate::character::{linked_attribute, Awakening, CharacterSheet, ATTRIBUTES, SKILLS};
+use crate::display::Display;
+use crate::error::SharadError;
+use crate::menu::select_from_list;
+use async_openai::types::FunctionObject;
+use crossterm::style::Color;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+pub const MAX_SKILL_RATING: u8 = 12;
+/// Magic cannot be raised past this without initiation, which is not modelled, less a point
+/// for each point of Essence lost to augmentations.
+pub const MAX_MAGIC: u8 = 6;
+const MAX_AWARD: u32 = 20;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct KarmaEntry {
+    /// Positive for awards, negative for karma spent.
+    pub amount: i32,
+    pub reason: String,
+    /// In-game date of the award or purchase.
+    #[serde(default)]
+    pub date: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Karma {
+    pub available: u32,
+    pub total_earned: u32,
+    #[serde(default)]
+    pub history: Vec<KarmaEntry>,
+}
+
+/// One step of character improvement bought with karma.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Advancement {
+    Attribute(String),
+    Edge,
+    Magic,
+    Skill(String),
+}
+
+impl Advancement {
+    fn current_rating(&self, sheet: &CharacterSheet) -> u8 {
+        match self {
+            Advancement::Attribute(attribute) => sheet.attribute(attribute),
+            Advancement::Edge => sheet.edge,
+            Advancement::Magic => sheet.magic,
+            Advancement::Skill(skill) => sheet.skill(skill),
+        }
+    }
+
+    fn maximum(&self, sheet: &CharacterSheet) -> u8 {
+        match self {
+            Advancement::Attribute(attribute) => sheet.metatype.attribute_range(attribute).1,
+            Advancement::Edge => sheet.metatype.attribute_range("Edge").1,
+            Advancement::Magic => MAX_MAGIC.saturating_sub(magic_loss(sheet.essence())),
+            Advancement::Skill(_) => MAX_SKILL_RATING,
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            Advancement::Attribute(attribute) => attribute,
+            Advancement::Edge => "Edge",
+            Advancement::Magic => "Magic",
+            Advancement::Skill(skill) => skill,
+        }
+    }
+
+    /// Karma cost of raising the rating by one: new rating x5 for attributes, x2 for skills.
+    pub fn cost(&self, sheet: &CharacterSheet) -> u32 {
+        let new_rating = self.current_rating(sheet) as u32 + 1;
+        match self {
+            Advancement::Skill(_) => new_rating * 2,
+            _ => new_rating * 5,
+        }
+    }
+
+    /// Checks the advancement against the sheet, without looking at the karma available.
+    pub fn validate(&self, sheet: &CharacterSheet) -> Result<(), String> {
+        match self {
+            Advancement::Attribute(attribute) if !ATTRIBUTES.contains(&attribute.as_str()) => {
+                return Err(format!("{} is not an attribute.", attribute));
+            }
+            Advancement::Magic if sheet.awakening == Awakening::Mundane => {
+                return Err("A mundane character has no Magic to raise.".to_string());
+            }
+            Advancement::Skill(skill) => match linked_attribute(skill) {
+                None => return Err(format!("Unknown skill: {}.", skill)),
+                Some("Magic") if sheet.awakening == Awakening::Mundane => {
+                    return Err(format!("{} requires a magical character.", skill));
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+        if self.current_rating(sheet) >= self.maximum(sheet) {
+            return Err(format!(
+                "{} is already at its maximum of {}.",
+                self.name(),
+                self.maximum(sheet)
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn describe(&self, sheet: &CharacterSheet) -> String {
+        let current = self.current_rating(sheet);
+        if current >= self.maximum(sheet) {
+            format!("{} {} (maximum)", self.name(), current)
+        } else {
+            format!(
+                "{} {} -> {} ({} karma)",
+                self.name(),
+                current,
+                current + 1,
+                self.cost(sheet)
+            )
+        }
+    }
+}
+
+/// Raises one rating, deducting its cost and recording the purchase in the karma history.
+pub fn spend_karma(
+    sheet: &mut CharacterSheet,
+    advancement: &Advancement,
+    date: Option<String>,
+) -> Result<String, SharadError> {
+    advancement.validate(sheet).map_err(SharadError::Message)?;
+    let cost = advancement.cost(sheet);
+    if cost > sheet.karma.available {
+        return Err(SharadError::Message(format!(
+            "{} costs {} karma, but only {} is available.",
+            advancement.name(),
+            cost,
+            sheet.karma.available
+        )));
+    }
+
+    let new_rating = advancement.current_rating(sheet) + 1;
+    match advancement {
+        Advancement::Attribute(attribute) => {
+            sheet.attributes.insert(attribute.clone(), new_rating);
+            if attribute == "Body" || attribute == "Willpower" {
+                sheet.refresh_condition_monitor();
+            }
+        }
+        Advancement::Edge => sheet.edge = new_rating,
+        Advancement::Magic => sheet.magic = new_rating,
+        Advancement::Skill(skill) => {
+            let name = SKILLS
+                .iter()
+                .map(|(name, _)| *name)
+                .find(|name| name.eq_ignore_ascii_case(skill))
+                .unwrap_or(skill);
+            sheet.skills.insert(name.to_string(), new_rating);
+        }
+    }
+
+    let reason = format!("Raised {} to {}", advancement.name(), new_rating);
+    sheet.karma.available -= cost;
+    sheet.karma.history.push(KarmaEntry {
+        amount: -(cost as i32),
+        reason: reason.clone(),
+        date,
+    });
+    Ok(reason)
+}
+
+#[derive(Deserialize)]
+struct AwardKarmaArgs {
+    amount: u32,
+    reason: String,
+}
+
+pub fn award_karma(
+    character: Option<&mut CharacterSheet>,
+    date: String,
+    args: Value,
+) -> Result<Value, SharadError> {
+    let args: AwardKarmaArgs = serde_json::from_value(args)?;
+    let sheet = character
+        .ok_or_else(|| SharadError::Message("The player has no character sheet.".into()))?;
+    if args.amount == 0 || args.amount > MAX_AWARD {
+        return Err(SharadError::Message(format!(
+            "Karma awards must be between 1 and {}.",
+            MAX_AWARD
+        )));
+    }
+
+    sheet.karma.available += args.amount;
+    sheet.karma.total_earned += args.amount;
+    sheet.karma.history.push(KarmaEntry {
+        amount: args.amount as i32,
+        reason: args.reason,
+        date: Some(date),
+    });
+    Ok(json!({
+        "available": sheet.karma.available,
+        "total_earned": sheet.karma.total_earned,
+    }))
+}
+
+pub fn tool_definitions() -> Vec<FunctionObject> {
+    vec![FunctionObject {
+        name: "award_karma".to_string(),
+        description: Some(
+            "Award karma to the player at the end of a run or for outstanding roleplay. Usually 2 to 8 per run"
+                .to_string(),
+        ),
+        parameters: Some(json!({
+            "type": "object",
+            "properties": {
+                "amount": { "type": "integer" },
+                "reason": { "type": "string", "description": "What the karma is awarded for" }
+            },
+            "required": ["amount", "reason"]
+        })),
+    }]
+}
+
+/// Lets the player spend karma on the sheet. Returns whether anything was bought.
+pub fn advancement_screen(
+    display: &mut Display,
+    sheet: &mut CharacterSheet,
+    date: Option<String>,
+) -> Result<bool, SharadError> {
+    let mut changed = false;
+    loop {
+        let mut options: Vec<Advancement> = ATTRIBUTES
+            .iter()
+            .map(|attribute| Advancement::Attribute(attribute.to_string()))
+            .collect();
+        options.push(Advancement::Edge);
+        if sheet.awakening != Awakening::Mundane {
+            options.push(Advancement::Magic);
+        }
+        options.extend(sheet.skills.keys().cloned().map(Advancement::Skill));
+
+        let mut items: Vec<String> = options
+            .iter()
+            .map(|advancement| advancement.describe(sheet))
+            .collect();
+        items.push("Learn a new skill (2 karma)".to_string());
+        items.push("Karma history".to_string());
+
+        let title = format!(
+            "Spend karma - {} available ({} earned)",
+            sheet.karma.available, sheet.karma.total_earned
+        );
+        let Some(index) = select_from_list(display, &title, &items)? else {
+            return Ok(changed);
+        };
+
+        let advancement = match index.checked_sub(options.len()) {
+            None => options[index].clone(),
+            Some(0) => match choose_new_skill(display, sheet)? {
+                Some(skill) => Advancement::Skill(skill),
+                None => continue,
+            },
+            Some(_) => {
+                show_history(display, sheet);
+                continue;
+            }
+        };
+
+        if let Err(problem) = advancement.validate(sheet) {
+            display.print_wrapped(&problem, Color::Yellow);
+            continue;
+        }
+        let prompt = format!(
+            "Spend {} karma to raise {}? (y/n)",
+            advancement.cost(sheet),
+            advancement.name()
+        );
+        match display
+            .get_user_input(&prompt)?
+            .map(|s| s.to_lowercase())
+            .as_deref()
+        {
+            Some("y") | Some("yes") => {}
+            _ => continue,
+        }
+        match spend_karma(sheet, &advancement, date.clone()) {
+            Ok(result) => {
+                display.print_wrapped(&result, Color::Green);
+                changed = true;
+            }
+            Err(e) => display.print_wrapped(&e.to_string(), Color::Yellow),
+        }
+    }
+}
+
+fn choose_new_skill(
+    display: &mut Display,
+    sheet: &CharacterSheet,
+) -> Result<Option<String>, SharadError> {
+    let items: Vec<String> = SKILLS
+        .iter()
+        .filter(|(skill, _)| sheet.skill(skill) == 0)
+        .filter(|(_, attribute)| *attribute != "Magic" || sheet.awakening != Awakening::Mundane)
+        .map(|(skill, _)| skill.to_string())
+        .collect();
+    Ok(select_from_list(display, "Learn which skill?", &items)?.map(|i| items[i].clone()))
+}
+
+fn show_history(display: &mut Display, sheet: &CharacterSheet) {
+    display.print_header("Karma history");
+    if sheet.karma.history.is_empty() {
+        display.print_wrapped("No karma earned yet.", Color::Yellow);
+    }
+    for entry in &sheet.karma.history {
+        let line = match &entry.date {
+            Some(date) => format!("{:+}  {} ({})", entry.amount, entry.reason, date),
+            None => format!("{:+}  {}", entry.amount, entry.reason),
+        };
+        let color = if entry.amount > 0 {
+            Color::Green
+        } else {
+            Color::White
+        };
+        display.print_wrapped(&line, color);
+    }
+    display.print_separator(Color::Yellow);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::character::test_sheet;
+
+    #[test]
+    fn spends_only_the_karma_available() {
+        let mut sheet = test_sheet(Awakening::Mundane, 0);
+        sheet.karma.available = 25;
+        let pistols = Advancement::Skill("pistols".to_string());
+        assert_eq!(pistols.cost(&sheet), 10);
+        spend_karma(&mut sheet, &pistols, None).unwrap();
+        assert_eq!(sheet.skill("Pistols"), 5);
+        assert_eq!(sheet.karma.available, 15);
+
+        // Agility 4 costs 20
+        let agility = Advancement::Attribute("Agility".to_string());
+        assert!(spend_karma(&mut sheet, &agility, None).is_err());
+        assert_eq!(sheet.attribute("Agility"), 3);
+        assert_eq!(sheet.karma.available, 15);
+        assert_eq!(sheet.karma.history.len(), 1);
+        assert_eq!(sheet.karma.history[0].amount, -10);
+    }
+
+    #[test]
+    fn lost_essence_lowers_the_magic_maximum() {
+        let mut sheet = test_sheet(Awakening::Magician, 5);
+        sheet.karma.available = 100;
+        assert!(Advancement::Magic.validate(&sheet).is_ok());
+        sheet.essence_hole = 0.5;
+        assert!(Advancement::Magic.validate(&sheet).is_err());
+
+        let sheet = test_sheet(Awakening::Mundane, 0);
+        assert!(Advancement::Magic.validate(&sheet).is_err());
+        assert!(Advancement::Skill("Spellcasting".to_string())
+            .validate(&sheet)
+            .is_err());
+    }
+
+    #[test]
+    fn awards_stay_within_bounds() {
+        let mut sheet = test_sheet(Awakening::Mundane, 0);
+        let award = |sheet: &mut CharacterSheet, amount: u32| {
+            award_karma(
+                Some(sheet),
+                "Day 1".to_string(),
+                json!({ "amount": amount, "reason": "Run" }),
+            )
+        };
+        assert!(award(&mut sheet, 0).is_err());
+        assert!(award(&mut sheet, MAX_AWARD + 1).is_err());
+        award(&mut sheet, 5).unwrap();
+        assert_eq!(sheet.karma.available, 5);
+        assert_eq!(sheet.karma.total_earned, 5);
+    }
+}
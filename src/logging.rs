@@ -0,0 +1,85 @@
+use crate::error::SharadError;
+use crate::paths::log_dir;
+use chrono::Local;
+use std::env;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+use tracing::{debug, error};
+use tracing_subscriber::{prelude::*, reload, EnvFilter, Registry};
+
+/// Game events at `info`, everything else only when it goes wrong.
+pub const DEFAULT_LOG_FILTER: &str = "warn,sharad=info";
+/// What the old debug mode showed.
+pub const DEBUG_LOG_FILTER: &str = "warn,sharad=debug";
+
+static FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+static LOG_FILE: OnceLock<PathBuf> = OnceLock::new();
+
+fn parse_filter(filter: &str) -> Result<EnvFilter, SharadError> {
+    EnvFilter::try_new(filter)
+        .map_err(|e| SharadError::Message(format!("Invalid log filter '{}': {}", filter, e)))
+}
+
+/// Starts writing events as JSON lines to this hour's log, adding to it when the game was
+/// already started this hour. `RUST_LOG` takes precedence over the filter from the settings.
+pub fn init(filter: &str) -> Result<(), SharadError> {
+    fs::create_dir_all(log_dir())?;
+    let path = log_dir().join(format!("log_{}.jsonl", Local::now().format("%Y%m%d_%H")));
+    let file = OpenOptions::new().create(true).append(true).open(&path)?;
+
+    let filter = env::var("RUST_LOG")
+        .ok()
+        .and_then(|filter| parse_filter(&filter).ok())
+        .or_else(|| parse_filter(filter).ok())
+        .unwrap_or_else(|| EnvFilter::new(DEFAULT_LOG_FILTER));
+    let (filter, handle) = reload::Layer::new(filter);
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(
+            tracing_subscriber::fmt::layer()
+                .json()
+                .with_current_span(true)
+                .with_span_list(true)
+                .with_writer(Mutex::new(file)),
+        )
+        .try_init()
+        .map_err(|e| SharadError::Other(e.to_string()))?;
+    let _ = FILTER.set(handle);
+    let _ = LOG_FILE.set(path);
+    Ok(())
+}
+
+/// The log being written to, once logging has started.
+pub fn current_log() -> Option<&'static Path> {
+    LOG_FILE.get().map(PathBuf::as_path)
+}
+
+/// Changes which events are logged, and which debug messages are shown, while the game runs.
+pub fn set_filter(filter: &str) -> Result<(), SharadError> {
+    let filter = parse_filter(filter)?;
+    if let Some(handle) = FILTER.get() {
+        handle
+            .reload(filter)
+            .map_err(|e| SharadError::Other(e.to_string()))?;
+    }
+    Ok(())
+}
+
+/// Awaits an API request, logging how long it took and why it failed.
+pub async fn timed<T, E: fmt::Display>(
+    request: &'static str,
+    future: impl Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+    let start = Instant::now();
+    let result = future.await;
+    let latency_ms = start.elapsed().as_millis() as u64;
+    match &result {
+        Ok(_) => debug!(request, latency_ms, "api request"),
+        Err(e) => error!(request, latency_ms, error = %e, "api request failed"),
+    }
+    result
+}
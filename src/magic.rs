@@ -0,0 +1,426 @@
+use crate::character::{Awakening, CharacterSheet};
+use crate::combat::{wound_modifier, DamageType, Encounter};
+use crate::error::SharadError;
+use crate::utils::shadowrun_dice_roll;
+use async_openai::types::FunctionObject;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fmt;
+
+/// Drain is never lower than this, whatever the spell's modifier.
+const MIN_DRAIN: u8 = 2;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Tradition {
+    #[default]
+    Hermetic,
+    Shamanic,
+}
+
+impl Tradition {
+    /// Attribute added to Willpower when resisting Drain.
+    pub fn drain_attribute(&self) -> &'static str {
+        match self {
+            Tradition::Hermetic => "Logic",
+            Tradition::Shamanic => "Charisma",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SpellCategory {
+    Combat,
+    Detection,
+    Health,
+    Illusion,
+    Manipulation,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SpellType {
+    Physical,
+    #[default]
+    Mana,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Spell {
+    pub name: String,
+    pub category: SpellCategory,
+    #[serde(default, rename = "type")]
+    pub spell_type: SpellType,
+    /// Added to the Force to get the Drain value, e.g. -3 for "F-3".
+    pub drain: i8,
+}
+
+impl Spell {
+    pub fn drain_value(&self, force: u8) -> u8 {
+        (force as i32 + self.drain as i32).max(MIN_DRAIN as i32) as u8
+    }
+}
+
+impl fmt::Display for Spell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({:?}, {:?}, Drain F{:+})",
+            self.name, self.category, self.spell_type, self.drain
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Spirit {
+    pub kind: String,
+    pub force: u8,
+    pub services: u8,
+}
+
+/// Spells known and spirits owing services, kept on the character sheet between sessions.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Grimoire {
+    #[serde(default)]
+    pub tradition: Tradition,
+    #[serde(default)]
+    pub spells: Vec<Spell>,
+    #[serde(default)]
+    pub spirits: Vec<Spirit>,
+}
+
+impl Grimoire {
+    pub fn is_empty(&self) -> bool {
+        self.spells.is_empty() && self.spirits.is_empty()
+    }
+
+    fn spell(&self, name: &str) -> Option<&Spell> {
+        self.spells
+            .iter()
+            .find(|spell| spell.name.eq_ignore_ascii_case(name.trim()))
+    }
+
+    pub fn render(&self) -> Vec<String> {
+        let mut lines = vec![format!("Tradition  {:?}", self.tradition)];
+        if !self.spells.is_empty() {
+            lines.push("Spells".to_string());
+            lines.extend(self.spells.iter().map(|spell| format!("  {}", spell)));
+        }
+        if !self.spirits.is_empty() {
+            lines.push("Spirits".to_string());
+            lines.extend(self.spirits.iter().map(|spirit| {
+                format!(
+                    "  {} spirit, Force {} ({} services)",
+                    spirit.kind, spirit.force, spirit.services
+                )
+            }));
+        }
+        lines
+    }
+}
+
+/// Drain taken by the caster, so it can also be applied to a running combat.
+pub struct Drain {
+    pub amount: u8,
+    pub damage_type: DamageType,
+}
+
+/// Rolls Willpower + tradition attribute against the Drain value and applies what gets through.
+fn resist_drain(sheet: &mut CharacterSheet, drain_value: u8, force: u8) -> (Value, Drain) {
+    let pool = sheet.augmented_attribute("Willpower")
+        + sheet.augmented_attribute(sheet.grimoire.tradition.drain_attribute());
+    let resisted = shadowrun_dice_roll(pool, 0).successes();
+    // Drain is Physical when the caster pushes past their own Magic
+    let damage_type = if force > sheet.magic {
+        DamageType::Physical
+    } else {
+        DamageType::Stun
+    };
+    let amount = drain_value.saturating_sub(resisted);
+    sheet.condition.apply(amount, damage_type);
+
+    let output = json!({
+        "drain_value": drain_value,
+        "drain_resisted": resisted,
+        "drain_taken": amount,
+        "drain_type": damage_type,
+        "physical_damage": format!("{}/{}", sheet.condition.physical_damage, sheet.condition.physical_boxes),
+        "stun_damage": format!("{}/{}", sheet.condition.stun_damage, sheet.condition.stun_boxes),
+        "incapacitated": sheet.condition.is_incapacitated(),
+    });
+    (
+        output,
+        Drain {
+            amount,
+            damage_type,
+        },
+    )
+}
+
+fn magician(character: Option<&mut CharacterSheet>) -> Result<&mut CharacterSheet, SharadError> {
+    let sheet = character
+        .ok_or_else(|| SharadError::Message("The player has no character sheet.".into()))?;
+    if sheet.awakening != Awakening::Magician {
+        return Err(SharadError::Message(
+            "Only magicians can cast spells and summon spirits.".into(),
+        ));
+    }
+    if sheet.magic == 0 {
+        return Err(SharadError::Message(
+            "The character's Magic has burned out.".into(),
+        ));
+    }
+    Ok(sheet)
+}
+
+/// Force is limited to twice the caster's Magic.
+fn check_force(sheet: &CharacterSheet, force: u8) -> Result<(), SharadError> {
+    if force == 0 || force > sheet.magic * 2 {
+        return Err(SharadError::Message(format!(
+            "Force must be between 1 and {} (twice the character's Magic).",
+            sheet.magic * 2
+        )));
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct LearnSpellArgs {
+    name: String,
+    category: SpellCategory,
+    #[serde(default, rename = "type")]
+    spell_type: SpellType,
+    drain: i8,
+}
+
+#[derive(Deserialize)]
+struct CastSpellArgs {
+    spell: String,
+    force: u8,
+    #[serde(default)]
+    threshold: u8,
+}
+
+#[derive(Deserialize)]
+struct SummonSpiritArgs {
+    kind: String,
+    force: u8,
+}
+
+#[derive(Deserialize)]
+struct SpiritServiceArgs {
+    kind: String,
+    /// Release the spirit from all remaining services.
+    #[serde(default)]
+    dismiss: bool,
+}
+
+pub fn learn_spell(
+    character: Option<&mut CharacterSheet>,
+    args: Value,
+) -> Result<Value, SharadError> {
+    let args: LearnSpellArgs = serde_json::from_value(args)?;
+    let sheet = magician(character)?;
+    if sheet.grimoire.spell(&args.name).is_some() {
+        return Err(SharadError::Message(format!(
+            "{} already knows {}.",
+            sheet.name, args.name
+        )));
+    }
+
+    let spell = Spell {
+        name: args.name.trim().to_string(),
+        category: args.category,
+        spell_type: args.spell_type,
+        drain: args.drain,
+    };
+    let output = json!({ "learned": spell.to_string() });
+    sheet.grimoire.spells.push(spell);
+    Ok(output)
+}
+
+/// Spellcasting + Magic, with hits limited by the Force.
+pub fn cast_spell(
+    character: Option<&mut CharacterSheet>,
+    combat: Option<&Encounter>,
+    args: Value,
+) -> Result<(Value, Drain), SharadError> {
+    let args: CastSpellArgs = serde_json::from_value(args)?;
+    let sheet = magician(character)?;
+    let spell = sheet.grimoire.spell(&args.spell).cloned().ok_or_else(|| {
+        SharadError::Message(format!("{} does not know {}.", sheet.name, args.spell))
+    })?;
+    check_force(sheet, args.force)?;
+
+    let pool = sheet.dice_pool("Spellcasting") as i32 + wound_modifier(sheet, combat);
+    let roll = shadowrun_dice_roll(pool.max(0) as u8, 0);
+    let hits = roll.successes().min(args.force);
+    let (drain_output, drain) = resist_drain(sheet, spell.drain_value(args.force), args.force);
+
+    let mut output = json!({
+        "spell": spell.name,
+        "force": args.force,
+        "hits": hits,
+        "glitch": roll.glitch(),
+        "is_successful": hits > 0 && hits >= args.threshold,
+    });
+    if let (Value::Object(output), Value::Object(drain)) = (&mut output, drain_output) {
+        output.extend(drain);
+    }
+    Ok((output, drain))
+}
+
+/// Summoning + Magic opposed by the spirit's Force; net hits become services owed.
+pub fn summon_spirit(
+    character: Option<&mut CharacterSheet>,
+    combat: Option<&Encounter>,
+    args: Value,
+) -> Result<(Value, Drain), SharadError> {
+    let args: SummonSpiritArgs = serde_json::from_value(args)?;
+    let sheet = magician(character)?;
+    check_force(sheet, args.force)?;
+    if let Some(spirit) = sheet.grimoire.spirits.first() {
+        return Err(SharadError::Message(format!(
+            "A {} spirit is still bound. Use or dismiss it before summoning another.",
+            spirit.kind
+        )));
+    }
+
+    let pool = sheet.dice_pool("Summoning") as i32 + wound_modifier(sheet, combat);
+    let roll = shadowrun_dice_roll(pool.max(0) as u8, 0);
+    let hits = roll.successes().min(args.force);
+    let spirit_hits = shadowrun_dice_roll(args.force, 0).successes();
+    let services = hits.saturating_sub(spirit_hits);
+    let (drain_output, drain) = resist_drain(sheet, (spirit_hits * 2).max(MIN_DRAIN), args.force);
+
+    if services > 0 {
+        sheet.grimoire.spirits.push(Spirit {
+            kind: args.kind.trim().to_string(),
+            force: args.force,
+            services,
+        });
+    }
+    let mut output = json!({
+        "spirit": args.kind,
+        "force": args.force,
+        "hits": hits,
+        "spirit_hits": spirit_hits,
+        "glitch": roll.glitch(),
+        "services_owed": services,
+        "is_successful": services > 0,
+    });
+    if let (Value::Object(output), Value::Object(drain)) = (&mut output, drain_output) {
+        output.extend(drain);
+    }
+    Ok((output, drain))
+}
+
+pub fn spirit_service(
+    character: Option<&mut CharacterSheet>,
+    args: Value,
+) -> Result<Value, SharadError> {
+    let args: SpiritServiceArgs = serde_json::from_value(args)?;
+    let sheet = magician(character)?;
+    let index = sheet
+        .grimoire
+        .spirits
+        .iter()
+        .position(|spirit| spirit.kind.eq_ignore_ascii_case(args.kind.trim()))
+        .ok_or_else(|| {
+            SharadError::Message(format!(
+                "No {} spirit owes the character services.",
+                args.kind
+            ))
+        })?;
+
+    let spirit = &mut sheet.grimoire.spirits[index];
+    if !args.dismiss {
+        spirit.services -= 1;
+    }
+    let output = json!({
+        "spirit": spirit.kind,
+        "services_left": if args.dismiss { 0 } else { spirit.services },
+        "departed": args.dismiss || spirit.services == 0,
+    });
+    if args.dismiss || spirit.services == 0 {
+        sheet.grimoire.spirits.remove(index);
+    }
+    Ok(output)
+}
+
+pub fn tool_definitions() -> Vec<FunctionObject> {
+    vec![
+        FunctionObject {
+            name: "learn_spell".to_string(),
+            description: Some("Add a spell to a magician player's known spells".to_string()),
+            parameters: Some(json!({
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string" },
+                    "category": {
+                        "type": "string",
+                        "enum": ["combat", "detection", "health", "illusion", "manipulation"]
+                    },
+                    "type": { "type": "string", "enum": ["physical", "mana"] },
+                    "drain": {
+                        "type": "integer",
+                        "description": "Drain modifier added to the Force, e.g. -3 for F-3"
+                    }
+                },
+                "required": ["name", "category", "type", "drain"]
+            })),
+        },
+        FunctionObject {
+            name: "cast_spell".to_string(),
+            description: Some(
+                "Cast a known spell for the player: rolls Spellcasting + Magic limited by Force, then resists and applies Drain"
+                    .to_string(),
+            ),
+            parameters: Some(json!({
+                "type": "object",
+                "properties": {
+                    "spell": { "type": "string" },
+                    "force": {
+                        "type": "integer",
+                        "description": "Up to twice the caster's Magic. Drain becomes Physical above Magic"
+                    },
+                    "threshold": {
+                        "type": "integer",
+                        "description": "Hits needed, or the target's resistance hits for opposed spells"
+                    }
+                },
+                "required": ["spell", "force"]
+            })),
+        },
+        FunctionObject {
+            name: "summon_spirit".to_string(),
+            description: Some(
+                "Summon a spirit for the player: Summoning + Magic opposed by the spirit's Force. Net hits are services owed; Drain is applied"
+                    .to_string(),
+            ),
+            parameters: Some(json!({
+                "type": "object",
+                "properties": {
+                    "kind": { "type": "string", "description": "e.g. Fire, Air, Man, Beasts" },
+                    "force": { "type": "integer" }
+                },
+                "required": ["kind", "force"]
+            })),
+        },
+        FunctionObject {
+            name: "spirit_service".to_string(),
+            description: Some(
+                "Spend one service of the player's spirit, or dismiss it".to_string(),
+            ),
+            parameters: Some(json!({
+                "type": "object",
+                "properties": {
+                    "kind": { "type": "string" },
+                    "dismiss": { "type": "boolean" }
+                },
+                "required": ["kind"]
+            })),
+        },
+    ]
+}
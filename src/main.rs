@@ -1,22 +1,57 @@
+mod archive;
 mod assistant;
 mod audio;
+mod augmentation;
+mod bundle;
+mod cartridge;
+mod character;
+mod character_creation;
+mod clock;
+mod combat;
+mod commands;
+mod consistency;
+mod d20;
 mod display;
 mod error;
+mod export;
+mod game_state;
 mod image;
+mod intent;
+mod journal;
+mod karma;
+mod logging;
+mod magic;
+mod matrix;
 mod menu;
+mod npc;
+mod paths;
+mod replay;
+mod reputation;
+mod rules;
+mod save;
 mod settings;
+mod shadowrun;
+mod shop;
+mod storage;
+mod tables;
+mod tools;
+mod transcript;
 mod utils;
 
+use crate::bundle::{export_bundle, import_bundle};
 use crate::display::Display;
 use crate::error::SharadError;
-use chrono::Local;
+use crate::logging::DEFAULT_LOG_FILTER;
+use crate::paths::migrate_legacy_data;
+use crate::settings::{load_or_recover_settings, load_settings, validate_settings};
+use crate::storage::{clean_up, disk_usage, format_size, Category, Retention};
 use crossterm::{
     execute,
     style::{Attribute, Color, ResetColor, SetAttribute, SetForegroundColor},
 };
 use menu::main_menu;
-use std::fs::{self, File};
 use std::io::{stdout, Write};
+use std::path::Path;
 
 use core::cmp::Ordering;
 use rand::Rng;
@@ -25,6 +60,7 @@ use semver::Version;
 use std::env;
 use std::error::Error;
 use tokio::signal;
+use tracing::{error, info, warn};
 
 fn check_for_updates() -> Result<(), Box<dyn Error + Send + Sync>> {
     println!("Checking for updates...");
@@ -99,30 +135,171 @@ fn rainbow(text: &str) {
     let _ = execute!(stdout(), ResetColor);
 }
 
+/// Shows how much room each kind of file takes and how long it is kept.
+fn print_storage_report(display: &mut Display, retention: &Retention) -> Result<(), SharadError> {
+    let report = disk_usage()?;
+    let total: u64 = report.iter().map(|usage| usage.bytes).sum();
+    for usage in report {
+        display.print_wrapped(
+            &format!(
+                "{}: {} files, {} in {}",
+                usage.label,
+                usage.files,
+                format_size(usage.bytes),
+                usage.dir.display()
+            ),
+            Color::Cyan,
+        );
+    }
+    display.print_wrapped(&format!("Total: {}", format_size(total)), Color::Green);
+    for category in Category::ALL {
+        display.print_wrapped(
+            &format!("{}: {}", category, retention.policy(category)),
+            Color::Yellow,
+        );
+    }
+    Ok(())
+}
+
+/// Removes the files the retention policies no longer keep, or lists them with `--dry-run`.
+fn run_cleanup(
+    display: &mut Display,
+    retention: &Retention,
+    dry_run: bool,
+) -> Result<(), SharadError> {
+    let cleanup = clean_up(retention, dry_run)?;
+    for path in &cleanup.removed {
+        display.print_wrapped(&path.display().to_string(), Color::Yellow);
+    }
+    for skipped in &cleanup.skipped {
+        display.print_wrapped(&format!("Left alone: {}", skipped), Color::Red);
+    }
+    display.print_wrapped(
+        &format!(
+            "{} {} files, {}.",
+            if dry_run { "Would remove" } else { "Removed" },
+            cleanup.removed.len(),
+            format_size(cleanup.bytes)
+        ),
+        Color::Green,
+    );
+    Ok(())
+}
+
+/// Runs `sharad export <save> [file]`, `sharad import <file> [name]`, `sharad storage` or
+/// `sharad cleanup [--dry-run]`.
+/// Returns `false` when the arguments name no command, to start the game instead.
+async fn run_command_line(args: &[String], display: &mut Display) -> Result<bool, SharadError> {
+    let (Some(command), argument, target) = (args.get(1), args.get(2), args.get(3)) else {
+        return Ok(false);
+    };
+    match command.as_str() {
+        "storage" => {
+            let settings = load_or_recover_settings(display)?;
+            print_storage_report(display, &settings.retention)?;
+            return Ok(true);
+        }
+        "cleanup" => {
+            let settings = load_or_recover_settings(display)?;
+            let dry_run = argument.is_some_and(|argument| argument == "--dry-run");
+            run_cleanup(display, &settings.retention, dry_run)?;
+            return Ok(true);
+        }
+        "export" | "import" => {}
+        _ => return Ok(false),
+    }
+    let Some(argument) = argument else {
+        display.print_wrapped(
+            "Usage: sharad export <save> [file] | sharad import <file> [name] | sharad storage | sharad cleanup [--dry-run]",
+            Color::Yellow,
+        );
+        return Ok(true);
+    };
+
+    // Bundles talk to the API, which needs the key from the settings
+    validate_settings(&mut load_or_recover_settings(display)?, display).await?;
+    if command == "export" {
+        let path = export_bundle(argument, target.map(Path::new)).await?;
+        display.print_wrapped(
+            &format!("'{}' exported to {}", argument, path.display()),
+            Color::Green,
+        );
+    } else {
+        display.print_wrapped("Importing...", Color::Yellow);
+        let name = import_bundle(Path::new(argument), target.map(String::as_str)).await?;
+        display.print_wrapped(&format!("Imported as '{}'.", name), Color::Green);
+    }
+    Ok(true)
+}
+
 #[tokio::main]
 async fn main() -> Result<(), SharadError> {
     let mut display = Display::new();
 
+    match migrate_legacy_data() {
+        Ok(moved) if !moved.is_empty() => {
+            display.print_wrapped("Files from ./data moved to:", Color::Cyan);
+            for line in moved {
+                display.print_wrapped(&line, Color::Cyan);
+            }
+        }
+        Ok(_) => {}
+        Err(e) => display.print_wrapped(
+            &format!("Failed to move files from ./data: {}", e),
+            Color::Red,
+        ),
+    }
+
+    let args: Vec<String> = env::args().collect();
+    if run_command_line(&args, &mut display).await? {
+        return Ok(());
+    }
+
     let update_result = tokio::task::spawn_blocking(check_for_updates).await?;
     if let Err(e) = update_result {
         display.print_wrapped(&format!("Failed to check for updates: {}", e), Color::Red);
     }
 
-    fs::create_dir_all("./data/logs")?;
-    let log_file_path = format!("./data/logs/log_{}.txt", Local::now().format("%Y%m%d_%H"));
-    let mut log_file = File::create(&log_file_path).map_err(|e| {
+    let settings = load_settings().ok();
+    let log_filter = settings
+        .as_ref()
+        .map_or(DEFAULT_LOG_FILTER, |settings| settings.log_filter.as_str());
+    logging::init(log_filter).map_err(|e| {
         display.print_wrapped(&format!("Failed to create log file: {}", e), Color::Red);
-        SharadError::Io(e)
+        e
     })?;
+    // Only when the player asked for it; settings that cannot be read never clean up
+    if let Some(settings) = settings
+        .as_ref()
+        .filter(|settings| settings.retention.on_start)
+    {
+        match clean_up(&settings.retention, false) {
+            Ok(cleanup) => {
+                if !cleanup.removed.is_empty() {
+                    info!(
+                        files = cleanup.removed.len(),
+                        bytes = cleanup.bytes,
+                        "old files cleaned up"
+                    );
+                }
+                for skipped in &cleanup.skipped {
+                    warn!(reason = %skipped, "cleanup skipped");
+                }
+            }
+            Err(e) => error!(error = %e, "cleanup failed"),
+        }
+    }
 
     tokio::spawn(async move {
         signal::ctrl_c().await.expect("Failed to listen for Ctrl-C");
         std::process::exit(0);
     });
 
-    writeln!(log_file, "Sharad game started.")?;
+    info!(version = env!("CARGO_PKG_VERSION"), "game started");
 
-    let _ = main_menu(log_file).await;
+    if let Err(e) = main_menu().await {
+        error!(error = %e, "game ended with an error");
+    }
     // Display the art once before entering the loop
     Ok(())
 }
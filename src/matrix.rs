@@ -0,0 +1,550 @@
+use crate::error::SharadError;
+use crate::utils::shadowrun_dice_roll;
+use async_openai::types::FunctionObject;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// At this Overwatch Score GOD converges on the persona.
+pub const CONVERGENCE: u8 = 40;
+const MAX_MARKS: u8 = 3;
+const MAX_HOST_RATING: u8 = 12;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MatrixAction {
+    /// Sleaze: marks the host quietly, but a failed attempt alerts it.
+    HackOnTheFly,
+    /// Attack: marks the host by force and always alerts it.
+    BruteForce,
+    EnterHost,
+    LeaveHost,
+    DataSpike,
+    /// Any other illegal action against the current host: editing or cracking files, controlling devices.
+    IllegalAction,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Ice {
+    pub name: String,
+    pub kind: String,
+    pub boxes: u8,
+    #[serde(default)]
+    pub damage: u8,
+}
+
+impl Ice {
+    fn new(name: String, kind: String, host_rating: u8) -> Self {
+        Ice {
+            name,
+            kind,
+            boxes: 8 + host_rating.div_ceil(2),
+            damage: 0,
+        }
+    }
+
+    pub fn is_crashed(&self) -> bool {
+        self.damage >= self.boxes
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Host {
+    pub name: String,
+    pub rating: u8,
+    /// The host this one is reached from, or `None` for hosts on the public grid.
+    #[serde(default)]
+    pub connected_to: Option<String>,
+    #[serde(default)]
+    pub marks: u8,
+    #[serde(default)]
+    pub alert: bool,
+    #[serde(default)]
+    pub ice: Vec<Ice>,
+}
+
+impl Host {
+    fn is_reached_from(&self, location: Option<&str>) -> bool {
+        match (&self.connected_to, location) {
+            (None, None) => true,
+            (Some(parent), Some(location)) => parent.eq_ignore_ascii_case(location),
+            _ => false,
+        }
+    }
+
+    fn active_ice(&self) -> impl Iterator<Item = &Ice> {
+        self.ice.iter().filter(|ice| !ice.is_crashed())
+    }
+}
+
+/// Tactical state of a hacking scene: the host network, the persona's position and GOD's attention.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MatrixRun {
+    pub hosts: Vec<Host>,
+    /// Host the persona is in, or `None` while on the public grid.
+    #[serde(default)]
+    pub location: Option<String>,
+    #[serde(default)]
+    pub overwatch: u8,
+}
+
+impl MatrixRun {
+    fn host(&self, name: &str) -> Option<&Host> {
+        self.hosts
+            .iter()
+            .find(|host| host.name.eq_ignore_ascii_case(name.trim()))
+    }
+
+    fn host_mut(&mut self, name: &str) -> Result<&mut Host, SharadError> {
+        self.hosts
+            .iter_mut()
+            .find(|host| host.name.eq_ignore_ascii_case(name.trim()))
+            .ok_or_else(|| SharadError::Message(format!("No host named '{}'.", name)))
+    }
+
+    pub fn has_converged(&self) -> bool {
+        self.overwatch >= CONVERGENCE
+    }
+
+    fn raise_overwatch(&mut self, amount: u8) {
+        self.overwatch = self.overwatch.saturating_add(amount).min(CONVERGENCE);
+    }
+
+    /// The host a mark or move targets: the current host or one reachable from it.
+    fn reachable_host(&self, name: &str) -> Result<&Host, SharadError> {
+        let host = self
+            .host(name)
+            .ok_or_else(|| SharadError::Message(format!("No host named '{}'.", name)))?;
+        let location = self.location.as_deref();
+        let here = location.is_some_and(|location| host.name.eq_ignore_ascii_case(location));
+        if here || host.is_reached_from(location) {
+            Ok(host)
+        } else {
+            Err(SharadError::Message(format!(
+                "{} cannot be reached from {}.",
+                host.name,
+                location.unwrap_or("the grid")
+            )))
+        }
+    }
+
+    fn current_host_mut(&mut self) -> Result<&mut Host, SharadError> {
+        let location = self.location.clone().ok_or_else(|| {
+            SharadError::Message("The persona is on the grid, not inside a host.".into())
+        })?;
+        self.host_mut(&location)
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "location": self.location.as_deref().unwrap_or("grid"),
+            "overwatch_score": self.overwatch,
+            "convergence": self.has_converged(),
+            "hosts": self.hosts.iter().map(|host| json!({
+                "name": host.name,
+                "rating": host.rating,
+                "connected_to": host.connected_to.as_deref().unwrap_or("grid"),
+                "marks": host.marks,
+                "alert": host.alert,
+                "ice": host.ice.iter().map(|ice| json!({
+                    "name": ice.name,
+                    "kind": ice.kind,
+                    "damage": format!("{}/{}", ice.damage, ice.boxes),
+                    "crashed": ice.is_crashed(),
+                })).collect::<Vec<_>>(),
+            })).collect::<Vec<_>>(),
+        })
+    }
+
+    /// ASCII map of the host network, with the persona's position and the Overwatch track.
+    pub fn status_block(&self) -> String {
+        let filled = (self.overwatch / 2) as usize;
+        let mut lines = vec![format!(
+            "Matrix - Overwatch {:>2}/{} [{}{}]{}",
+            self.overwatch,
+            CONVERGENCE,
+            "#".repeat(filled),
+            ".".repeat((CONVERGENCE / 2) as usize - filled),
+            if self.has_converged() {
+                " CONVERGENCE"
+            } else {
+                ""
+            }
+        )];
+        lines.push(format!(
+            "(GRID){}",
+            if self.location.is_none() {
+                "  <= you"
+            } else {
+                ""
+            }
+        ));
+        self.render_hosts(None, "", &mut lines);
+        lines.join("\n")
+    }
+
+    fn render_hosts(&self, parent: Option<&str>, prefix: &str, lines: &mut Vec<String>) {
+        let children: Vec<&Host> = self
+            .hosts
+            .iter()
+            .filter(|host| host.is_reached_from(parent))
+            .collect();
+        for (index, host) in children.iter().enumerate() {
+            let last = index + 1 == children.len();
+            let here = self
+                .location
+                .as_deref()
+                .is_some_and(|location| host.name.eq_ignore_ascii_case(location));
+            lines.push(format!(
+                "{}{}[{} R{}] marks {}{}{}{}",
+                prefix,
+                if last { "`-- " } else { "+-- " },
+                host.name,
+                host.rating,
+                "*".repeat(host.marks as usize),
+                ".".repeat((MAX_MARKS - host.marks.min(MAX_MARKS)) as usize),
+                if host.alert { " ALERT" } else { "" },
+                if here { "  <= you" } else { "" }
+            ));
+            let child_prefix = format!("{}{}", prefix, if last { "    " } else { "|   " });
+            for ice in &host.ice {
+                lines.push(format!(
+                    "{}  ~ {} ({}) [{}{}]{}",
+                    child_prefix,
+                    ice.name,
+                    ice.kind,
+                    "#".repeat(ice.damage.min(ice.boxes) as usize),
+                    ".".repeat(ice.boxes.saturating_sub(ice.damage) as usize),
+                    if ice.is_crashed() { " crashed" } else { "" }
+                ));
+            }
+            self.render_hosts(Some(&host.name), &child_prefix, lines);
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct IceArgs {
+    name: String,
+    kind: String,
+}
+
+#[derive(Deserialize)]
+struct HostArgs {
+    name: String,
+    rating: u8,
+    #[serde(default)]
+    connected_to: Option<String>,
+    #[serde(default)]
+    ice: Vec<IceArgs>,
+}
+
+#[derive(Deserialize)]
+struct JackInArgs {
+    hosts: Vec<HostArgs>,
+}
+
+#[derive(Deserialize)]
+struct MatrixActionArgs {
+    action: MatrixAction,
+    /// Host for marks and moves, IC for data spikes.
+    #[serde(default)]
+    target: Option<String>,
+    #[serde(default)]
+    dice_pool: u8,
+}
+
+#[derive(Deserialize)]
+struct LaunchIceArgs {
+    host: String,
+    name: String,
+    kind: String,
+}
+
+pub fn jack_in(matrix: &mut Option<MatrixRun>, args: Value) -> Result<Value, SharadError> {
+    if matrix.is_some() {
+        return Err(SharadError::Message(
+            "The persona is already jacked in. Jack out before starting a new run.".into(),
+        ));
+    }
+    let args: JackInArgs = serde_json::from_value(args)?;
+
+    let mut hosts: Vec<Host> = Vec::new();
+    for host in args.hosts {
+        // Hosts may only connect to hosts listed before them, which keeps the network a tree
+        let connected_to = host
+            .connected_to
+            .filter(|parent| !parent.eq_ignore_ascii_case("grid"));
+        if let Some(parent) = &connected_to {
+            if !hosts.iter().any(|h| h.name.eq_ignore_ascii_case(parent)) {
+                return Err(SharadError::Message(format!(
+                    "{} connects to '{}', which must be listed before it.",
+                    host.name, parent
+                )));
+            }
+        }
+        if hosts
+            .iter()
+            .any(|h| h.name.eq_ignore_ascii_case(&host.name))
+        {
+            return Err(SharadError::Message(format!(
+                "Two hosts are named '{}'.",
+                host.name
+            )));
+        }
+        let rating = host.rating.clamp(1, MAX_HOST_RATING);
+        hosts.push(Host {
+            name: host.name,
+            rating,
+            connected_to,
+            marks: 0,
+            alert: false,
+            ice: host
+                .ice
+                .into_iter()
+                .map(|ice| Ice::new(ice.name, ice.kind, rating))
+                .collect(),
+        });
+    }
+    if hosts.is_empty() {
+        return Err(SharadError::Message(
+            "A Matrix run needs at least one host.".into(),
+        ));
+    }
+
+    let run = MatrixRun {
+        hosts,
+        location: None,
+        overwatch: 0,
+    };
+    let output = run.to_json();
+    *matrix = Some(run);
+    Ok(output)
+}
+
+/// Resolves a matrix action. Illegal actions are opposed by the host (rating x2 dice), and the
+/// defender's hits raise the Overwatch Score.
+pub fn matrix_action(matrix: &mut Option<MatrixRun>, args: Value) -> Result<Value, SharadError> {
+    let run = active_run(matrix)?;
+    let args: MatrixActionArgs = serde_json::from_value(args)?;
+    if run.has_converged() {
+        return Err(SharadError::Message(
+            "GOD has converged on the persona. The only action left is jack_out.".into(),
+        ));
+    }
+    let target = || {
+        args.target
+            .clone()
+            .ok_or_else(|| SharadError::Message("This action needs a target.".into()))
+    };
+
+    let mut result = json!({ "action": args.action });
+    match args.action {
+        MatrixAction::EnterHost => {
+            let host = run.reachable_host(&target()?)?;
+            if host.marks == 0 {
+                return Err(SharadError::Message(format!(
+                    "Entering {} requires a mark on it. Hack on the fly or brute force first.",
+                    host.name
+                )));
+            }
+            let name = host.name.clone();
+            run.location = Some(name);
+        }
+        MatrixAction::LeaveHost => {
+            let location = run.location.clone().ok_or_else(|| {
+                SharadError::Message("The persona is already on the grid.".into())
+            })?;
+            run.location = run.host_mut(&location)?.connected_to.clone();
+        }
+        MatrixAction::HackOnTheFly | MatrixAction::BruteForce => {
+            let name = run.reachable_host(&target()?)?.name.clone();
+            let host = run.host_mut(&name)?;
+            let attack = shadowrun_dice_roll(args.dice_pool, 0);
+            let defense = shadowrun_dice_roll(host.rating * 2, 0);
+            let net_hits = attack.successes().saturating_sub(defense.successes());
+
+            host.marks = (host.marks + net_hits).min(MAX_MARKS);
+            if args.action == MatrixAction::BruteForce || net_hits == 0 {
+                host.alert = true;
+            }
+            result["hits"] = json!(attack.successes());
+            result["glitch"] = json!(attack.glitch());
+            result["defender_hits"] = json!(defense.successes());
+            result["marks"] = json!(host.marks);
+            result["host_alerted"] = json!(host.alert);
+            run.raise_overwatch(defense.successes());
+        }
+        MatrixAction::DataSpike => {
+            let target = target()?;
+            let host = run.current_host_mut()?;
+            let rating = host.rating;
+            let ice = host
+                .ice
+                .iter_mut()
+                .find(|ice| ice.name.eq_ignore_ascii_case(target.trim()) && !ice.is_crashed())
+                .ok_or_else(|| {
+                    SharadError::Message(format!("No active IC named '{}' here.", target))
+                })?;
+            let attack = shadowrun_dice_roll(args.dice_pool, 0);
+            let defense = shadowrun_dice_roll(rating * 2, 0);
+            let net_hits = attack.successes().saturating_sub(defense.successes());
+
+            ice.damage = ice.damage.saturating_add(net_hits);
+            result["hits"] = json!(attack.successes());
+            result["glitch"] = json!(attack.glitch());
+            result["defender_hits"] = json!(defense.successes());
+            result["ice_damage"] = json!(format!("{}/{}", ice.damage, ice.boxes));
+            result["ice_crashed"] = json!(ice.is_crashed());
+            host.alert = true;
+            run.raise_overwatch(defense.successes());
+        }
+        MatrixAction::IllegalAction => {
+            let host = run.current_host_mut()?;
+            let attack = shadowrun_dice_roll(args.dice_pool, 0);
+            let defense = shadowrun_dice_roll(host.rating * 2, 0);
+            result["hits"] = json!(attack.successes());
+            result["glitch"] = json!(attack.glitch());
+            result["defender_hits"] = json!(defense.successes());
+            result["net_hits"] = json!(attack.successes().saturating_sub(defense.successes()));
+            run.raise_overwatch(defense.successes());
+        }
+    }
+
+    result["location"] = json!(run.location.as_deref().unwrap_or("grid"));
+    result["overwatch_score"] = json!(run.overwatch);
+    if run.has_converged() {
+        result["convergence"] = json!(
+            "GOD converges: the persona takes 12 DV matrix damage, is dumpshocked off the Matrix and the physical location is reported"
+        );
+    }
+    if let Some(location) = &run.location {
+        if let Some(host) = run.host(location) {
+            let active: Vec<&str> = host.active_ice().map(|ice| ice.name.as_str()).collect();
+            if host.alert && !active.is_empty() {
+                result["active_ice"] = json!(active);
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Alerted hosts launch one IC program per combat turn.
+pub fn launch_ice(matrix: &mut Option<MatrixRun>, args: Value) -> Result<Value, SharadError> {
+    let run = active_run(matrix)?;
+    let args: LaunchIceArgs = serde_json::from_value(args)?;
+    let host = run.host_mut(&args.host)?;
+    let ice = Ice::new(args.name, args.kind, host.rating);
+    let output = json!({
+        "host": host.name,
+        "ice": ice.name,
+        "boxes": ice.boxes,
+    });
+    host.alert = true;
+    host.ice.push(ice);
+    Ok(output)
+}
+
+pub fn jack_out(matrix: &mut Option<MatrixRun>) -> Result<Value, SharadError> {
+    let run = matrix
+        .take()
+        .ok_or_else(|| SharadError::Message("The persona is not jacked in.".into()))?;
+    Ok(json!({
+        "status": "Jacked out",
+        "overwatch_score": run.overwatch,
+        "convergence": run.has_converged(),
+        "dumpshock": run.location.is_some(),
+    }))
+}
+
+fn active_run(matrix: &mut Option<MatrixRun>) -> Result<&mut MatrixRun, SharadError> {
+    matrix.as_mut().ok_or_else(|| {
+        SharadError::Message("The persona is not jacked in. Call jack_in first.".into())
+    })
+}
+
+pub fn tool_definitions() -> Vec<FunctionObject> {
+    vec![
+        FunctionObject {
+            name: "jack_in".to_string(),
+            description: Some(
+                "Start a Matrix run, describing the host network the decker can reach".to_string(),
+            ),
+            parameters: Some(json!({
+                "type": "object",
+                "properties": {
+                    "hosts": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "name": { "type": "string" },
+                                "rating": { "type": "integer", "description": "Host rating (1 to 12)" },
+                                "connected_to": {
+                                    "type": "string",
+                                    "description": "Name of a host listed earlier that leads here, or 'grid'"
+                                },
+                                "ice": {
+                                    "type": "array",
+                                    "description": "IC already running on the host",
+                                    "items": {
+                                        "type": "object",
+                                        "properties": {
+                                            "name": { "type": "string" },
+                                            "kind": { "type": "string", "description": "e.g. Patrol, Killer, Black IC" }
+                                        },
+                                        "required": ["name", "kind"]
+                                    }
+                                }
+                            },
+                            "required": ["name", "rating"]
+                        }
+                    }
+                },
+                "required": ["hosts"]
+            })),
+        },
+        FunctionObject {
+            name: "matrix_action".to_string(),
+            description: Some(
+                "Resolve a decker's Matrix action. Illegal actions are opposed by the host and raise the Overwatch Score"
+                    .to_string(),
+            ),
+            parameters: Some(json!({
+                "type": "object",
+                "properties": {
+                    "action": {
+                        "type": "string",
+                        "enum": ["hack_on_the_fly", "brute_force", "enter_host", "leave_host", "data_spike", "illegal_action"]
+                    },
+                    "target": {
+                        "type": "string",
+                        "description": "The host to mark or enter, or the IC to data spike"
+                    },
+                    "dice_pool": {
+                        "type": "integer",
+                        "description": "Decker's dice pool, e.g. Hacking + Logic or Cybercombat + Logic"
+                    }
+                },
+                "required": ["action"]
+            })),
+        },
+        FunctionObject {
+            name: "launch_ice".to_string(),
+            description: Some("Launch an IC program on an alerted host".to_string()),
+            parameters: Some(json!({
+                "type": "object",
+                "properties": {
+                    "host": { "type": "string" },
+                    "name": { "type": "string" },
+                    "kind": { "type": "string", "description": "e.g. Patrol, Killer, Black IC" }
+                },
+                "required": ["host", "name", "kind"]
+            })),
+        },
+        FunctionObject {
+            name: "jack_out".to_string(),
+            description: Some("End the Matrix run".to_string()),
+            parameters: Some(json!({ "type": "object", "properties": {} })),
+        },
+    ]
+}
@@ -1,10 +1,22 @@
 use crate::assistant::{
-    load_conversation_from_file, run_conversation, run_conversation_with_save, Save, SAVE_DIR,
+    branch_thread, load_conversation_from_file, run_conversation, run_conversation_with_save,
 };
+use crate::commands::advance;
 use crate::display::Display;
 use crate::error::SharadError;
+use crate::export::{export_save, ExportFormat};
 use crate::image;
-use crate::settings::{load_settings, save_settings, validate_settings, Settings};
+use crate::logging::{set_filter, DEBUG_LOG_FILTER, DEFAULT_LOG_FILTER};
+use crate::replay::{replay_save, MAX_PACE_SECONDS};
+use crate::save::{
+    delete_save, has_backup, list_saves, rename_save, restore_backup, validate_new_name, Save,
+    SaveEntry, SaveMetadata,
+};
+use crate::settings::{
+    load_or_recover_settings, load_settings, save_settings, validate_settings, Settings,
+};
+use crate::transcript::{self, Role};
+use async_openai::Client;
 
 use crossterm::style::Color;
 use crossterm::{
@@ -14,15 +26,15 @@ use crossterm::{
     style::SetForegroundColor,
     terminal::{self, Clear, ClearType},
 };
-use std::fs::File;
 use std::io;
 use std::io::{stdout, Write};
-use std::path::Path;
-use tokio::fs;
+use textwrap::wrap;
+use unicode_width::UnicodeWidthStr;
 
-pub const MAIN_MENU_ITEMS: [&str; 5] = [
+pub const MAIN_MENU_ITEMS: [&str; 6] = [
     "Start a new game",
     "Load a game",
+    "Spend karma",
     "Create an image",
     "Settings",
     "Exit",
@@ -61,10 +73,10 @@ pub const ART: &str = r#"
 |_____/|_| |_|\__,_|_|  \__,_|\__,_|
     "#;
 
-pub async fn main_menu(mut log_file: File) -> Result<(), SharadError> {
+pub async fn main_menu() -> Result<(), SharadError> {
     let mut display = Display::new();
     display_art(&mut display)?;
-    let mut settings = load_settings()?;
+    let mut settings = load_or_recover_settings(&mut display)?;
     validate_settings(&mut settings, &mut display).await?;
 
     terminal::enable_raw_mode()?;
@@ -85,13 +97,7 @@ pub async fn main_menu(mut log_file: File) -> Result<(), SharadError> {
                         selected = (selected + 1) % menu_items_count;
                     }
                     KeyCode::Enter => {
-                        if handle_main_menu_selection(
-                            &mut log_file,
-                            selected,
-                            &mut display,
-                            &mut settings,
-                        )
-                        .await?
+                        if handle_main_menu_selection(selected, &mut display, &mut settings).await?
                         {
                             break;
                         }
@@ -106,13 +112,8 @@ pub async fn main_menu(mut log_file: File) -> Result<(), SharadError> {
                             .map(|d| d as usize)
                         {
                             if index < menu_items_count
-                                && handle_main_menu_selection(
-                                    &mut log_file,
-                                    index,
-                                    &mut display,
-                                    &mut settings,
-                                )
-                                .await?
+                                && handle_main_menu_selection(index, &mut display, &mut settings)
+                                    .await?
                             {
                                 break;
                             }
@@ -239,7 +240,7 @@ fn draw_settings_menu(
         .enumerate()
         .map(|(i, item)| match i {
             0 => item.len() + settings.language.len() + 13,
-            2..=4 => item.len() + 7,
+            2..=6 => item.len() + 7,
             _ => item.len(),
         })
         .max()
@@ -279,8 +280,29 @@ fn draw_settings_menu(
                 item,
                 settings.audio_input_enabled
             ),
-            4 => format!("{}{}. {} ({})", prefix, i + 1, item, settings.debug_mode),
-            5 => format!("{}{}. {}", prefix, i + 1, item),
+            4 => format!("{}{}. {} ({})", prefix, i + 1, item, settings.log_filter),
+            5 => format!(
+                "{}{}. {} ({})",
+                prefix,
+                i + 1,
+                item,
+                settings.autosave_interval
+            ),
+            6 => format!(
+                "{}{}. {} ({})",
+                prefix,
+                i + 1,
+                item,
+                settings.autosave_slots
+            ),
+            7 => format!(
+                "{}{}. {} ({}s)",
+                prefix,
+                i + 1,
+                item,
+                settings.replay_pace_seconds
+            ),
+            8 => format!("{}{}. {}", prefix, i + 1, item),
             _ => unreachable!(),
         };
 
@@ -316,7 +338,7 @@ fn print_centered_line(
     line: u16,
 ) -> Result<(), SharadError> {
     let (term_width, _) = terminal::size()?;
-    let start_x = (term_width - text.len() as u16) / 2;
+    let start_x = term_width.saturating_sub(text.width() as u16) / 2;
     execute!(
         io::stdout(),
         cursor::MoveTo(start_x, line),
@@ -329,7 +351,6 @@ fn print_centered_line(
 }
 
 async fn handle_main_menu_selection(
-    log_file: &mut File,
     selected: usize,
     display: &mut Display,
     settings: &mut Settings,
@@ -338,7 +359,7 @@ async fn handle_main_menu_selection(
 
     let should_exit = match selected {
         0 => {
-            match run_conversation(log_file, true, display).await {
+            match run_conversation(true, display).await {
                 Ok(_) => {
                     display.print_wrapped("Conversation completed successfully.", Color::Green)
                 }
@@ -351,31 +372,39 @@ async fn handle_main_menu_selection(
         1 => {
             display.print_wrapped("Loading a game.", Color::Green);
             match load_conversation_from_file(display).await {
-                Ok(save) => {
-                    match run_conversation_with_save(
-                        log_file,
-                        &save.assistant_id,
-                        &save.thread_id,
-                        false,
-                        display,
-                    )
-                    .await
-                    {
-                        Ok(_) => display.print_wrapped(
-                            "Saved game loaded and conversation completed successfully.",
-                            Color::Green,
-                        ),
-                        Err(e) => display.print_wrapped(
-                            &format!("Failed to run conversation with save: {}", e),
-                            Color::Red,
-                        ),
-                    }
-                }
+                Ok(mut save) => match run_conversation_with_save(&mut save, false, display).await {
+                    Ok(_) => display.print_wrapped(
+                        "Saved game loaded and conversation completed successfully.",
+                        Color::Green,
+                    ),
+                    Err(e) => display.print_wrapped(
+                        &format!("Failed to run conversation with save: {}", e),
+                        Color::Red,
+                    ),
+                },
                 Err(e) => display.print_wrapped(&format!("Failed to load game: {}", e), Color::Red),
             }
             false
         }
         2 => {
+            match load_conversation_from_file(display).await {
+                Ok(mut save) => match advance(&mut save.game_state, display) {
+                    Ok(true) => match save.write() {
+                        Ok(_) => display.print_wrapped("Advancement saved.", Color::Green),
+                        Err(e) => {
+                            display.print_wrapped(&format!("Failed to save: {}", e), Color::Red)
+                        }
+                    },
+                    Ok(false) => {}
+                    Err(e) => {
+                        display.print_wrapped(&format!("Advancement failed: {}", e), Color::Red)
+                    }
+                },
+                Err(e) => display.print_wrapped(&format!("Failed to load game: {}", e), Color::Red),
+            }
+            false
+        }
+        3 => {
             match display.get_user_input("What image would you like to generate?")? {
                 Some(prompt) => match image::generate_and_save_image(&prompt).await {
                     Ok(_) => display
@@ -387,7 +416,7 @@ async fn handle_main_menu_selection(
             }
             false
         }
-        3 => {
+        4 => {
             match change_settings(settings, display).await {
                 Ok(_) => display.print_wrapped("Settings updated successfully.", Color::Green),
                 Err(e) => {
@@ -396,7 +425,7 @@ async fn handle_main_menu_selection(
             }
             false
         }
-        4 => {
+        5 => {
             display.print_wrapped("Exiting game.", Color::Green);
             true
         }
@@ -458,14 +487,72 @@ async fn handle_settings_selection(
             false
         }
         4 => {
-            settings.debug_mode = !settings.debug_mode;
             display.print_wrapped(
-                &format!("Debug Mode: {}", settings.debug_mode),
-                Color::Green,
+                &format!(
+                    "Examples: '{}' for game events, '{}' to also see debug messages, 'off' to keep no log.",
+                    DEFAULT_LOG_FILTER, DEBUG_LOG_FILTER
+                ),
+                Color::Cyan,
             );
+            match display.get_user_input("Enter the log filter:")? {
+                Some(filter) if !filter.trim().is_empty() => match set_filter(filter.trim()) {
+                    Ok(()) => {
+                        settings.log_filter = filter.trim().to_string();
+                        display.print_wrapped(
+                            &format!("Log Filter: {}", settings.log_filter),
+                            Color::Green,
+                        );
+                    }
+                    Err(e) => display.print_wrapped(&e.to_string(), Color::Red),
+                },
+                Some(_) => display.print_wrapped(
+                    "Log filter cannot be empty. No changes made.",
+                    Color::Yellow,
+                ),
+                None => display.print_wrapped("Log filter change cancelled.", Color::Yellow),
+            }
+            false
+        }
+        5 => {
+            if let Some(interval) = ask_number(
+                display,
+                "Autosave every how many turns? (0 to only autosave on exit)",
+            )? {
+                settings.autosave_interval = interval;
+                display.print_wrapped(
+                    &format!("Autosave Every N Turns: {}", settings.autosave_interval),
+                    Color::Green,
+                );
+            }
+            false
+        }
+        6 => {
+            if let Some(slots) = ask_number(display, "How many autosaves should be kept?")? {
+                settings.autosave_slots = slots.max(1);
+                display.print_wrapped(
+                    &format!("Autosave Slots: {}", settings.autosave_slots),
+                    Color::Green,
+                );
+            }
+            false
+        }
+        7 => {
+            if let Some(pace) = ask_number(
+                display,
+                &format!(
+                    "Seconds each turn stays on screen in a replay? (0 to wait for a key, at most {})",
+                    MAX_PACE_SECONDS
+                ),
+            )? {
+                settings.replay_pace_seconds = pace.min(MAX_PACE_SECONDS);
+                display.print_wrapped(
+                    &format!("Replay Pace: {}s", settings.replay_pace_seconds),
+                    Color::Green,
+                );
+            }
             false
         }
-        5 => match save_settings(settings) {
+        8 => match save_settings(settings) {
             Ok(_) => {
                 display.print_wrapped("Settings saved successfully.", Color::Green);
                 true
@@ -486,10 +573,27 @@ async fn handle_settings_selection(
     Ok(should_exit)
 }
 
+/// Asks for a whole number. Returns `None` if the player cancels or types something else.
+fn ask_number(display: &mut Display, prompt: &str) -> Result<Option<u32>, SharadError> {
+    match display.get_user_input(prompt)? {
+        Some(input) => match input.trim().parse() {
+            Ok(number) => Ok(Some(number)),
+            Err(_) => {
+                display.print_wrapped(
+                    "Please enter a whole number. No changes made.",
+                    Color::Yellow,
+                );
+                Ok(None)
+            }
+        },
+        None => Ok(None),
+    }
+}
+
 pub async fn choose_assistant(
     assistants: Vec<(String, String)>,
     display: &Display,
-) -> Result<Option<String>, SharadError> {
+) -> Result<Option<(String, String)>, SharadError> {
     let mut menu_items = assistants
         .iter()
         .map(|(_, name)| name.clone())
@@ -518,7 +622,7 @@ pub async fn choose_assistant(
                         return if selected == menu_items_count - 1 {
                             Ok(None)
                         } else {
-                            Ok(Some(assistants[selected].0.clone()))
+                            Ok(Some(assistants[selected].clone()))
                         };
                     }
                     KeyCode::Esc => {
@@ -533,7 +637,7 @@ pub async fn choose_assistant(
                                 return if index == menu_items_count - 1 {
                                     Ok(None)
                                 } else {
-                                    Ok(Some(assistants[index].0.clone()))
+                                    Ok(Some(assistants[index].clone()))
                                 };
                             }
                         }
@@ -545,59 +649,36 @@ pub async fn choose_assistant(
     }
 }
 
-pub async fn load_game_menu(display: &mut Display) -> Result<Option<Save>, SharadError> {
-    let save_dir = Path::new(SAVE_DIR);
-
-    // Check if the save directory exists
-    if !save_dir.exists() {
-        display.print_wrapped("No save folder found. Creating one now.", Color::Yellow);
-        fs::create_dir_all(save_dir)
-            .await
-            .map_err(SharadError::Io)?;
-        return Ok(None);
-    }
-
-    let mut save_files = Vec::new();
-    let mut dir = fs::read_dir(save_dir).await.map_err(SharadError::Io)?;
-
-    while let Some(entry) = dir.next_entry().await.map_err(SharadError::Io)? {
-        let path = entry.path();
-        if path.is_file() && path.extension().and_then(|os_str| os_str.to_str()) == Some("json") {
-            if let Some(file_stem) = path.file_stem().and_then(|os_str| os_str.to_str()) {
-                save_files.push(file_stem.to_string());
-            }
-        }
-    }
-
-    if save_files.is_empty() {
-        display.print_wrapped("No save files found.", Color::Yellow);
-        display.get_user_input("Press Enter to continue...")?;
+/// Lets the player pick one entry of `items`. Returns `None` when the menu is escaped.
+pub fn select_from_list(
+    display: &Display,
+    title: &str,
+    items: &[String],
+) -> Result<Option<usize>, SharadError> {
+    // There is nothing to pick, e.g. once every skill is learned
+    if items.is_empty() {
         return Ok(None);
     }
-
-    let mut menu_items = save_files;
-    menu_items.push("Return to Main Menu".to_string());
-
     let mut selected = 0;
-    let menu_items_count = menu_items.len();
+    let items_count = items.len();
 
     terminal::enable_raw_mode()?;
 
     loop {
-        draw_load_game_menu(display, &menu_items, selected)?;
+        draw_list_menu(display, title, items, selected)?;
 
         if let Event::Key(key_event) = event::read()? {
             if key_event.kind == KeyEventKind::Press {
                 match key_event.code {
                     KeyCode::Up => {
-                        selected = (selected + menu_items_count - 1) % menu_items_count;
+                        selected = (selected + items_count - 1) % items_count;
                     }
                     KeyCode::Down => {
-                        selected = (selected + 1) % menu_items_count;
+                        selected = (selected + 1) % items_count;
                     }
                     KeyCode::Enter => {
                         terminal::disable_raw_mode()?;
-                        return handle_load_game_selection(save_dir, &menu_items, selected).await;
+                        return Ok(Some(selected));
                     }
                     KeyCode::Esc => {
                         terminal::disable_raw_mode()?;
@@ -607,10 +688,10 @@ pub async fn load_game_menu(display: &mut Display) -> Result<Option<Save>, Shara
                         if let Some(index) = c
                             .to_digit(10)
                             .and_then(|d| (d as usize).checked_sub(1))
-                            .filter(|&i| i < menu_items_count)
+                            .filter(|&i| i < items_count)
                         {
                             terminal::disable_raw_mode()?;
-                            return handle_load_game_selection(save_dir, &menu_items, index).await;
+                            return Ok(Some(index));
                         }
                     }
                     _ => {}
@@ -620,21 +701,292 @@ pub async fn load_game_menu(display: &mut Display) -> Result<Option<Save>, Shara
     }
 }
 
-async fn handle_load_game_selection(
-    save_dir: &Path,
+/// What the player asked for in the load menu.
+enum SaveAction {
+    Load(usize),
+    Inspect(usize),
+    Rename(usize),
+    Branch(usize),
+    Delete(usize),
+    Export(usize),
+    Replay(usize),
+    Back,
+}
+
+pub async fn load_game_menu(display: &mut Display) -> Result<Option<Save>, SharadError> {
+    let mut selected = 0;
+    loop {
+        let saves = list_saves()?;
+        if saves.is_empty() {
+            display.print_wrapped("No save files found.", Color::Yellow);
+            display.get_user_input("Press Enter to continue...")?;
+            return Ok(None);
+        }
+
+        let mut menu_items: Vec<String> = saves
+            .iter()
+            .map(|entry| match &entry.metadata {
+                Some(metadata) => format!("{} - {}", entry.name, metadata.summary()),
+                None => format!("{} - unreadable save", entry.name),
+            })
+            .collect();
+        menu_items.push("Return to Main Menu".to_string());
+        selected = selected.min(menu_items.len() - 1);
+
+        let action = choose_save_action(display, &saves, &menu_items, &mut selected)?;
+        let result = match action {
+            SaveAction::Load(index) => match load_save(display, &saves[index].name) {
+                Ok(Some(save)) => return Ok(Some(save)),
+                result => result.map(|_| ()),
+            },
+            SaveAction::Back => return Ok(None),
+            SaveAction::Inspect(index) => inspect_save(display, &saves[index].name),
+            SaveAction::Rename(index) => rename_save_prompt(display, &saves[index].name),
+            SaveAction::Branch(index) => branch_save_prompt(display, &saves[index].name).await,
+            SaveAction::Delete(index) => delete_save_prompt(display, &saves[index].name),
+            SaveAction::Export(index) => export_save_prompt(display, &saves[index].name),
+            SaveAction::Replay(index) => replay_save_prompt(display, &saves[index].name).await,
+        };
+        if let Err(e) = result {
+            display.print_wrapped(&e.to_string(), Color::Red);
+            display.get_user_input("Press Enter to continue...")?;
+        }
+        display_art(display)?;
+    }
+}
+
+/// Runs the load menu until the player picks a save or an action on one.
+fn choose_save_action(
+    display: &Display,
+    saves: &[SaveEntry],
     menu_items: &[String],
-    selected: usize,
-) -> Result<Option<Save>, SharadError> {
-    if selected == menu_items.len() - 1 {
-        Ok(None)
-    } else {
-        let save_file = save_dir.join(format!("{}.json", menu_items[selected]));
-        let data = fs::read_to_string(save_file)
-            .await
-            .map_err(SharadError::Io)?;
-        let save: Save = serde_json::from_str(&data).map_err(SharadError::SerdeJson)?;
-        Ok(Some(save))
+    selected: &mut usize,
+) -> Result<SaveAction, SharadError> {
+    let menu_items_count = menu_items.len();
+
+    terminal::enable_raw_mode()?;
+
+    let action = loop {
+        let details = saves
+            .get(*selected)
+            .and_then(|entry| entry.metadata.as_ref())
+            .map(SaveMetadata::details)
+            .unwrap_or_default();
+        draw_load_game_menu(display, menu_items, *selected, &details)?;
+
+        if let Event::Key(key_event) = event::read()? {
+            if key_event.kind == KeyEventKind::Press {
+                // Actions only apply to saves, not to the last entry
+                let on_save = *selected < saves.len();
+                match key_event.code {
+                    KeyCode::Up => {
+                        *selected = (*selected + menu_items_count - 1) % menu_items_count;
+                    }
+                    KeyCode::Down => {
+                        *selected = (*selected + 1) % menu_items_count;
+                    }
+                    KeyCode::Enter if on_save => break SaveAction::Load(*selected),
+                    KeyCode::Enter | KeyCode::Esc => break SaveAction::Back,
+                    KeyCode::Char('i') if on_save => break SaveAction::Inspect(*selected),
+                    KeyCode::Char('r') if on_save => break SaveAction::Rename(*selected),
+                    KeyCode::Char('b') if on_save => break SaveAction::Branch(*selected),
+                    KeyCode::Char('d') if on_save => break SaveAction::Delete(*selected),
+                    KeyCode::Char('e') if on_save => break SaveAction::Export(*selected),
+                    KeyCode::Char('p') if on_save => break SaveAction::Replay(*selected),
+                    KeyCode::Char(c) => {
+                        if let Some(index) = c
+                            .to_digit(10)
+                            .and_then(|d| (d as usize).checked_sub(1))
+                            .filter(|&i| i < menu_items_count)
+                        {
+                            break if index < saves.len() {
+                                SaveAction::Load(index)
+                            } else {
+                                SaveAction::Back
+                            };
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    };
+
+    terminal::disable_raw_mode()?;
+    Ok(action)
+}
+
+/// Reads the chosen save, offering its backup when the save itself cannot be read.
+fn load_save(display: &mut Display, name: &str) -> Result<Option<Save>, SharadError> {
+    let error = match Save::read(name) {
+        Ok(save) => return Ok(Some(save)),
+        Err(error) => error,
+    };
+    if !has_backup(name) {
+        return Err(error);
+    }
+
+    display.print_wrapped(
+        &format!("'{}' could not be read: {}", name, error),
+        Color::Red,
+    );
+    let confirm = display
+        .get_user_input("Restore the version saved before it? (y/n)")?
+        .map(|s| s.to_lowercase());
+    match confirm.as_deref() {
+        Some("y") | Some("yes") => restore_backup(name).map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// Writes the save's story as a book, in the format the player picks.
+fn export_save_prompt(display: &mut Display, name: &str) -> Result<(), SharadError> {
+    let choices: Vec<String> = ExportFormat::ALL
+        .iter()
+        .enumerate()
+        .map(|(i, format)| format!("{}. {}", i + 1, format))
+        .collect();
+    let Some(choice) =
+        display.get_user_input(&format!("Export '{}' as {}?", name, choices.join(", ")))?
+    else {
+        return Ok(());
+    };
+    let Some(format) = choice
+        .trim()
+        .parse::<usize>()
+        .ok()
+        .and_then(|choice| ExportFormat::ALL.get(choice.wrapping_sub(1)))
+    else {
+        display.print_wrapped("Export cancelled.", Color::Yellow);
+        return Ok(());
+    };
+    let include_dice = display
+        .get_user_input("Include dice results? (y/n)")?
+        .map(|s| s.to_lowercase());
+    let include_dice = matches!(include_dice.as_deref(), Some("y") | Some("yes"));
+
+    let save = Save::read(name)?;
+    let path = export_save(&save, *format, &load_settings()?.language, include_dice)?;
+    display.print_wrapped(
+        &format!("Story exported to {}", path.display()),
+        Color::Green,
+    );
+    display.get_user_input("Press Enter to continue...")?;
+    Ok(())
+}
+
+/// Plays the save's story back from its transcript.
+async fn replay_save_prompt(display: &mut Display, name: &str) -> Result<(), SharadError> {
+    let save = Save::read(name)?;
+    display_art(display)?;
+    replay_save(display, &save, name, &load_settings()?).await?;
+    display.get_user_input("Press Enter to continue...")?;
+    Ok(())
+}
+
+/// Turns of the transcript shown when inspecting a save.
+const INSPECT_HISTORY: usize = 6;
+
+fn inspect_save(display: &mut Display, name: &str) -> Result<(), SharadError> {
+    let save = Save::read(name)?;
+    display_art(display)?;
+    display.print_header(name);
+    display.print_wrapped(&save.metadata.summary(), Color::Green);
+    for line in save.metadata.details() {
+        display.print_wrapped(&line, Color::White);
+    }
+
+    let game_state = &save.game_state;
+    display.print_wrapped(&game_state.clock.status_line(), Color::Cyan);
+    if let Some(location) = &game_state.location {
+        display.print_wrapped(&format!("Location: {}", location), Color::Cyan);
+    }
+    if let Some(recap) = game_state.campaign_recap() {
+        display.print_wrapped(&recap, Color::Cyan);
+    }
+
+    match transcript::read(&save.thread_id) {
+        Ok(entries) => {
+            let history: Vec<_> = entries
+                .iter()
+                .filter_map(|entry| Some((entry.role, entry.text()?)))
+                .collect();
+            display.print_separator(Color::Cyan);
+            display.print_wrapped("Recent history:", Color::Yellow);
+            for (role, text) in history
+                .iter()
+                .skip(history.len().saturating_sub(INSPECT_HISTORY))
+            {
+                match role {
+                    Role::User => display.print_wrapped(&format!("You: {}", text), Color::Blue),
+                    Role::Assistant => {
+                        display.print_wrapped(&format!("Game Master: {}", text), Color::Green)
+                    }
+                }
+            }
+        }
+        Err(_) => display.print_wrapped(
+            "No local transcript yet. It is fetched the next time this save is played.",
+            Color::Yellow,
+        ),
     }
+    display.print_separator(Color::Yellow);
+    display.get_user_input("Press Enter to continue...")?;
+    Ok(())
+}
+
+fn rename_save_prompt(display: &mut Display, name: &str) -> Result<(), SharadError> {
+    let Some(new_name) = display.get_user_input(&format!("New name for '{}':", name))? else {
+        return Ok(());
+    };
+    rename_save(name, &new_name)?;
+    display.print_wrapped(
+        &format!("'{}' renamed to '{}'.", name, new_name.trim()),
+        Color::Green,
+    );
+    Ok(())
+}
+
+/// Copies a save and its conversation, so the story can go another way from there.
+async fn branch_save_prompt(display: &mut Display, name: &str) -> Result<(), SharadError> {
+    let Some(new_name) =
+        display.get_user_input(&format!("Name of the new branch of '{}':", name))?
+    else {
+        return Ok(());
+    };
+    validate_new_name(&new_name)?;
+
+    let mut branch = Save::read(name)?;
+    display.print_wrapped("Copying the conversation...", Color::Yellow);
+    branch_thread(&Client::new(), &mut branch).await?;
+    branch.name = Some(new_name.trim().to_string());
+    // A new game as far as quickload is concerned, since its thread differs
+    branch.metadata.created = chrono::Local::now().to_rfc3339();
+    branch.write()?;
+    display.print_wrapped(
+        &format!("'{}' now branches off '{}'.", new_name.trim(), name),
+        Color::Green,
+    );
+    Ok(())
+}
+
+fn delete_save_prompt(display: &mut Display, name: &str) -> Result<(), SharadError> {
+    let confirm = display
+        .get_user_input(&format!(
+            "Delete '{}' with its transcript, audio and portraits? Files other saves use are kept. (y/n)",
+            name
+        ))?
+        .map(|s| s.to_lowercase());
+    if !matches!(confirm.as_deref(), Some("y") | Some("yes")) {
+        return Ok(());
+    }
+
+    let removed = delete_save(name)?;
+    display.print_wrapped(
+        &format!("Deleted '{}' ({} files).", name, removed.len()),
+        Color::Green,
+    );
+    Ok(())
 }
 
 fn draw_assistant_menu(
@@ -672,6 +1024,7 @@ fn draw_load_game_menu(
     display: &Display,
     menu_items: &[String],
     selected: usize,
+    details: &[String],
 ) -> Result<(), SharadError> {
     clear_menu_area()?;
 
@@ -680,6 +1033,13 @@ fn draw_load_game_menu(
     // Print the "Load Game" title
     print_centered_line(display, "Load Game", Color::Green, current_line)?;
     current_line += 1;
+    print_centered_line(
+        display,
+        "Enter: load  I: inspect  R: rename  B: branch  D: delete  E: export  P: replay  Esc: back",
+        Color::DarkGrey,
+        current_line,
+    )?;
+    current_line += 1;
 
     // Add an empty line after the title
     current_line += 1;
@@ -696,6 +1056,44 @@ fn draw_load_game_menu(
         current_line += 1;
     }
 
+    // Describe the highlighted save below the list
+    if !details.is_empty() {
+        current_line += 1;
+        let width = (terminal::size()?.0 as usize).saturating_sub(4);
+        for line in details.iter().flat_map(|line| wrap(line, width)) {
+            print_centered_line(display, &line, Color::Cyan, current_line)?;
+            current_line += 1;
+        }
+    }
+
+    Ok(())
+}
+
+fn draw_list_menu(
+    display: &Display,
+    title: &str,
+    menu_items: &[String],
+    selected: usize,
+) -> Result<(), SharadError> {
+    clear_menu_area()?;
+
+    let mut current_line = ART_HEIGHT + 1; // Start one line below the art
+
+    print_centered_line(display, title, Color::Green, current_line)?;
+    current_line += 2;
+
+    for (i, item) in menu_items.iter().enumerate() {
+        let prefix = if i == selected { "> " } else { "  " };
+        let color = if i == selected {
+            Color::Green
+        } else {
+            Color::White
+        };
+        let numbered_item = format!("{}{}. {}", prefix, i + 1, item);
+        print_centered_line(display, &numbered_item, color, current_line)?;
+        current_line += 1;
+    }
+
     Ok(())
 }
 
@@ -714,11 +1112,14 @@ pub fn display_art(display: &mut Display) -> Result<(), SharadError> {
     Ok(())
 }
 
-pub const SETTINGS_MENU_ITEMS: [&str; 6] = [
+pub const SETTINGS_MENU_ITEMS: [&str; 9] = [
     "Change Language",
     "Change OpenAI API Key",
     "Toggle Audio Output",
     "Toggle Audio Input",
-    "Toggle Debug Mode",
+    "Log Filter",
+    "Autosave Every N Turns",
+    "Autosave Slots",
+    "Replay Pace",
     "Back to Main Menu",
 ];
@@ -0,0 +1,326 @@
+use crate::error::SharadError;
+use async_openai::types::FunctionObject;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fmt;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Disposition {
+    Hostile,
+    Unfriendly,
+    #[default]
+    Neutral,
+    Friendly,
+    Allied,
+}
+
+impl Disposition {
+    const ALL: [Disposition; 5] = [
+        Disposition::Hostile,
+        Disposition::Unfriendly,
+        Disposition::Neutral,
+        Disposition::Friendly,
+        Disposition::Allied,
+    ];
+
+    /// Moves the disposition up (positive) or down (negative) the scale, stopping at either end.
+    pub fn shift(self, steps: i32) -> Disposition {
+        let index = Disposition::ALL
+            .iter()
+            .position(|disposition| *disposition == self)
+            .unwrap_or(2) as i32;
+        Disposition::ALL[(index + steps).clamp(0, 4) as usize]
+    }
+}
+
+impl fmt::Display for Disposition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            Disposition::Hostile => "Hostile",
+            Disposition::Unfriendly => "Unfriendly",
+            Disposition::Neutral => "Neutral",
+            Disposition::Friendly => "Friendly",
+            Disposition::Allied => "Allied",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Npc {
+    pub name: String,
+    pub description: String,
+    #[serde(default)]
+    pub faction: Option<String>,
+    #[serde(default)]
+    pub disposition: Disposition,
+    /// Contact ratings, only set for NPCs the player can call on.
+    #[serde(default)]
+    pub connection: Option<u8>,
+    #[serde(default)]
+    pub loyalty: Option<u8>,
+    #[serde(default)]
+    pub last_seen: Option<String>,
+    #[serde(default)]
+    pub portrait: Option<String>,
+    #[serde(default)]
+    pub notes: Vec<String>,
+}
+
+impl Npc {
+    pub fn is_contact(&self) -> bool {
+        self.connection.is_some()
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!(self)
+    }
+
+    pub fn summary(&self) -> String {
+        let mut summary = self.name.clone();
+        if let Some(faction) = &self.faction {
+            summary += &format!(" ({})", faction);
+        }
+        summary += &format!(" - {}", self.disposition);
+        if let (Some(connection), Some(loyalty)) = (self.connection, self.loyalty) {
+            summary += &format!(" - Connection {} / Loyalty {}", connection, loyalty);
+        }
+        if let Some(last_seen) = &self.last_seen {
+            summary += &format!(" - last seen: {}", last_seen);
+        }
+        summary
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct NpcRegistry {
+    pub npcs: Vec<Npc>,
+}
+
+impl NpcRegistry {
+    pub fn find(&self, name: &str) -> Option<&Npc> {
+        self.npcs
+            .iter()
+            .find(|npc| npc.name.eq_ignore_ascii_case(name.trim()))
+    }
+
+    pub fn find_mut(&mut self, name: &str) -> Option<&mut Npc> {
+        self.npcs
+            .iter_mut()
+            .find(|npc| npc.name.eq_ignore_ascii_case(name.trim()))
+    }
+
+    pub fn contacts(&self) -> impl Iterator<Item = &Npc> {
+        self.npcs.iter().filter(|npc| npc.is_contact())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.npcs.is_empty()
+    }
+
+    /// Short list given to the Game Master so it reuses existing characters.
+    pub fn to_context(&self) -> Value {
+        json!(self
+            .npcs
+            .iter()
+            .map(|npc| json!({
+                "name": npc.name,
+                "faction": npc.faction,
+                "disposition": npc.disposition,
+                "is_contact": npc.is_contact(),
+            }))
+            .collect::<Vec<_>>())
+    }
+}
+
+#[derive(Deserialize)]
+struct CreateNpcArgs {
+    name: String,
+    description: String,
+    faction: Option<String>,
+    #[serde(default)]
+    disposition: Disposition,
+    connection: Option<u8>,
+    loyalty: Option<u8>,
+    last_seen: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct LookupNpcArgs {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    faction: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct UpdateNpcArgs {
+    name: String,
+    description: Option<String>,
+    faction: Option<String>,
+    disposition: Option<Disposition>,
+    connection: Option<u8>,
+    loyalty: Option<u8>,
+    last_seen: Option<String>,
+    note: Option<String>,
+}
+
+pub fn create_npc(registry: &mut NpcRegistry, args: Value) -> Result<Value, SharadError> {
+    let args: CreateNpcArgs = serde_json::from_value(args)?;
+    if let Some(existing) = registry.find(&args.name) {
+        return Err(SharadError::Message(format!(
+            "An NPC named '{}' already exists. Use update_npc instead: {}",
+            existing.name,
+            existing.to_json()
+        )));
+    }
+
+    let npc = Npc {
+        name: args.name.trim().to_string(),
+        description: args.description,
+        faction: args.faction,
+        disposition: args.disposition,
+        connection: args.connection.map(|c| c.clamp(1, 12)),
+        loyalty: args.loyalty.map(|l| l.clamp(1, 6)),
+        last_seen: args.last_seen,
+        portrait: None,
+        notes: Vec::new(),
+    };
+    let output = npc.to_json();
+    registry.npcs.push(npc);
+    Ok(output)
+}
+
+pub fn lookup_npc(registry: &NpcRegistry, args: Value) -> Result<Value, SharadError> {
+    let args: LookupNpcArgs = serde_json::from_value(args)?;
+    let query = args.name.map(|name| name.to_lowercase());
+    let faction = args.faction.map(|faction| faction.to_lowercase());
+
+    let matches: Vec<Value> = registry
+        .npcs
+        .iter()
+        .filter(|npc| {
+            query
+                .as_ref()
+                .is_none_or(|q| npc.name.to_lowercase().contains(q.as_str()))
+        })
+        .filter(|npc| {
+            faction.as_ref().is_none_or(|f| {
+                npc.faction
+                    .as_ref()
+                    .is_some_and(|npc_faction| npc_faction.to_lowercase().contains(f.as_str()))
+            })
+        })
+        .map(Npc::to_json)
+        .collect();
+
+    Ok(json!({ "matches": matches }))
+}
+
+pub fn update_npc(registry: &mut NpcRegistry, args: Value) -> Result<Value, SharadError> {
+    let args: UpdateNpcArgs = serde_json::from_value(args)?;
+    let npc = registry.find_mut(&args.name).ok_or_else(|| {
+        SharadError::Message(format!(
+            "No NPC named '{}'. Use create_npc first.",
+            args.name
+        ))
+    })?;
+
+    if let Some(description) = args.description {
+        npc.description = description;
+    }
+    if let Some(faction) = args.faction {
+        npc.faction = Some(faction);
+    }
+    if let Some(disposition) = args.disposition {
+        npc.disposition = disposition;
+    }
+    if let Some(connection) = args.connection {
+        npc.connection = Some(connection.clamp(1, 12));
+    }
+    if let Some(loyalty) = args.loyalty {
+        npc.loyalty = Some(loyalty.clamp(1, 6));
+    }
+    if let Some(last_seen) = args.last_seen {
+        npc.last_seen = Some(last_seen);
+    }
+    if let Some(note) = args.note {
+        npc.notes.push(note);
+    }
+    Ok(npc.to_json())
+}
+
+pub fn tool_definitions() -> Vec<FunctionObject> {
+    let disposition = json!({
+        "type": "string",
+        "enum": ["hostile", "unfriendly", "neutral", "friendly", "allied"]
+    });
+
+    vec![
+        FunctionObject {
+            name: "create_npc".to_string(),
+            description: Some(
+                "Register a new recurring character so they stay consistent across the campaign"
+                    .to_string(),
+            ),
+            parameters: Some(json!({
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string" },
+                    "description": {
+                        "type": "string",
+                        "description": "Appearance, personality and role in the story"
+                    },
+                    "faction": { "type": "string" },
+                    "disposition": disposition,
+                    "connection": {
+                        "type": "integer",
+                        "description": "Contact Connection rating (1-12), only for the player's contacts"
+                    },
+                    "loyalty": {
+                        "type": "integer",
+                        "description": "Contact Loyalty rating (1-6), only for the player's contacts"
+                    },
+                    "last_seen": { "type": "string", "description": "Where the NPC was last seen" }
+                },
+                "required": ["name", "description"]
+            })),
+        },
+        FunctionObject {
+            name: "lookup_npc".to_string(),
+            description: Some(
+                "Look up known NPCs by name or faction before bringing them back into the story"
+                    .to_string(),
+            ),
+            parameters: Some(json!({
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string", "description": "Full or partial name" },
+                    "faction": { "type": "string" }
+                }
+            })),
+        },
+        FunctionObject {
+            name: "update_npc".to_string(),
+            description: Some(
+                "Update a known NPC after an interaction (disposition, location, ratings, notes)"
+                    .to_string(),
+            ),
+            parameters: Some(json!({
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string" },
+                    "description": { "type": "string" },
+                    "faction": { "type": "string" },
+                    "disposition": disposition,
+                    "connection": { "type": "integer" },
+                    "loyalty": { "type": "integer" },
+                    "last_seen": { "type": "string" },
+                    "note": { "type": "string", "description": "Something worth remembering about this NPC" }
+                },
+                "required": ["name"]
+            })),
+        },
+    ]
+}
@@ -0,0 +1,308 @@
+use crate::error::SharadError;
+use crate::save::{list_saves, Save};
+use crate::settings::load_settings;
+use crate::transcript;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Keeps settings, data and cache under one directory, e.g. for a portable install.
+pub const HOME_VAR: &str = "SHARAD_HOME";
+/// Moves only the data directory, where saves, transcripts and portraits are kept.
+pub const DATA_DIR_VAR: &str = "SHARAD_DATA_DIR";
+const APP_NAME: &str = "sharad";
+/// Where earlier versions kept everything, relative to the working directory.
+const LEGACY_DIR: &str = "./data";
+/// Left in the old log directory once its files have moved, so they are only moved once.
+const MOVED_NOTE: &str = "MOVED.txt";
+
+enum Kind {
+    Config,
+    Data,
+    Cache,
+}
+
+fn env_dir(var: &str) -> Option<PathBuf> {
+    env::var_os(var)
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+}
+
+/// The platform's directory for this kind of file: XDG directories on Linux, `Library` on
+/// macOS and `AppData` on Windows.
+fn platform_dir(kind: Kind) -> PathBuf {
+    let base = if cfg!(target_os = "windows") {
+        env_dir(match kind {
+            Kind::Config | Kind::Data => "APPDATA",
+            Kind::Cache => "LOCALAPPDATA",
+        })
+    } else if cfg!(target_os = "macos") {
+        env_dir("HOME").map(|home| {
+            home.join(match kind {
+                Kind::Config | Kind::Data => "Library/Application Support",
+                Kind::Cache => "Library/Caches",
+            })
+        })
+    } else {
+        let (var, fallback) = match kind {
+            Kind::Config => ("XDG_CONFIG_HOME", ".config"),
+            Kind::Data => ("XDG_DATA_HOME", ".local/share"),
+            Kind::Cache => ("XDG_CACHE_HOME", ".cache"),
+        };
+        // The XDG spec says to ignore relative paths
+        env_dir(var)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| Some(env_dir("HOME")?.join(fallback)))
+    };
+    // Without a home directory, keep the files where earlier versions did
+    base.map(|base| base.join(APP_NAME))
+        .unwrap_or_else(|| PathBuf::from(LEGACY_DIR))
+}
+
+pub fn config_dir() -> &'static Path {
+    static DIR: OnceLock<PathBuf> = OnceLock::new();
+    DIR.get_or_init(|| env_dir(HOME_VAR).unwrap_or_else(|| platform_dir(Kind::Config)))
+}
+
+/// The data directory, from `SHARAD_DATA_DIR`, the `data_dir` setting or `SHARAD_HOME`, in
+/// that order, else the platform's.
+pub fn data_dir() -> &'static Path {
+    static DIR: OnceLock<PathBuf> = OnceLock::new();
+    DIR.get_or_init(|| {
+        env_dir(DATA_DIR_VAR)
+            .or_else(|| {
+                // Relative paths would depend on where the game was started from
+                let data_dir = PathBuf::from(load_settings().ok()?.data_dir?.trim());
+                data_dir.is_absolute().then_some(data_dir)
+            })
+            .or_else(|| env_dir(HOME_VAR))
+            .unwrap_or_else(|| platform_dir(Kind::Data))
+    })
+}
+
+pub fn cache_dir() -> &'static Path {
+    static DIR: OnceLock<PathBuf> = OnceLock::new();
+    DIR.get_or_init(|| match env_dir(HOME_VAR) {
+        Some(home) => home.join("cache"),
+        None => platform_dir(Kind::Cache),
+    })
+}
+
+pub fn settings_file() -> PathBuf {
+    config_dir().join("settings.json")
+}
+
+pub fn save_dir() -> PathBuf {
+    data_dir().join("saves")
+}
+
+pub fn transcript_dir() -> PathBuf {
+    data_dir().join("transcripts")
+}
+
+/// Generated portraits, which saves refer to.
+pub fn image_dir() -> PathBuf {
+    data_dir().join("images")
+}
+
+pub fn export_dir() -> PathBuf {
+    data_dir().join("exports")
+}
+
+/// Narration read aloud, kept to replay it without asking for it again.
+pub fn audio_dir() -> PathBuf {
+    cache_dir().join("audio")
+}
+
+pub fn recording_dir() -> PathBuf {
+    cache_dir().join("recordings")
+}
+
+pub fn log_dir() -> PathBuf {
+    cache_dir().join("logs")
+}
+
+/// Cartridges and tables shipped with the game, in `data` next to the executable. Debug builds
+/// run from the source tree use the one there.
+fn shipped_dir() -> &'static Path {
+    static DIR: OnceLock<PathBuf> = OnceLock::new();
+    DIR.get_or_init(|| {
+        let next_to_exe = env::current_exe()
+            .ok()
+            .and_then(|exe| Some(exe.parent()?.join("data")));
+        // Release builds must not carry the path of the machine they were built on
+        #[cfg(debug_assertions)]
+        let source_tree = Some(Path::new(env!("CARGO_MANIFEST_DIR")).join("data"));
+        #[cfg(not(debug_assertions))]
+        let source_tree: Option<PathBuf> = None;
+        next_to_exe
+            .iter()
+            .chain(&source_tree)
+            .find(|dir| dir.is_dir())
+            .or(next_to_exe.as_ref())
+            .cloned()
+            .unwrap_or_else(|| PathBuf::from(LEGACY_DIR))
+    })
+}
+
+/// A cartridge or table file, e.g. `cartridges/shadowrun/shop.json`: the player's own in the
+/// data directory if there is one, else the shipped one.
+pub fn asset_file(path: &Path) -> PathBuf {
+    let custom = data_dir().join(path);
+    if custom.is_file() {
+        custom
+    } else {
+        shipped_dir().join(path)
+    }
+}
+
+/// The files of an asset directory, shipped and the player's, sorted by name. The player's
+/// replace shipped files of the same name.
+pub fn asset_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = BTreeMap::new();
+    for base in [shipped_dir(), data_dir()] {
+        let Ok(entries) = fs::read_dir(base.join(dir)) else {
+            continue;
+        };
+        for path in entries.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+            if let Some(name) = path.file_name().filter(|_| path.is_file()) {
+                files.insert(name.to_os_string(), path);
+            }
+        }
+    }
+    files.into_values().collect()
+}
+
+/// Moves a file, copying it when it goes to another file system. Files already at the
+/// destination are kept.
+fn move_file(from: &Path, to: &Path) -> Result<bool, SharadError> {
+    if to.exists() {
+        return Ok(false);
+    }
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::rename(from, to).is_err() {
+        fs::copy(from, to)?;
+        fs::remove_file(from)?;
+    }
+    Ok(true)
+}
+
+fn move_dir(from: &Path, to: &Path) -> Result<usize, SharadError> {
+    let mut moved = 0;
+    for entry in fs::read_dir(from)? {
+        let path = entry?.path();
+        if let Some(name) = path.file_name().filter(|_| path.is_file()) {
+            moved += move_file(&path, &to.join(name))? as usize;
+        }
+    }
+    Ok(moved)
+}
+
+/// Where a file of the old log directory goes, by its kind.
+fn legacy_destination(path: &Path) -> Option<PathBuf> {
+    let name = path.file_name()?.to_str()?;
+    let extension = path.extension().and_then(OsStr::to_str)?.to_lowercase();
+    let dir = match extension.as_str() {
+        "png" | "jpg" | "jpeg" | "webp" => image_dir(),
+        "mp3" | "wav" if name.starts_with("recording_") => recording_dir(),
+        "mp3" | "wav" => audio_dir(),
+        "txt" if name.starts_with("log_") => log_dir(),
+        _ => return None,
+    };
+    Some(dir.join(name))
+}
+
+/// Moves what earlier versions kept in `./data` to the platform directories, the first time the
+/// game runs from a directory that has them. Saves and transcripts are updated to point to the
+/// portraits and audio where they now are. Returns what was moved, to tell the player.
+pub fn migrate_legacy_data() -> Result<Vec<String>, SharadError> {
+    let legacy_logs = Path::new(LEGACY_DIR).join("logs");
+    if !legacy_logs.is_dir() || legacy_logs.join(MOVED_NOTE).exists() {
+        return Ok(Vec::new());
+    }
+    let mut report = Vec::new();
+
+    for file in ["settings.json", "settings.json.bak"] {
+        let from = legacy_logs.join(file);
+        if from.is_file() && move_file(&from, &config_dir().join(file))? && file == "settings.json"
+        {
+            report.push(format!("settings: {}", config_dir().display()));
+        }
+    }
+    for (dir, destination) in [("saves", save_dir()), ("transcripts", transcript_dir())] {
+        let from = legacy_logs.join(dir);
+        if from.is_dir() {
+            let moved = move_dir(&from, &destination)?;
+            report.push(format!("{} {}: {}", moved, dir, destination.display()));
+        }
+    }
+    let exports = Path::new(LEGACY_DIR).join("exports");
+    if exports.is_dir() {
+        move_dir(&exports, &export_dir())?;
+    }
+
+    // Portraits and audio, by file name, with where they are now
+    let mut moved_media = HashMap::new();
+    for entry in fs::read_dir(&legacy_logs)? {
+        let path = entry?.path();
+        let Some(destination) = legacy_destination(&path) else {
+            continue;
+        };
+        move_file(&path, &destination)?;
+        if let Some(name) = path.file_name().and_then(OsStr::to_str) {
+            moved_media.insert(name.to_string(), destination.display().to_string());
+        }
+    }
+    if !moved_media.is_empty() {
+        report.push(format!(
+            "{} images, recordings and logs: {} and {}",
+            moved_media.len(),
+            data_dir().display(),
+            cache_dir().display()
+        ));
+    }
+    let relocate = |path: &str| {
+        let name = Path::new(path).file_name()?.to_str()?;
+        moved_media.get(name).cloned()
+    };
+    for entry in list_saves()? {
+        if let Ok(mut save) = Save::read(&entry.name) {
+            save.game_state.relocate_media(relocate);
+            save.write_file()?;
+        }
+    }
+    if let Ok(entries) = fs::read_dir(transcript_dir()) {
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension() != Some(OsStr::new("jsonl")) {
+                continue;
+            }
+            let Some(thread_id) = path.file_stem().and_then(OsStr::to_str) else {
+                continue;
+            };
+            let mut transcript = transcript::read(thread_id)?;
+            for entry in &mut transcript {
+                if let Some(audio) = entry.audio.as_deref().and_then(relocate) {
+                    entry.audio = Some(audio);
+                }
+            }
+            transcript::write(thread_id, &transcript)?;
+        }
+    }
+
+    fs::write(
+        legacy_logs.join(MOVED_NOTE),
+        format!(
+            "Sharad now keeps its files in:\nSettings: {}\nData: {}\nCache: {}\n",
+            config_dir().display(),
+            data_dir().display(),
+            cache_dir().display()
+        ),
+    )?;
+    Ok(report)
+}
@@ -0,0 +1,302 @@
+use crate::assistant::fetch_transcript;
+use crate::audio::play_audio_file;
+use crate::display::Display;
+use crate::error::SharadError;
+use crate::save::Save;
+use crate::settings::Settings;
+use crate::transcript::{self, Role, TranscriptEntry};
+use async_openai::Client;
+use chrono::{DateTime, Local};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::style::Color;
+use crossterm::terminal;
+use serde_json::Value;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task;
+
+/// How often keys are read while a turn is on screen.
+const TICK: Duration = Duration::from_millis(100);
+/// Slowest pace the player can set during a replay.
+pub const MAX_PACE_SECONDS: u32 = 60;
+const CONTROLS: &str = "Enter/Right: next  Left: previous  J: jump to turn  Space: pause  +/-: faster/slower  A: audio  Esc: stop";
+
+/// One exchange of the story: the player's action and the Game Master's answer.
+#[derive(Default)]
+struct Turn {
+    timestamp: Option<DateTime<Local>>,
+    action: Option<String>,
+    /// Summary of the test rolled for the action.
+    test: Option<String>,
+    reasoning: Option<String>,
+    narration: Option<String>,
+    /// Narration read aloud when the turn was played, if it is still cached.
+    audio: Option<PathBuf>,
+}
+
+/// A text field of a message's JSON, following `path`.
+fn json_text(json: Option<&Value>, path: &[&str]) -> Option<String> {
+    let mut value = json?;
+    for key in path {
+        value = value.get(key)?;
+    }
+    value.as_str().map(str::to_string)
+}
+
+/// Groups a transcript into turns. The opening narration is a turn without an action, and
+/// answers the Game Master revised are replaced by the revision.
+fn turns(entries: &[TranscriptEntry]) -> Vec<Turn> {
+    let mut turns: Vec<Turn> = Vec::new();
+    for entry in entries {
+        let Some(text) = entry.text() else {
+            continue;
+        };
+        let json = serde_json::from_str::<Value>(&entry.content).ok();
+        let timestamp = DateTime::parse_from_rfc3339(&entry.timestamp)
+            .ok()
+            .map(|timestamp| timestamp.with_timezone(&Local));
+        match entry.role {
+            Role::User => turns.push(Turn {
+                timestamp,
+                action: Some(text),
+                test: json_text(json.as_ref(), &["proposed_test", "summary"]),
+                ..Turn::default()
+            }),
+            Role::Assistant => {
+                if turns.is_empty() {
+                    turns.push(Turn {
+                        timestamp,
+                        ..Turn::default()
+                    });
+                }
+                if let Some(turn) = turns.last_mut() {
+                    turn.reasoning = json_text(json.as_ref(), &["reasoning"]);
+                    turn.narration = Some(text);
+                    turn.audio = entry.audio.as_ref().map(PathBuf::from);
+                }
+            }
+        }
+    }
+    turns
+}
+
+/// Shows a turn the way it was shown when it was played.
+fn show_turn(display: &mut Display, turns: &[Turn], index: usize) {
+    let turn = &turns[index];
+    let date = turn
+        .timestamp
+        .map(|timestamp| timestamp.format(" - %Y-%m-%d %H:%M").to_string())
+        .unwrap_or_default();
+    display.print_separator(Color::Cyan);
+    display.print_wrapped(
+        &format!("Turn {} of {}{}", index + 1, turns.len(), date),
+        Color::Yellow,
+    );
+    if let Some(action) = &turn.action {
+        display.print_wrapped(action, Color::Blue);
+    }
+    if let Some(test) = &turn.test {
+        display.print_wrapped(&format!("Test: {}", test), Color::Cyan);
+    }
+    if let Some(reasoning) = &turn.reasoning {
+        display.print_debug(&format!("reasoning: {}", reasoning), Color::Magenta);
+    }
+    if let Some(narration) = &turn.narration {
+        display.print_wrapped(narration, Color::Green);
+    }
+}
+
+/// What the player can change while the replay runs.
+struct Controls {
+    /// Seconds each turn stays on screen; 0 waits for a key.
+    pace: u32,
+    audio: bool,
+    paused: bool,
+}
+
+enum Step {
+    Next,
+    Previous,
+    Jump,
+    Stop,
+}
+
+/// Prints a line while keys are read in raw mode.
+fn note(display: &mut Display, text: &str) -> Result<(), SharadError> {
+    terminal::disable_raw_mode()?;
+    display.print_wrapped(text, Color::Yellow);
+    terminal::enable_raw_mode()?;
+    Ok(())
+}
+
+fn pace_note(pace: u32) -> String {
+    match pace {
+        0 => "Pace: waiting for a key".to_string(),
+        pace => format!("Pace: {}s per turn", pace),
+    }
+}
+
+/// Reads keys until the player moves on, or until the turn's narration and pace have run out.
+fn read_step(
+    display: &mut Display,
+    controls: &mut Controls,
+    stop_audio: &AtomicBool,
+    audio_playing: &dyn Fn() -> bool,
+) -> Result<Step, SharadError> {
+    let mut waited = Duration::ZERO;
+    loop {
+        if !event::poll(TICK)? {
+            if !controls.paused && controls.pace > 0 && !audio_playing() {
+                waited += TICK;
+                if waited >= Duration::from_secs(controls.pace.into()) {
+                    return Ok(Step::Next);
+                }
+            }
+            continue;
+        }
+        let Event::Key(key_event) = event::read()? else {
+            continue;
+        };
+        if key_event.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key_event.code {
+            KeyCode::Enter | KeyCode::Right | KeyCode::Char('n') => return Ok(Step::Next),
+            KeyCode::Left | KeyCode::Char('p') => return Ok(Step::Previous),
+            KeyCode::Char('j') => return Ok(Step::Jump),
+            KeyCode::Esc | KeyCode::Char('q') => return Ok(Step::Stop),
+            KeyCode::Char(' ') => {
+                controls.paused = !controls.paused;
+                note(display, if controls.paused { "Paused" } else { "Resumed" })?;
+            }
+            KeyCode::Char('+') => {
+                // 0 waits for a key, so the fastest timed pace is a second
+                if controls.pace > 1 {
+                    controls.pace -= 1;
+                }
+                note(display, &pace_note(controls.pace))?;
+            }
+            KeyCode::Char('-') => {
+                controls.pace = (controls.pace + 1).min(MAX_PACE_SECONDS);
+                note(display, &pace_note(controls.pace))?;
+            }
+            KeyCode::Char('a') => {
+                controls.audio = !controls.audio;
+                if !controls.audio {
+                    stop_audio.store(true, Ordering::Relaxed);
+                }
+                note(
+                    display,
+                    if controls.audio {
+                        "Audio on"
+                    } else {
+                        "Audio off"
+                    },
+                )?;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Plays the turn's cached narration, if any, while waiting for the player or the pace.
+async fn play_turn(
+    display: &mut Display,
+    turn: &Turn,
+    controls: &mut Controls,
+) -> Result<Step, SharadError> {
+    let stop_audio = Arc::new(AtomicBool::new(false));
+    let playback = turn
+        .audio
+        .clone()
+        .filter(|path| controls.audio && path.is_file())
+        .map(|path| {
+            let stop_audio = Arc::clone(&stop_audio);
+            task::spawn_blocking(move || play_audio_file(&path, &stop_audio))
+        });
+    let audio_playing = || {
+        playback
+            .as_ref()
+            .is_some_and(|playback| !playback.is_finished())
+    };
+
+    terminal::enable_raw_mode()?;
+    let step = read_step(display, controls, &stop_audio, &audio_playing);
+    terminal::disable_raw_mode()?;
+
+    stop_audio.store(true, Ordering::Relaxed);
+    if let Some(playback) = playback {
+        if let Ok(Err(e)) = playback.await {
+            display.print_debug(&format!("Failed to play narration: {}", e), Color::Red);
+        }
+    }
+    step
+}
+
+/// Asks which turn to go to. Returns its index, or `None` to stay.
+fn ask_turn(display: &mut Display, count: usize) -> Result<Option<usize>, SharadError> {
+    let Some(input) = display.get_user_input(&format!("Jump to which turn? (1-{})", count))? else {
+        return Ok(None);
+    };
+    match input.trim().parse::<usize>() {
+        Ok(turn) if (1..=count).contains(&turn) => Ok(Some(turn - 1)),
+        _ => {
+            display.print_wrapped("There is no such turn.", Color::Yellow);
+            Ok(None)
+        }
+    }
+}
+
+/// Plays a save's story back from its transcript, turn by turn, with the narration audio
+/// kept from when it was played.
+pub async fn replay_save(
+    display: &mut Display,
+    save: &Save,
+    title: &str,
+    settings: &Settings,
+) -> Result<(), SharadError> {
+    if !transcript::exists(&save.thread_id) {
+        fetch_transcript(&Client::new(), &save.thread_id).await?;
+    }
+    let entries = transcript::until(
+        transcript::read(&save.thread_id)?,
+        save.last_message_id.as_deref(),
+    );
+    let turns = turns(&entries);
+    if turns.is_empty() {
+        return Err(SharadError::Message(
+            "There is nothing to replay yet.".into(),
+        ));
+    }
+
+    let mut controls = Controls {
+        pace: settings.replay_pace_seconds.min(MAX_PACE_SECONDS),
+        audio: settings.audio_output_enabled,
+        paused: false,
+    };
+    display.print_header(&format!("Replay: {}", title));
+    display.print_wrapped(CONTROLS, Color::Yellow);
+
+    let mut index = 0;
+    loop {
+        show_turn(display, &turns, index);
+        match play_turn(display, &turns[index], &mut controls).await? {
+            Step::Next if index + 1 < turns.len() => index += 1,
+            Step::Next => {
+                display.print_separator(Color::Yellow);
+                display.print_wrapped("End of the replay.", Color::Yellow);
+                break;
+            }
+            Step::Previous => index = index.saturating_sub(1),
+            Step::Jump => {
+                if let Some(turn) = ask_turn(display, turns.len())? {
+                    index = turn;
+                }
+            }
+            Step::Stop => break,
+        }
+    }
+    Ok(())
+}
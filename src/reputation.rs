@@ -0,0 +1,322 @@
+use crate::error::SharadError;
+use crate::npc::NpcRegistry;
+use async_openai::types::FunctionObject;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Faction standing runs from -MAX_STANDING (sworn enemies) to MAX_STANDING (trusted allies).
+const MAX_STANDING: i32 = 5;
+/// Most recent changes sent to the Game Master with each action.
+const CONTEXT_HISTORY: usize = 5;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Track {
+    StreetCred,
+    Notoriety,
+    PublicAwareness,
+    Faction,
+}
+
+impl fmt::Display for Track {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            Track::StreetCred => "Street Cred",
+            Track::Notoriety => "Notoriety",
+            Track::PublicAwareness => "Public Awareness",
+            Track::Faction => "Standing",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReputationEntry {
+    pub track: Track,
+    #[serde(default)]
+    pub faction: Option<String>,
+    pub amount: i32,
+    pub reason: String,
+    /// In-game date of the change.
+    #[serde(default)]
+    pub date: Option<String>,
+}
+
+impl ReputationEntry {
+    pub fn describe(&self) -> String {
+        let track = match &self.faction {
+            Some(faction) => format!("{} with {}", self.track, faction),
+            None => self.track.to_string(),
+        };
+        format!("{} {:+}: {}", track, self.amount, self.reason)
+    }
+}
+
+/// How the street sees the player: Street Cred for respect earned, Notoriety and Public
+/// Awareness for the heat they attract, and standing with each faction they dealt with.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Reputation {
+    #[serde(default)]
+    pub street_cred: u32,
+    #[serde(default)]
+    pub notoriety: u32,
+    #[serde(default)]
+    pub public_awareness: u32,
+    #[serde(default)]
+    pub factions: BTreeMap<String, i32>,
+    #[serde(default)]
+    pub history: Vec<ReputationEntry>,
+}
+
+impl Reputation {
+    pub fn is_empty(&self) -> bool {
+        self.history.is_empty()
+    }
+
+    /// Reuses the spelling of a faction already tracked or named on an NPC.
+    fn faction_key(&self, faction: &str, npcs: &NpcRegistry) -> String {
+        let faction = faction.trim();
+        self.factions
+            .keys()
+            .map(String::as_str)
+            .chain(npcs.npcs.iter().filter_map(|npc| npc.faction.as_deref()))
+            .find(|known| known.eq_ignore_ascii_case(faction))
+            .unwrap_or(faction)
+            .to_string()
+    }
+
+    pub fn to_context(&self) -> Value {
+        json!({
+            "street_cred": self.street_cred,
+            "notoriety": self.notoriety,
+            "public_awareness": self.public_awareness,
+            "faction_standing": self.factions,
+            "recent_changes": self
+                .history
+                .iter()
+                .rev()
+                .take(CONTEXT_HISTORY)
+                .map(ReputationEntry::describe)
+                .collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// Disposition steps a faction's members are moved from neutral at a given standing.
+fn standing_tier(standing: i32) -> i32 {
+    match standing {
+        ..=-4 => -2,
+        -3..=-2 => -1,
+        -1..=1 => 0,
+        2..=3 => 1,
+        _ => 2,
+    }
+}
+
+#[derive(Deserialize)]
+struct AdjustReputationArgs {
+    track: Track,
+    #[serde(default)]
+    faction: Option<String>,
+    amount: i32,
+    reason: String,
+}
+
+/// Records a change and, for faction standing, shifts how that faction's NPCs feel about the player.
+pub fn adjust_reputation(
+    reputation: &mut Reputation,
+    npcs: &mut NpcRegistry,
+    date: String,
+    args: Value,
+) -> Result<Value, SharadError> {
+    let args: AdjustReputationArgs = serde_json::from_value(args)?;
+    if args.amount == 0 {
+        return Err(SharadError::Message(
+            "The reputation change cannot be 0.".into(),
+        ));
+    }
+    if args.reason.trim().is_empty() {
+        return Err(SharadError::Message(
+            "Give the reason for the reputation change.".into(),
+        ));
+    }
+
+    let adjust = |value: &mut u32| *value = value.saturating_add_signed(args.amount);
+    let mut output = json!({});
+    let faction = match args.track {
+        Track::StreetCred => {
+            adjust(&mut reputation.street_cred);
+            output["street_cred"] = json!(reputation.street_cred);
+            None
+        }
+        Track::Notoriety => {
+            adjust(&mut reputation.notoriety);
+            output["notoriety"] = json!(reputation.notoriety);
+            None
+        }
+        Track::PublicAwareness => {
+            adjust(&mut reputation.public_awareness);
+            output["public_awareness"] = json!(reputation.public_awareness);
+            None
+        }
+        Track::Faction => {
+            let name = args.faction.as_deref().ok_or_else(|| {
+                SharadError::Message("Faction standing changes need a faction.".into())
+            })?;
+            let key = reputation.faction_key(name, npcs);
+            let standing = reputation.factions.entry(key.clone()).or_insert(0);
+            let previous = *standing;
+            *standing = previous
+                .saturating_add(args.amount)
+                .clamp(-MAX_STANDING, MAX_STANDING);
+
+            let steps = standing_tier(*standing) - standing_tier(previous);
+            let mut shifted = Vec::new();
+            if steps != 0 {
+                for npc in npcs.npcs.iter_mut().filter(|npc| {
+                    npc.faction
+                        .as_deref()
+                        .is_some_and(|faction| faction.eq_ignore_ascii_case(&key))
+                }) {
+                    npc.disposition = npc.disposition.shift(steps);
+                    shifted.push(format!("{} is now {}", npc.name, npc.disposition));
+                }
+            }
+            output["faction"] = json!(key);
+            output["standing"] = json!(*standing);
+            output["npcs_affected"] = json!(shifted);
+            Some(key)
+        }
+    };
+
+    reputation.history.push(ReputationEntry {
+        track: args.track,
+        faction,
+        amount: args.amount,
+        reason: args.reason,
+        date: Some(date),
+    });
+    Ok(output)
+}
+
+pub fn tool_definitions() -> Vec<FunctionObject> {
+    vec![FunctionObject {
+        name: "adjust_reputation".to_string(),
+        description: Some(
+            "Record a lasting consequence of the player's actions: Street Cred for respect earned, Notoriety for bad reputation, Public Awareness for media and law enforcement attention, or standing with a faction. Faction members' dispositions follow their faction's standing"
+                .to_string(),
+        ),
+        parameters: Some(json!({
+            "type": "object",
+            "properties": {
+                "track": {
+                    "type": "string",
+                    "enum": ["street_cred", "notoriety", "public_awareness", "faction"]
+                },
+                "faction": {
+                    "type": "string",
+                    "description": "For faction standing: the faction's name, as used on its NPCs"
+                },
+                "amount": {
+                    "type": "integer",
+                    "description": "Change, usually 1 or 2. Faction standing ranges from -5 to 5"
+                },
+                "reason": { "type": "string", "description": "What the player did" }
+            },
+            "required": ["track", "amount", "reason"]
+        })),
+    }]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::npc::{Disposition, Npc};
+
+    fn npc(name: &str, faction: &str) -> Npc {
+        Npc {
+            name: name.to_string(),
+            description: String::new(),
+            faction: Some(faction.to_string()),
+            disposition: Disposition::Neutral,
+            connection: None,
+            loyalty: None,
+            last_seen: None,
+            portrait: None,
+            notes: Vec::new(),
+        }
+    }
+
+    fn adjust(
+        reputation: &mut Reputation,
+        npcs: &mut NpcRegistry,
+        args: Value,
+    ) -> Result<Value, SharadError> {
+        adjust_reputation(reputation, npcs, "Day 1".to_string(), args)
+    }
+
+    #[test]
+    fn heat_never_goes_below_zero() {
+        let mut reputation = Reputation::default();
+        let mut npcs = NpcRegistry::default();
+        adjust(
+            &mut reputation,
+            &mut npcs,
+            json!({ "track": "notoriety", "amount": 2, "reason": "Loud run" }),
+        )
+        .unwrap();
+        let output = adjust(
+            &mut reputation,
+            &mut npcs,
+            json!({ "track": "notoriety", "amount": -5, "reason": "Laid low" }),
+        )
+        .unwrap();
+        assert_eq!(output["notoriety"], 0);
+        assert_eq!(reputation.history.len(), 2);
+        assert!(adjust(
+            &mut reputation,
+            &mut npcs,
+            json!({ "track": "street_cred", "amount": 0, "reason": "Nothing" }),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn faction_standing_shifts_its_members() {
+        let mut reputation = Reputation::default();
+        let mut npcs = NpcRegistry {
+            npcs: vec![npc("Jimmy", "Ancients"), npc("Lena", "Renraku")],
+        };
+
+        // Within the neutral band, nobody's disposition moves
+        adjust(
+            &mut reputation,
+            &mut npcs,
+            json!({ "track": "faction", "faction": "ancients", "amount": 1, "reason": "Paid up" }),
+        )
+        .unwrap();
+        assert_eq!(npcs.npcs[0].disposition, Disposition::Neutral);
+
+        let output = adjust(
+            &mut reputation,
+            &mut npcs,
+            json!({ "track": "faction", "faction": "Ancients", "amount": 9, "reason": "Saved their leader" }),
+        )
+        .unwrap();
+        // The spelling of the NPC's faction is kept, and standing is capped
+        assert_eq!(output["faction"], "Ancients");
+        assert_eq!(output["standing"], MAX_STANDING);
+        assert_eq!(reputation.factions.len(), 1);
+        assert_eq!(npcs.npcs[0].disposition, Disposition::Allied);
+        assert_eq!(npcs.npcs[1].disposition, Disposition::Neutral);
+
+        assert!(adjust(
+            &mut reputation,
+            &mut npcs,
+            json!({ "track": "faction", "amount": 1, "reason": "No faction" }),
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,227 @@
+use crate::character::{CharacterSheet, GearItem};
+use crate::combat::DamageType;
+use crate::d20::{D20Sheet, D20};
+use crate::display::Display;
+use crate::error::SharadError;
+use crate::game_state::GameState;
+use crate::intent::ProposedTest;
+use crate::shadowrun::Shadowrun;
+use async_openai::types::FunctionObject;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::future::Future;
+use std::pin::Pin;
+
+/// Game mechanics a cartridge is played with.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleSystemId {
+    #[default]
+    Shadowrun,
+    D20,
+}
+
+impl RuleSystemId {
+    pub fn rules(&self) -> &'static dyn RuleSystem {
+        match self {
+            RuleSystemId::Shadowrun => &Shadowrun,
+            RuleSystemId::D20 => &D20,
+        }
+    }
+}
+
+/// The player character, in the format of the rule system it was created for, named by its
+/// `system` field. Saves from before the field existed get it when they are read.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "system", rename_all = "lowercase")]
+pub enum Character {
+    Shadowrun(CharacterSheet),
+    D20(D20Sheet),
+}
+
+impl Character {
+    pub fn name(&self) -> &str {
+        match self {
+            Character::Shadowrun(sheet) => &sheet.name,
+            Character::D20(sheet) => &sheet.name,
+        }
+    }
+
+    pub fn set_portrait(&mut self, path: String) {
+        match self {
+            Character::Shadowrun(sheet) => sheet.portrait = Some(path),
+            Character::D20(sheet) => sheet.portrait = Some(path),
+        }
+    }
+
+    pub fn portrait(&self) -> Option<&str> {
+        match self {
+            Character::Shadowrun(sheet) => sheet.portrait.as_deref(),
+            Character::D20(sheet) => sheet.portrait.as_deref(),
+        }
+    }
+
+    /// Heals damage on whichever track the character's rule system uses.
+    pub fn heal(&mut self, amount: u8, damage_type: DamageType) {
+        match self {
+            Character::Shadowrun(sheet) => sheet.condition.heal(amount, damage_type),
+            Character::D20(sheet) => {
+                sheet.hit_points = (sheet.hit_points + amount as i32).min(sheet.max_hit_points)
+            }
+        }
+    }
+
+    /// Money on hand: nuyen in Shadowrun, gold in d20.
+    pub fn funds(&self) -> u32 {
+        match self {
+            Character::Shadowrun(sheet) => sheet.nuyen,
+            Character::D20(sheet) => sheet.gold,
+        }
+    }
+
+    pub fn spend(&mut self, amount: u32) -> Result<(), SharadError> {
+        let funds = match self {
+            Character::Shadowrun(sheet) => &mut sheet.nuyen,
+            Character::D20(sheet) => &mut sheet.gold,
+        };
+        *funds = funds.checked_sub(amount).ok_or_else(|| {
+            SharadError::Message(format!(
+                "This costs {}, but only {} is left.",
+                amount, funds
+            ))
+        })?;
+        Ok(())
+    }
+
+    pub fn add_gear(&mut self, name: &str, cost: u32) {
+        match self {
+            Character::Shadowrun(sheet) => sheet.gear.push(GearItem {
+                name: name.to_string(),
+                cost,
+            }),
+            Character::D20(sheet) => sheet.gear.push(name.to_string()),
+        }
+    }
+
+    pub fn receive(&mut self, amount: u32) {
+        match self {
+            Character::Shadowrun(sheet) => sheet.nuyen = sheet.nuyen.saturating_add(amount),
+            Character::D20(sheet) => sheet.gold = sheet.gold.saturating_add(amount),
+        }
+    }
+
+    fn gear_names(&self) -> Vec<&str> {
+        match self {
+            Character::Shadowrun(sheet) => {
+                sheet.gear.iter().map(|item| item.name.as_str()).collect()
+            }
+            Character::D20(sheet) => sheet.gear.iter().map(String::as_str).collect(),
+        }
+    }
+
+    /// Finds a carried item by its full name, ignoring case. Partial names do not match, so
+    /// "ammo for Ares Predator V" is not the Ares Predator V.
+    pub fn find_gear(&self, name: &str) -> Option<usize> {
+        let name = name.trim().to_lowercase();
+        if name.is_empty() {
+            return None;
+        }
+        self.gear_names()
+            .iter()
+            .position(|item| item.trim().to_lowercase() == name)
+    }
+
+    pub fn remove_gear(&mut self, index: usize) {
+        match self {
+            Character::Shadowrun(sheet) => {
+                sheet.gear.remove(index);
+            }
+            Character::D20(sheet) => {
+                sheet.gear.remove(index);
+            }
+        }
+    }
+
+    pub fn is_incapacitated(&self) -> bool {
+        match self {
+            Character::Shadowrun(sheet) => sheet.condition.is_incapacitated(),
+            Character::D20(sheet) => sheet.hit_points <= 0,
+        }
+    }
+
+    pub fn as_shadowrun(&self) -> Option<&CharacterSheet> {
+        match self {
+            Character::Shadowrun(sheet) => Some(sheet),
+            _ => None,
+        }
+    }
+
+    pub fn as_shadowrun_mut(&mut self) -> Option<&mut CharacterSheet> {
+        match self {
+            Character::Shadowrun(sheet) => Some(sheet),
+            _ => None,
+        }
+    }
+
+    pub fn as_d20_mut(&mut self) -> Option<&mut D20Sheet> {
+        match self {
+            Character::D20(sheet) => Some(sheet),
+            _ => None,
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        match self {
+            Character::Shadowrun(sheet) => sheet.to_json(),
+            other => json!(other),
+        }
+    }
+}
+
+pub type CharacterCreation<'a> =
+    Pin<Box<dyn Future<Output = Result<Option<Character>, SharadError>> + 'a>>;
+
+/// Everything that depends on the mechanics of the game being played.
+pub trait RuleSystem: Sync {
+    fn name(&self) -> &'static str;
+
+    /// JSON schema of the character sheet, sent to the Game Master at the start of a game.
+    fn character_schema(&self) -> Value;
+
+    /// Walks the player through creating a character. Resolves to `None` if they cancel.
+    fn create_character<'a>(&'a self, display: &'a mut Display) -> CharacterCreation<'a>;
+
+    /// Resolves a test requested by the Game Master through the system's test tool.
+    fn resolve_test(
+        &self,
+        character: Option<&Character>,
+        args: Value,
+    ) -> Result<Value, SharadError>;
+
+    /// Rolls the test the player's action seems to call for, before the Game Master narrates it.
+    /// Returns `None` when no skill of the sheet fits the action.
+    fn propose_test(&self, game_state: &GameState, action: &str) -> Option<ProposedTest>;
+
+    /// Rolls whether the character finds a seller for an item of the given Availability.
+    /// The result always has an `is_successful` field.
+    fn availability_test(&self, character: Option<&Character>, availability: u8) -> Value;
+
+    /// Name of the money the system's characters carry.
+    fn currency(&self) -> &'static str;
+
+    /// Tools this system adds for the Game Master, including its test tool.
+    fn tool_definitions(&self) -> Vec<FunctionObject>;
+
+    /// Runs one of this system's tools. Returns `None` if the tool is not one of them.
+    fn handle_tool_call(
+        &self,
+        name: &str,
+        args: Value,
+        game_state: &mut GameState,
+    ) -> Option<Result<Value, SharadError>>;
+
+    /// Extra Game Master instructions explaining how to use this system's tools.
+    fn gm_instructions(&self) -> &'static str;
+
+    fn render_sheet(&self, character: &Character) -> String;
+}
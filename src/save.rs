@@ -0,0 +1,605 @@
+use crate::error::SharadError;
+use crate::game_state::GameState;
+use crate::paths::save_dir;
+use crate::transcript::{self, transcript_path};
+use crate::utils::{backup_path, write_atomic, write_with_backup};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Version written by this build. Saves without a version predate metadata and are version 1;
+/// version 2 saves keep the character sheet without its rule system.
+pub const SAVE_VERSION: u32 = 3;
+/// Slot written by quicksave and read by quickload.
+pub const QUICKSAVE_NAME: &str = "quicksave";
+/// Autosaves rotate through `autosave-1` to `autosave-<slots>`.
+const AUTOSAVE_PREFIX: &str = "autosave-";
+/// Characters of the last narration kept to remind the player where they left off.
+const EXCERPT_LENGTH: usize = 160;
+
+fn legacy_version() -> u32 {
+    1
+}
+
+/// Names the rule system of a character saved before sheets said which one they follow: the
+/// cartridge's, which is Shadowrun for games older than rule systems.
+fn tag_character(save: &mut Value) {
+    let system = save
+        .pointer("/game_state/cartridge/rule_system")
+        .cloned()
+        .unwrap_or_else(|| Value::from("shadowrun"));
+    if let Some(Value::Object(character)) = save.pointer_mut("/game_state/character") {
+        character.entry("system").or_insert(system);
+    }
+}
+
+/// What the load menu shows about a save without reading its whole game state.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SaveMetadata {
+    /// RFC 3339 timestamps.
+    #[serde(default)]
+    pub created: String,
+    #[serde(default)]
+    pub updated: String,
+    #[serde(default)]
+    pub cartridge: String,
+    #[serde(default)]
+    pub character: Option<String>,
+    #[serde(default)]
+    pub turns: u32,
+    #[serde(default)]
+    pub playtime_seconds: u64,
+    #[serde(default)]
+    pub last_narration: String,
+    /// Portrait of the player's character.
+    #[serde(default)]
+    pub thumbnail: Option<String>,
+}
+
+fn format_timestamp(timestamp: &str) -> String {
+    DateTime::parse_from_rfc3339(timestamp)
+        .map(|date| {
+            date.with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        })
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+fn format_playtime(seconds: u64) -> String {
+    let minutes = seconds / 60;
+    match minutes / 60 {
+        0 => format!("{}m", minutes),
+        hours => format!("{}h {:02}m", hours, minutes % 60),
+    }
+}
+
+impl SaveMetadata {
+    /// One line for the load menu, e.g. `Kira (shadowrun) - 42 turns, 3h 05m - 2075-01-02 21:30`.
+    pub fn summary(&self) -> String {
+        let character = self.character.as_deref().unwrap_or("No character");
+        format!(
+            "{} ({}) - {} {}, {} - {}",
+            character,
+            self.cartridge,
+            self.turns,
+            if self.turns == 1 { "turn" } else { "turns" },
+            format_playtime(self.playtime_seconds),
+            format_timestamp(&self.updated)
+        )
+    }
+
+    /// Longer description of the highlighted save.
+    pub fn details(&self) -> Vec<String> {
+        let mut lines = vec![format!(
+            "Started {} - last played {}",
+            format_timestamp(&self.created),
+            format_timestamp(&self.updated)
+        )];
+        if !self.last_narration.is_empty() {
+            lines.push(format!("\"{}\"", self.last_narration));
+        }
+        if let Some(thumbnail) = &self.thumbnail {
+            lines.push(format!("Portrait: {}", thumbnail));
+        }
+        lines
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Save {
+    #[serde(default = "legacy_version")]
+    pub version: u32,
+    pub assistant_id: String,
+    pub thread_id: String,
+    #[serde(default)]
+    pub metadata: SaveMetadata,
+    #[serde(default)]
+    pub game_state: GameState,
+    /// Last message of the thread when the save was written, to rewind threads that went on.
+    #[serde(default)]
+    pub last_message_id: Option<String>,
+    #[serde(skip)]
+    pub name: Option<String>,
+    /// When playtime was last added to the metadata, while the game is being played.
+    #[serde(skip)]
+    session_start: Option<Instant>,
+    /// Turn count at the last autosave, or when the game was loaded.
+    #[serde(skip)]
+    last_autosave_turn: u32,
+}
+
+impl Save {
+    pub fn new(assistant_id: String, thread_id: String, game_state: GameState) -> Self {
+        let now = Local::now().to_rfc3339();
+        let mut save = Save {
+            version: SAVE_VERSION,
+            assistant_id,
+            thread_id,
+            metadata: SaveMetadata {
+                created: now.clone(),
+                updated: now,
+                ..SaveMetadata::default()
+            },
+            game_state,
+            last_message_id: None,
+            name: None,
+            session_start: None,
+            last_autosave_turn: 0,
+        };
+        save.refresh_metadata();
+        save
+    }
+
+    /// Parses a save, bringing the JSON of older versions up to date first.
+    pub fn parse(data: &[u8]) -> Result<Save, SharadError> {
+        let mut json: Value = serde_json::from_slice(data)?;
+        let version = json
+            .get("version")
+            .and_then(Value::as_u64)
+            .unwrap_or(legacy_version().into());
+        if version > SAVE_VERSION.into() {
+            return Err(SharadError::Message(format!(
+                "This save was made by a newer version of Sharad (save version {}, this one reads up to {}).",
+                version, SAVE_VERSION
+            )));
+        }
+        if version < 3 {
+            tag_character(&mut json);
+        }
+        Ok(serde_json::from_value(json)?)
+    }
+
+    /// Reads a save from the save directory, upgrading saves written by older versions in place.
+    pub fn read(name: &str) -> Result<Save, SharadError> {
+        let path = save_path(name);
+        let mut save = Save::parse(&fs::read(&path)?)?;
+        save.name = Some(name.to_string());
+        if save.version < SAVE_VERSION {
+            save.migrate(&path);
+            save.write_file()?;
+        }
+        Ok(save)
+    }
+
+    /// Fills the metadata of a save that predates it. The turn count of older games is unknown.
+    fn migrate(&mut self, path: &Path) {
+        if self.version < 2 {
+            let modified = fs::metadata(path)
+                .and_then(|metadata| metadata.modified())
+                .unwrap_or_else(|_| SystemTime::now());
+            let modified = DateTime::<Local>::from(modified).to_rfc3339();
+            if self.metadata.created.is_empty() {
+                self.metadata.created = modified.clone();
+            }
+            self.metadata.updated = modified;
+            self.refresh_metadata();
+        }
+        self.version = SAVE_VERSION;
+    }
+
+    /// Copies the details shown in the load menu from the game state.
+    fn refresh_metadata(&mut self) {
+        self.metadata.cartridge = self.game_state.cartridge.name.clone();
+        self.metadata.character = self
+            .game_state
+            .character
+            .as_ref()
+            .map(|character| character.name().to_string());
+        self.metadata.thumbnail = self
+            .game_state
+            .character
+            .as_ref()
+            .and_then(|character| character.portrait())
+            .map(str::to_string);
+    }
+
+    /// Starts counting playtime. Each write adds the time played since.
+    pub fn start_session(&mut self) {
+        self.session_start = Some(Instant::now());
+        self.last_autosave_turn = self.metadata.turns;
+    }
+
+    /// Counts a finished Game Master turn and keeps the start of its narration.
+    pub fn record_turn(&mut self, narration: &str) {
+        self.metadata.turns += 1;
+        let narration = narration.trim();
+        self.metadata.last_narration = match narration.char_indices().nth(EXCERPT_LENGTH) {
+            Some((end, _)) => format!("{}...", narration[..end].trim_end()),
+            None => narration.to_string(),
+        };
+    }
+
+    /// Writes the save back to its file. Saves that were never named are kept in memory only.
+    pub fn write(&mut self) -> Result<(), SharadError> {
+        if self.name.is_none() {
+            return Ok(());
+        }
+        if let Some(start) = self.session_start {
+            let elapsed = start.elapsed().as_secs();
+            self.metadata.playtime_seconds += elapsed;
+            self.session_start = Some(start + Duration::from_secs(elapsed));
+        }
+        self.metadata.updated = Local::now().to_rfc3339();
+        self.refresh_metadata();
+        self.write_file()
+    }
+
+    /// Writes the save under another name, leaving its own file as it was.
+    pub fn write_copy(&mut self, name: &str) -> Result<(), SharadError> {
+        let own_name = self.name.replace(name.to_string());
+        let result = self.write();
+        self.name = own_name;
+        result
+    }
+
+    /// Whether an autosave is due every `interval` turns. Turns only count narrated replies, so
+    /// the count can stay the same for several actions; each count is autosaved once.
+    pub fn autosave_due(&self, interval: u32) -> bool {
+        interval > 0
+            && self.metadata.turns > self.last_autosave_turn
+            && self.metadata.turns.is_multiple_of(interval)
+    }
+
+    /// Writes the save to the autosave slot written longest ago. Returns the slot's name.
+    pub fn autosave(&mut self, slots: u32) -> Result<String, SharadError> {
+        let slot = (1..=slots.max(1))
+            .map(|slot| format!("{}{}", AUTOSAVE_PREFIX, slot))
+            .min_by_key(|name| {
+                // Missing slots sort first
+                fs::metadata(save_path(name))
+                    .and_then(|metadata| metadata.modified())
+                    .ok()
+            })
+            .unwrap_or_else(|| format!("{}1", AUTOSAVE_PREFIX));
+        self.write_copy(&slot)?;
+        self.last_autosave_turn = self.metadata.turns;
+        Ok(slot)
+    }
+
+    /// Whether both saves come from the same game, wherever they were saved.
+    pub fn same_game(&self, other: &Save) -> bool {
+        self.assistant_id == other.assistant_id && self.metadata.created == other.metadata.created
+    }
+
+    /// Writes the save as it is, without counting playtime or updating its metadata.
+    pub fn write_file(&self) -> Result<(), SharadError> {
+        let Some(save_name) = &self.name else {
+            return Ok(());
+        };
+
+        let save_dir = save_dir();
+        if !save_dir.exists() {
+            fs::create_dir_all(save_dir).map_err(SharadError::Io)?;
+        }
+
+        let json = serde_json::to_string(self).map_err(SharadError::SerdeJson)?;
+        write_with_backup(&save_path(save_name), json.as_bytes()).map_err(SharadError::Io)?;
+        Ok(())
+    }
+}
+
+pub fn save_path(name: &str) -> PathBuf {
+    save_dir().join(format!("{}.json", name))
+}
+
+pub fn has_backup(name: &str) -> bool {
+    backup_path(&save_path(name)).is_file()
+}
+
+/// Puts back the version of a save written before its last write, for saves that cannot be
+/// read anymore.
+pub fn restore_backup(name: &str) -> Result<Save, SharadError> {
+    let data = fs::read(backup_path(&save_path(name)))?;
+    // Only replace the save with a backup that can be read, from this version or an older one
+    Save::parse(&data)?;
+    write_atomic(&save_path(name), &data)?;
+    Save::read(name)
+}
+
+/// Characters Windows does not allow in file names.
+const FORBIDDEN_CHARACTERS: [char; 9] = ['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+
+/// Whether quicksave or the rolling autosaves write to this name.
+fn is_reserved(name: &str) -> bool {
+    let name = name.to_lowercase();
+    name == QUICKSAVE_NAME
+        || name
+            .strip_prefix(AUTOSAVE_PREFIX)
+            .is_some_and(|slot| !slot.is_empty() && slot.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Checks a name typed for a save file, which may be one that exists.
+pub fn validate_name(name: &str) -> Result<(), SharadError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(SharadError::Message("Save name cannot be empty.".into()));
+    }
+    if name.contains(FORBIDDEN_CHARACTERS)
+        || name.contains(char::is_control)
+        || name.starts_with('.')
+        || name.ends_with('.')
+    {
+        return Err(SharadError::Message(format!(
+            "'{}' cannot be used as a file name.",
+            name
+        )));
+    }
+    if is_reserved(name) {
+        return Err(SharadError::Message(format!(
+            "'{}' is kept for quicksaves and autosaves.",
+            name
+        )));
+    }
+    Ok(())
+}
+
+/// Checks a name typed for a new save file.
+pub fn validate_new_name(name: &str) -> Result<(), SharadError> {
+    validate_name(name)?;
+    if save_path(name.trim()).exists() {
+        return Err(SharadError::Message(format!(
+            "A save called '{}' already exists.",
+            name.trim()
+        )));
+    }
+    Ok(())
+}
+
+pub fn rename_save(name: &str, new_name: &str) -> Result<(), SharadError> {
+    validate_new_name(new_name)?;
+    let new_path = save_path(new_name.trim());
+    fs::rename(save_path(name), &new_path)?;
+    let backup = backup_path(&save_path(name));
+    if backup.is_file() {
+        fs::rename(backup, backup_path(&new_path))?;
+    }
+    Ok(())
+}
+
+/// Deletes a save with its transcript, narration audio and portraits, unless another save still
+/// uses them. Returns the files removed.
+pub fn delete_save(name: &str) -> Result<Vec<PathBuf>, SharadError> {
+    let save = Save::read(name).ok();
+    let path = save_path(name);
+    fs::remove_file(&path)?;
+    let mut removed = vec![path];
+    let backup = backup_path(&save_path(name));
+    if fs::remove_file(&backup).is_ok() {
+        removed.push(backup);
+    }
+    let Some(save) = save else {
+        return Ok(removed);
+    };
+
+    let others: Vec<Save> = list_saves()?
+        .iter()
+        .filter_map(|entry| Save::read(&entry.name).ok())
+        .collect();
+    if !others.iter().any(|other| other.thread_id == save.thread_id) {
+        let audio = transcript::read(&save.thread_id).unwrap_or_default();
+        for file in audio.iter().filter_map(|entry| entry.audio.as_deref()) {
+            if fs::remove_file(file).is_ok() {
+                removed.push(PathBuf::from(file));
+            }
+        }
+        let transcript = transcript_path(&save.thread_id);
+        if fs::remove_file(&transcript).is_ok() {
+            removed.push(transcript);
+        }
+    }
+    for file in save.game_state.media_files() {
+        let shared = others
+            .iter()
+            .any(|other| other.game_state.media_files().contains(&file));
+        if !shared && fs::remove_file(file).is_ok() {
+            removed.push(PathBuf::from(file));
+        }
+    }
+    Ok(removed)
+}
+
+/// The part of a save file the load menu needs.
+#[derive(Deserialize)]
+struct SaveHeader {
+    #[serde(default = "legacy_version")]
+    version: u32,
+    #[serde(default)]
+    metadata: SaveMetadata,
+}
+
+/// Reads a save's metadata without writing anything. Saves from before metadata existed are
+/// parsed whole to fill it in memory; they are upgraded on disk once loaded.
+fn read_metadata(path: &Path) -> Result<SaveMetadata, SharadError> {
+    let data = fs::read(path)?;
+    let header: SaveHeader = serde_json::from_slice(&data)?;
+    if header.version >= 2 {
+        return Ok(header.metadata);
+    }
+    let mut save = Save::parse(&data)?;
+    save.migrate(path);
+    Ok(save.metadata)
+}
+
+/// A file in the save directory, with its metadata unless it could not be read.
+pub struct SaveEntry {
+    pub name: String,
+    pub metadata: Option<SaveMetadata>,
+}
+
+/// Lists the saves, most recently played first.
+pub fn list_saves() -> Result<Vec<SaveEntry>, SharadError> {
+    let save_dir = save_dir();
+    if !save_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(save_dir)? {
+        let path = entry?.path();
+        if !path.is_file() || path.extension().and_then(|os_str| os_str.to_str()) != Some("json") {
+            continue;
+        }
+        if let Some(name) = path.file_stem().and_then(|os_str| os_str.to_str()) {
+            entries.push(SaveEntry {
+                name: name.to_string(),
+                metadata: read_metadata(&path).ok(),
+            });
+        }
+    }
+
+    entries.sort_by_cached_key(|entry| {
+        std::cmp::Reverse(
+            entry
+                .metadata
+                .as_ref()
+                .and_then(|metadata| DateTime::parse_from_rfc3339(&metadata.updated).ok()),
+        )
+    });
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paths::DATA_DIR_VAR;
+    use crate::rules::Character;
+    use serde_json::json;
+    use std::env;
+    use std::process;
+    use std::sync::Once;
+
+    /// Keeps the saves written by tests in a temporary data directory.
+    fn use_temporary_data_dir() {
+        static DIR: Once = Once::new();
+        DIR.call_once(|| {
+            let dir = env::temp_dir().join(format!("sharad-test-{}", process::id()));
+            env::set_var(DATA_DIR_VAR, dir);
+        });
+    }
+
+    /// A save as version 2 wrote it: a Shadowrun character sheet without its rule system.
+    fn version_2_save() -> Value {
+        json!({
+            "version": 2,
+            "assistant_id": "asst_1",
+            "thread_id": "thread_1",
+            "metadata": {"created": "2024-06-01T20:00:00+00:00", "turns": 12},
+            "game_state": {
+                "cartridge": {"name": "Shadowrun"},
+                "character": {
+                    "name": "Kira",
+                    "metatype": "Human",
+                    "priorities": {"metatype": "E", "attributes": "A", "magic": "B", "skills": "C", "resources": "D"},
+                    "attributes": {"Body": 3, "Agility": 4, "Reaction": 3, "Strength": 2, "Willpower": 3, "Logic": 3, "Intuition": 3, "Charisma": 2},
+                    "edge": 2,
+                    "awakening": "Mundane",
+                    "skills": {"Pistols": 4},
+                    "nuyen": 500,
+                    "condition": {"physical_boxes": 10, "stun_boxes": 10, "physical_damage": 0, "stun_damage": 0}
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn restores_a_backup_from_an_older_version() {
+        use_temporary_data_dir();
+        let name = "restore-version-2";
+        let path = save_path(name);
+        fs::create_dir_all(save_dir()).unwrap();
+        fs::write(&path, b"{ truncated").unwrap();
+        fs::write(backup_path(&path), version_2_save().to_string()).unwrap();
+
+        let save = restore_backup(name).unwrap();
+        assert_eq!(save.version, SAVE_VERSION);
+        assert_eq!(save.metadata.turns, 12);
+        assert!(matches!(
+            save.game_state.character,
+            Some(Character::Shadowrun(_))
+        ));
+
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(backup_path(&path)).unwrap();
+    }
+
+    #[test]
+    fn tags_characters_of_older_saves_with_their_rule_system() {
+        let save = Save::parse(version_2_save().to_string().as_bytes()).unwrap();
+        assert_eq!(save.version, 2);
+        assert!(matches!(
+            save.game_state.character,
+            Some(Character::Shadowrun(_))
+        ));
+
+        let mut json = version_2_save();
+        json["game_state"]["cartridge"] = json!({"name": "Sword Coast", "rule_system": "d20"});
+        json["game_state"]["character"] = json!({
+            "name": "Brenna",
+            "class": "Fighter",
+            "level": 1,
+            "abilities": {"Strength": 16, "Dexterity": 12},
+            "hit_points": 12,
+            "max_hit_points": 12,
+            "armor_class": 16
+        });
+        let save = Save::parse(json.to_string().as_bytes()).unwrap();
+        assert!(matches!(save.game_state.character, Some(Character::D20(_))));
+
+        // A version 3 character must already say which system it follows
+        json["version"] = json!(3);
+        assert!(Save::parse(json.to_string().as_bytes()).is_err());
+    }
+
+    #[test]
+    fn refuses_saves_from_newer_versions() {
+        let mut json = version_2_save();
+        json["version"] = json!(SAVE_VERSION + 1);
+        assert!(Save::parse(json.to_string().as_bytes()).is_err());
+    }
+
+    #[test]
+    fn refuses_reserved_and_unportable_names() {
+        for name in ["quicksave", "QuickSave", "autosave-1", "autosave-12"] {
+            assert!(validate_name(name).is_err(), "{}", name);
+        }
+        for name in [
+            "a:b",
+            "what?",
+            "x*y",
+            "\"quoted\"",
+            "<tag>",
+            "a|b",
+            "back\\slash",
+            "trailing.",
+        ] {
+            assert!(validate_name(name).is_err(), "{}", name);
+        }
+        for name in ["Kira", "autosave", "autosave-final", "run 2"] {
+            assert!(validate_name(name).is_ok(), "{}", name);
+        }
+    }
+}
@@ -1,13 +1,16 @@
 use crate::display::Display;
 
 use crate::error::SharadError;
+use crate::logging::{DEBUG_LOG_FILTER, DEFAULT_LOG_FILTER};
+use crate::paths::{config_dir, settings_file};
+use crate::storage::Retention;
+use crate::utils::{backup_path, write_atomic, write_with_backup};
 use crate::Color;
 use async_openai::Client;
 use serde::{Deserialize, Serialize};
 use std::env;
 use std::fs;
-
-const SETTINGS_FILE: &str = "./data/logs/settings.json";
+use std::path::Path;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Settings {
@@ -17,14 +20,50 @@ pub struct Settings {
     pub audio_output_enabled: bool,
     #[serde(default = "default_true")]
     pub audio_input_enabled: bool,
+    /// Which events are logged and which debug messages are shown, in `RUST_LOG` syntax.
+    #[serde(default = "default_log_filter")]
+    pub log_filter: String,
+    /// Replaced by `log_filter`; read only to carry it over.
+    #[serde(default, skip_serializing)]
+    debug_mode: bool,
+    /// Turns between autosaves; 0 only autosaves on exit.
+    #[serde(default = "default_autosave_interval")]
+    pub autosave_interval: u32,
+    /// Autosave files kept before the oldest is overwritten.
+    #[serde(default = "default_autosave_slots")]
+    pub autosave_slots: u32,
+    /// Seconds each turn stays on screen in a replay; 0 waits for a key.
+    #[serde(default = "default_replay_pace")]
+    pub replay_pace_seconds: u32,
+    /// How long recordings, narration audio, portraits, logs and exports are kept.
     #[serde(default)]
-    pub debug_mode: bool,
+    pub retention: Retention,
+    /// Where saves, transcripts and portraits are kept, instead of the platform's data directory.
+    /// Read once at startup.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_dir: Option<String>,
 }
 
 fn default_true() -> bool {
     true
 }
 
+fn default_log_filter() -> String {
+    DEFAULT_LOG_FILTER.to_string()
+}
+
+fn default_replay_pace() -> u32 {
+    5
+}
+
+fn default_autosave_interval() -> u32 {
+    5
+}
+
+fn default_autosave_slots() -> u32 {
+    3
+}
+
 impl Default for Settings {
     fn default() -> Self {
         Settings {
@@ -32,16 +71,23 @@ impl Default for Settings {
             openai_api_key: String::new(),
             audio_output_enabled: true,
             audio_input_enabled: true,
+            log_filter: default_log_filter(),
             debug_mode: false,
+            autosave_interval: default_autosave_interval(),
+            autosave_slots: default_autosave_slots(),
+            replay_pace_seconds: default_replay_pace(),
+            retention: Retention::default(),
+            data_dir: None,
         }
     }
 }
 
+/// Reads the settings, or the defaults when there are none yet. Settings that cannot be read
+/// are an error rather than a silent reset, which would erase the API key.
 pub fn load_settings() -> Result<Settings, SharadError> {
-    match fs::read_to_string(SETTINGS_FILE) {
+    match fs::read_to_string(settings_file()) {
         Ok(data) => {
-            let mut settings: Settings =
-                serde_json::from_str(&data).unwrap_or_else(|_| Settings::default());
+            let mut settings: Settings = serde_json::from_str(&data)?;
 
             // Check for empty required fields and set default values
             if settings.language.trim().is_empty() {
@@ -50,6 +96,10 @@ pub fn load_settings() -> Result<Settings, SharadError> {
             if settings.openai_api_key.trim().is_empty() {
                 settings.openai_api_key = Settings::default().openai_api_key;
             }
+            if settings.debug_mode {
+                settings.log_filter = DEBUG_LOG_FILTER.to_string();
+                settings.debug_mode = false;
+            }
 
             Ok(settings)
         }
@@ -57,9 +107,53 @@ pub fn load_settings() -> Result<Settings, SharadError> {
     }
 }
 
+/// Reads the settings, asking the player what to do when they cannot be read: restore the
+/// backup written before the last change, or start over with the damaged file set aside.
+pub fn load_or_recover_settings(display: &mut Display) -> Result<Settings, SharadError> {
+    let error = match load_settings() {
+        Ok(settings) => return Ok(settings),
+        Err(error) => error,
+    };
+    display.print_wrapped(
+        &format!("The settings could not be read: {}", error),
+        Color::Red,
+    );
+
+    let path = settings_file();
+    let backup = fs::read_to_string(backup_path(&path))
+        .ok()
+        .and_then(|data| {
+            let settings = serde_json::from_str::<Settings>(&data).ok()?;
+            Some((data, settings))
+        });
+    if let Some((data, settings)) = backup {
+        let confirm = display
+            .get_user_input("Restore the settings from before the last change? (y/n)")?
+            .map(|s| s.to_lowercase());
+        if matches!(confirm.as_deref(), Some("y") | Some("yes")) {
+            write_atomic(&path, data.as_bytes())?;
+            display.print_wrapped("Settings restored.", Color::Green);
+            return Ok(settings);
+        }
+    }
+
+    let mut damaged = path.as_os_str().to_owned();
+    damaged.push(".damaged");
+    fs::rename(&path, &damaged)?;
+    display.print_wrapped(
+        &format!(
+            "Starting with default settings. The damaged file was kept as {}.",
+            Path::new(&damaged).display()
+        ),
+        Color::Yellow,
+    );
+    Ok(Settings::default())
+}
+
 pub fn save_settings(settings: &Settings) -> Result<(), SharadError> {
     let json = serde_json::to_string_pretty(settings)?;
-    fs::write(SETTINGS_FILE, json)?;
+    fs::create_dir_all(config_dir())?;
+    write_with_backup(&settings_file(), json.as_bytes())?;
     Ok(())
 }
 
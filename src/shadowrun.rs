@@ -0,0 +1,711 @@
+use crate::augmentation;
+use crate::character::{linked_attribute, skill_limit};
+use crate::character_creation::create_character;
+use crate::combat;
+use crate::display::Display;
+use crate::error::SharadError;
+use crate::game_state::GameState;
+use crate::intent::{classify, ProposedTest, SkillKeywords};
+use crate::karma;
+use crate::magic::{self, Drain};
+use crate::matrix;
+use crate::rules::{Character, CharacterCreation, RuleSystem};
+use crate::utils::shadowrun_dice_roll;
+use async_openai::types::FunctionObject;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+/// Shadowrun 5th edition: d6 dice pools counting hits, with glitches on half ones.
+pub struct Shadowrun;
+
+/// Skills the player's actions are classified into. Spellcasting and Summoning are left to
+/// cast_spell and summon_spirit, which roll them together with Drain.
+const INTENT_KEYWORDS: &SkillKeywords = &[
+    (
+        "Pistols",
+        &[
+            "pistol",
+            "pistols",
+            "handgun",
+            "handguns",
+            "revolver",
+            "revolvers",
+            "pistolet",
+            "pistolets",
+            "flingue",
+            "flingues",
+        ],
+    ),
+    (
+        "Automatics",
+        &[
+            "smg",
+            "smgs",
+            "submachine",
+            "assault rifle",
+            "assault rifles",
+            "mitraillette",
+            "mitraillettes",
+            "mitrailleuse",
+        ],
+    ),
+    (
+        "Longarms",
+        &[
+            "shotgun", "shotguns", "sniper", "rifle", "rifles", "fusil", "fusils",
+        ],
+    ),
+    (
+        "Blades",
+        &[
+            "knife",
+            "knives",
+            "sword",
+            "swords",
+            "katana",
+            "blade",
+            "blades",
+            "stab",
+            "stabs",
+            "stabbing",
+            "slash",
+            "slashes",
+            "slashing",
+            "couteau",
+            "couteaux",
+            "épée",
+            "poignard",
+            "poignarde",
+        ],
+    ),
+    (
+        "Clubs",
+        &["baton", "bludgeon", "bludgeons", "matraque", "matraques"],
+    ),
+    (
+        "Unarmed Combat",
+        &[
+            "punch",
+            "punches",
+            "punching",
+            "kick",
+            "kicks",
+            "kicking",
+            "grapple",
+            "grapples",
+            "grappling",
+            "wrestle",
+            "wrestles",
+            "wrestling",
+            "fistfight",
+            "frappe",
+            "frapper",
+            "coup de poing",
+            "coups de poing",
+        ],
+    ),
+    (
+        "Throwing Weapons",
+        &[
+            "throw", "throws", "throwing", "hurl", "hurls", "hurling", "jette", "jeter",
+        ],
+    ),
+    (
+        "Sneaking",
+        &[
+            "sneak",
+            "sneaks",
+            "sneaking",
+            "snuck",
+            "hide",
+            "hides",
+            "hiding",
+            "stealth",
+            "stealthily",
+            "creep",
+            "creeps",
+            "creeping",
+            "tiptoe",
+            "tiptoes",
+            "tiptoeing",
+            "faufile",
+            "faufiler",
+            "cache",
+            "cacher",
+            "discrètement",
+            "furtivement",
+        ],
+    ),
+    (
+        "Locksmith",
+        &[
+            "lockpick",
+            "lockpicks",
+            "pick the lock",
+            "crochète",
+            "crocheter",
+            "serrure",
+        ],
+    ),
+    (
+        "Palming",
+        &[
+            "pickpocket",
+            "pickpockets",
+            "palm",
+            "palms",
+            "steal",
+            "steals",
+            "stealing",
+            "subtilise",
+            "subtiliser",
+            "vole",
+            "dérobe",
+            "dérober",
+        ],
+    ),
+    (
+        "Gymnastics",
+        &[
+            "climb",
+            "climbs",
+            "climbing",
+            "jump",
+            "jumps",
+            "jumping",
+            "leap",
+            "leaps",
+            "leaping",
+            "dodge",
+            "dodges",
+            "dodging",
+            "balance",
+            "grimpe",
+            "grimper",
+            "saute",
+            "sauter",
+            "escalade",
+            "escalader",
+            "esquive",
+            "esquiver",
+        ],
+    ),
+    (
+        "Running",
+        &[
+            "sprint",
+            "sprints",
+            "sprinting",
+            "run away",
+            "chase",
+            "chases",
+            "chasing",
+            "flee",
+            "flees",
+            "fleeing",
+            "courir",
+            "je cours",
+            "fuis",
+            "fuir",
+            "poursuis",
+            "poursuivre",
+        ],
+    ),
+    (
+        "Swimming",
+        &[
+            "swim", "swims", "swimming", "dive", "dives", "diving", "nage", "nager", "plonge",
+            "plonger",
+        ],
+    ),
+    (
+        "Pilot Ground Craft",
+        &[
+            "drive",
+            "drives",
+            "driving",
+            "car chase",
+            "conduis",
+            "conduire",
+            "pilote",
+            "piloter",
+        ],
+    ),
+    (
+        "Hacking",
+        &["hack", "hacks", "hacking", "pirate", "pirater"],
+    ),
+    (
+        "Electronic Warfare",
+        &[
+            "jam",
+            "jams",
+            "jamming",
+            "brouille",
+            "brouiller",
+            "intercept",
+            "intercepts",
+            "intercepting",
+            "intercepte",
+            "intercepter",
+        ],
+    ),
+    (
+        "Computer",
+        &[
+            "search the matrix",
+            "look up",
+            "database",
+            "databases",
+            "recherche",
+            "rechercher",
+            "base de données",
+        ],
+    ),
+    (
+        "First Aid",
+        &[
+            "bandage",
+            "bandages",
+            "first aid",
+            "patch up",
+            "heal",
+            "heals",
+            "healing",
+            "soigne",
+            "soigner",
+            "premiers secours",
+        ],
+    ),
+    (
+        "Demolitions",
+        &[
+            "explosive",
+            "explosives",
+            "bomb",
+            "bombs",
+            "detonate",
+            "detonates",
+            "detonating",
+            "explosif",
+            "explosifs",
+            "bombe",
+            "bombes",
+        ],
+    ),
+    (
+        "Hardware",
+        &[
+            "repair",
+            "repairs",
+            "repairing",
+            "rewire",
+            "rewires",
+            "rewiring",
+            "répare",
+            "réparer",
+            "bricole",
+            "bricoler",
+        ],
+    ),
+    (
+        "Perception",
+        &[
+            "search",
+            "searches",
+            "searching",
+            "look around",
+            "listen",
+            "listens",
+            "listening",
+            "notice",
+            "notices",
+            "examine",
+            "examines",
+            "examining",
+            "inspect",
+            "inspects",
+            "inspecting",
+            "observe",
+            "observes",
+            "observing",
+            "fouille",
+            "fouiller",
+            "écoute",
+            "écouter",
+            "regarde",
+            "regarder",
+        ],
+    ),
+    (
+        "Tracking",
+        &[
+            "track",
+            "tracks",
+            "tracking",
+            "follow the trail",
+            "piste",
+            "pister",
+            "traque",
+            "traquer",
+        ],
+    ),
+    (
+        "Negotiation",
+        &[
+            "negotiate",
+            "negotiates",
+            "negotiating",
+            "haggle",
+            "haggles",
+            "haggling",
+            "bargain",
+            "bargains",
+            "bargaining",
+            "négocie",
+            "négocier",
+            "marchande",
+            "marchander",
+        ],
+    ),
+    (
+        "Con",
+        &[
+            "lie",
+            "lies",
+            "lying",
+            "bluff",
+            "bluffs",
+            "bluffing",
+            "trick",
+            "tricks",
+            "tricking",
+            "convince",
+            "convinces",
+            "convincing",
+            "persuade",
+            "persuades",
+            "persuading",
+            "disguise",
+            "disguises",
+            "pretend",
+            "pretends",
+            "pretending",
+            "mens",
+            "mentir",
+            "convaincs",
+            "convaincre",
+            "persuader",
+            "déguise",
+            "déguiser",
+        ],
+    ),
+    (
+        "Intimidation",
+        &[
+            "intimidate",
+            "intimidates",
+            "intimidating",
+            "threaten",
+            "threatens",
+            "threatening",
+            "menace",
+            "menacer",
+            "intimide",
+            "intimider",
+        ],
+    ),
+    (
+        "Etiquette",
+        &["blend in", "fit in", "etiquette", "politesse"],
+    ),
+    (
+        "Leadership",
+        &[
+            "rally", "rallies", "command", "commands", "rallie", "rallier",
+        ],
+    ),
+    (
+        "Assensing",
+        &["assense", "assensing", "astral sight", "vision astrale"],
+    ),
+];
+
+#[derive(Deserialize)]
+struct RollDiceArgs {
+    dice_number: u8,
+    #[serde(default)]
+    threshold: u8,
+}
+
+/// Drain lands on the sheet; during a fight the player's combatant takes it too, since the
+/// combatant's condition is copied back to the sheet when the combat ends.
+fn apply_drain(game_state: &mut GameState, output: Value, drain: Drain) -> Value {
+    if let Some(encounter) = &mut game_state.combat {
+        encounter.damage_player(drain.amount, drain.damage_type);
+    }
+    output
+}
+
+impl RuleSystem for Shadowrun {
+    fn name(&self) -> &'static str {
+        "Shadowrun"
+    }
+
+    fn character_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string" },
+                "metatype": { "type": "string", "enum": ["Human", "Elf", "Dwarf", "Ork", "Troll"] },
+                "background": { "type": "string" },
+                "attributes": {
+                    "type": "object",
+                    "description": "Body, Agility, Reaction, Strength, Willpower, Logic, Intuition, Charisma",
+                    "additionalProperties": { "type": "integer" }
+                },
+                "edge": { "type": "integer" },
+                "awakening": { "type": "string", "enum": ["Mundane", "Magician", "Adept"] },
+                "magic": { "type": "integer" },
+                "skills": {
+                    "type": "object",
+                    "description": "Skill ratings; the dice pool is rating + linked attribute",
+                    "additionalProperties": { "type": "integer" }
+                },
+                "gear": { "type": "array", "items": { "type": "object" } },
+                "nuyen": { "type": "integer" },
+                "karma": { "type": "object", "description": "Karma available, earned and its history" },
+                "grimoire": {
+                    "type": "object",
+                    "description": "Magicians only: tradition, known spells with Drain modifiers and spirits owing services"
+                },
+                "essence": { "type": "number", "description": "Starts at 6; reduced by augmentations" },
+                "augmentations": {
+                    "type": "array",
+                    "description": "Installed cyberware and bioware with grade, Essence cost and bonuses",
+                    "items": { "type": "object" }
+                },
+                "condition": {
+                    "type": "object",
+                    "description": "Physical and Stun condition monitors"
+                }
+            }
+        })
+    }
+
+    fn create_character<'a>(&'a self, display: &'a mut Display) -> CharacterCreation<'a> {
+        Box::pin(async move { Ok(create_character(display).await?.map(Character::Shadowrun)) })
+    }
+
+    fn resolve_test(
+        &self,
+        _character: Option<&Character>,
+        args: Value,
+    ) -> Result<Value, SharadError> {
+        let args: RollDiceArgs = serde_json::from_value(args)?;
+        Ok(json!(shadowrun_dice_roll(args.dice_number, args.threshold)))
+    }
+
+    /// Skill + linked attribute, with wound modifiers, defaulting and the inherent limit.
+    fn propose_test(&self, game_state: &GameState, action: &str) -> Option<ProposedTest> {
+        let sheet = game_state
+            .character
+            .as_ref()
+            .and_then(Character::as_shadowrun)?;
+        let skill = classify(action, INTENT_KEYWORDS)?;
+        let attribute = linked_attribute(skill)?;
+        let rating = sheet.skill(skill);
+        // Assensing needs astral perception and cannot be defaulted on
+        if rating == 0 && skill == "Assensing" {
+            return None;
+        }
+
+        let wound_modifier = combat::wound_modifier(sheet, game_state.combat.as_ref());
+        let defaulting = if rating == 0 { -1 } else { 0 };
+        let pool = (sheet.dice_pool(skill) as i32 + wound_modifier + defaulting).max(0) as u8;
+        let limit = skill_limit(skill).map(|limit| (limit, sheet.limit(limit)));
+        let roll = shadowrun_dice_roll(pool, 0);
+        let hits = match limit {
+            Some((_, limit)) => roll.successes().min(limit),
+            None => roll.successes(),
+        };
+
+        let mut summary = format!("{} + {}: {} dice", skill, attribute, pool);
+        if let Some((kind, limit)) = limit {
+            summary.push_str(&format!(", {} limit {}", kind, limit));
+        }
+        summary.push_str(&format!(
+            " -> {} {}",
+            hits,
+            if hits == 1 { "hit" } else { "hits" }
+        ));
+        if roll.glitch() {
+            summary.push_str(", glitch");
+        }
+
+        // The threshold is the Game Master's call, so the roll's own verdict is meaningless
+        let mut roll = json!(roll);
+        if let Some(roll) = roll.as_object_mut() {
+            roll.remove("is_successful");
+        }
+        let mut result = json!({
+            "skill": skill,
+            "attribute": attribute,
+            "dice_pool": pool,
+            "wound_modifier": wound_modifier,
+            "defaulting": rating == 0,
+            "hits": hits,
+            "roll": roll,
+        });
+        if let Some((kind, limit)) = limit {
+            result["limit"] = json!(limit);
+            result["limit_type"] = json!(kind.to_string());
+        }
+        Some(ProposedTest { summary, result })
+    }
+
+    /// Negotiation + Charisma opposed by the item's Availability.
+    fn availability_test(&self, character: Option<&Character>, availability: u8) -> Value {
+        let pool = character
+            .and_then(Character::as_shadowrun)
+            .map_or(0, |sheet| sheet.dice_pool("Negotiation"));
+        let roll = shadowrun_dice_roll(pool, 0);
+        let seller = shadowrun_dice_roll(availability, 0);
+        json!({
+            "hits": roll.successes(),
+            "availability_hits": seller.successes(),
+            "glitch": roll.glitch(),
+            "is_successful": roll.successes() >= seller.successes(),
+        })
+    }
+
+    fn currency(&self) -> &'static str {
+        "nuyen"
+    }
+
+    fn tool_definitions(&self) -> Vec<FunctionObject> {
+        let mut definitions = vec![FunctionObject {
+            name: "roll_dice".to_string(),
+            description: Some("Roll a Shadowrun dice pool against a threshold".to_string()),
+            parameters: Some(json!({
+                "type": "object",
+                "properties": {
+                    "dice_number": {
+                        "type": "integer",
+                        "description": "The number of dice in the pool"
+                    },
+                    "threshold": {
+                        "type": "integer",
+                        "description": "The number of hits needed to succeed"
+                    }
+                },
+                "required": ["dice_number", "threshold"]
+            })),
+        }];
+        definitions.extend(combat::tool_definitions());
+        definitions.extend(matrix::tool_definitions());
+        definitions.extend(magic::tool_definitions());
+        definitions.extend(karma::tool_definitions());
+        definitions.extend(augmentation::tool_definitions());
+        definitions
+    }
+
+    fn handle_tool_call(
+        &self,
+        name: &str,
+        args: Value,
+        game_state: &mut GameState,
+    ) -> Option<Result<Value, SharadError>> {
+        let result = match name {
+            "roll_dice" => self.resolve_test(game_state.character.as_ref(), args),
+            "start_combat" => combat::start_combat(
+                &mut game_state.combat,
+                game_state
+                    .character
+                    .as_ref()
+                    .and_then(Character::as_shadowrun),
+                args,
+            ),
+            "apply_damage" => combat::apply_damage(&mut game_state.combat, args),
+            "next_pass" => combat::next_pass(&mut game_state.combat),
+            "end_combat" => combat::end_combat(
+                &mut game_state.combat,
+                game_state
+                    .character
+                    .as_mut()
+                    .and_then(Character::as_shadowrun_mut),
+            ),
+            "jack_in" => matrix::jack_in(&mut game_state.matrix, args),
+            "matrix_action" => matrix::matrix_action(&mut game_state.matrix, args),
+            "launch_ice" => matrix::launch_ice(&mut game_state.matrix, args),
+            "jack_out" => matrix::jack_out(&mut game_state.matrix),
+            "learn_spell" => magic::learn_spell(
+                game_state
+                    .character
+                    .as_mut()
+                    .and_then(Character::as_shadowrun_mut),
+                args,
+            ),
+            "cast_spell" => magic::cast_spell(
+                game_state
+                    .character
+                    .as_mut()
+                    .and_then(Character::as_shadowrun_mut),
+                game_state.combat.as_ref(),
+                args,
+            )
+            .map(|(output, drain)| apply_drain(game_state, output, drain)),
+            "summon_spirit" => magic::summon_spirit(
+                game_state
+                    .character
+                    .as_mut()
+                    .and_then(Character::as_shadowrun_mut),
+                game_state.combat.as_ref(),
+                args,
+            )
+            .map(|(output, drain)| apply_drain(game_state, output, drain)),
+            "spirit_service" => magic::spirit_service(
+                game_state
+                    .character
+                    .as_mut()
+                    .and_then(Character::as_shadowrun_mut),
+                args,
+            ),
+            "award_karma" => karma::award_karma(
+                game_state
+                    .character
+                    .as_mut()
+                    .and_then(Character::as_shadowrun_mut),
+                game_state.clock.describe(),
+                args,
+            ),
+            "install_augmentation" => augmentation::install_augmentation(
+                game_state
+                    .character
+                    .as_mut()
+                    .and_then(Character::as_shadowrun_mut),
+                args,
+            ),
+            "remove_augmentation" => augmentation::remove_augmentation(
+                game_state
+                    .character
+                    .as_mut()
+                    .and_then(Character::as_shadowrun_mut),
+                args,
+            ),
+            _ => return None,
+        };
+        Some(result)
+    }
+
+    fn gm_instructions(&self) -> &'static str {
+        "Roll dice pools of skill plus linked attribute with roll_dice. Use the combat tools to track initiative and damage whenever a fight breaks out. Run hacking scenes with jack_in, matrix_action, launch_ice and jack_out, and have alerted hosts launch IC. Resolve magic with cast_spell and summon_spirit, which apply Drain, and track spirit services with spirit_service. Reward finished runs with award_karma. Record cyberware and bioware with install_augmentation and remove_augmentation, and include augmentation dice in pools."
+    }
+
+    fn render_sheet(&self, character: &Character) -> String {
+        match character {
+            Character::Shadowrun(sheet) => sheet.render(),
+            other => serde_json::to_string_pretty(other).unwrap_or_default(),
+        }
+    }
+}
@@ -0,0 +1,452 @@
+use crate::cartridge::{cartridge_dir, Cartridge};
+use crate::display::Display;
+use crate::error::SharadError;
+use crate::game_state::GameState;
+use crate::menu::select_from_list;
+use crate::paths::asset_file;
+use async_openai::types::FunctionObject;
+use crossterm::style::Color;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fmt;
+use std::fs;
+
+/// Catalog file inside a cartridge's directory.
+const CATALOG_FILE: &str = "shop.json";
+/// Most recent transactions sent to the Game Master with each action.
+const CONTEXT_TRANSACTIONS: usize = 5;
+const MAX_QUANTITY: u32 = 20;
+/// Time before the player can look again for an item no seller had.
+const SEARCH_RETRY_MINUTES: u64 = 60 * 24;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Legality {
+    #[default]
+    Legal,
+    Restricted,
+    Forbidden,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ShopItem {
+    pub name: String,
+    #[serde(default)]
+    pub category: String,
+    pub price: u32,
+    /// Rating of the availability test; 0 means the item is always in stock.
+    #[serde(default)]
+    pub availability: u8,
+    #[serde(default)]
+    pub legality: Legality,
+    #[serde(default)]
+    pub description: String,
+}
+
+impl ShopItem {
+    /// Availability as written in the books, e.g. `6R` or `12F`.
+    pub fn availability_code(&self) -> String {
+        let suffix = match self.legality {
+            Legality::Legal => "",
+            Legality::Restricted => "R",
+            Legality::Forbidden => "F",
+        };
+        format!("{}{}", self.availability, suffix)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Catalog {
+    pub items: Vec<ShopItem>,
+}
+
+impl Catalog {
+    /// Reads the cartridge's catalog. Cartridges without one have nothing for sale.
+    pub fn load(cartridge: &Cartridge) -> Result<Catalog, SharadError> {
+        let path = asset_file(&cartridge_dir(&cartridge.name).join(CATALOG_FILE));
+        if !path.exists() {
+            return Ok(Catalog::default());
+        }
+        let data = fs::read_to_string(&path)?;
+        serde_json::from_str(&data).map_err(|e| {
+            SharadError::Message(format!("Invalid shop catalog {}: {}", path.display(), e))
+        })
+    }
+
+    pub fn find(&self, name: &str) -> Option<&ShopItem> {
+        self.items
+            .iter()
+            .find(|item| item.name.eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LedgerEntry {
+    /// Negative for money spent.
+    pub amount: i64,
+    pub description: String,
+    /// In-game date of the transaction.
+    #[serde(default)]
+    pub date: Option<String>,
+}
+
+impl fmt::Display for LedgerEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.date {
+            Some(date) => write!(f, "{:+} {} ({})", self.amount, self.description, date),
+            None => write!(f, "{:+} {}", self.amount, self.description),
+        }
+    }
+}
+
+/// An item the player looked for without finding a seller.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FailedSearch {
+    pub item: String,
+    /// Minutes since the start of the campaign after which the item can be looked for again.
+    pub retry_at: u64,
+}
+
+/// Every purchase the player made, so money spent does not vanish from the story.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Ledger {
+    #[serde(default)]
+    pub entries: Vec<LedgerEntry>,
+    /// Searches that failed recently, so the availability test cannot just be rolled again.
+    #[serde(default)]
+    pub failed_searches: Vec<FailedSearch>,
+}
+
+impl Ledger {
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn to_context(&self) -> Value {
+        json!(self
+            .entries
+            .iter()
+            .rev()
+            .take(CONTEXT_TRANSACTIONS)
+            .map(LedgerEntry::to_string)
+            .collect::<Vec<_>>())
+    }
+}
+
+/// Tries to buy an item: rolls availability, then pays and adds it to the character's gear.
+/// A failed availability test costs nothing, but the item cannot be looked for again until a
+/// day has passed in the game.
+pub fn purchase(
+    game_state: &mut GameState,
+    catalog: &Catalog,
+    name: &str,
+    quantity: u32,
+) -> Result<Value, SharadError> {
+    let item = catalog.find(name).ok_or_else(|| {
+        SharadError::Message(format!("'{}' is not in this cartridge's catalog.", name))
+    })?;
+    if quantity == 0 || quantity > MAX_QUANTITY {
+        return Err(SharadError::Message(format!(
+            "Quantity must be between 1 and {}.",
+            MAX_QUANTITY
+        )));
+    }
+    let now = game_state.clock.elapsed_minutes;
+    game_state
+        .ledger
+        .failed_searches
+        .retain(|search| search.retry_at > now);
+    if game_state
+        .ledger
+        .failed_searches
+        .iter()
+        .any(|search| search.item == item.name)
+    {
+        return Err(SharadError::Message(format!(
+            "No seller for {} turned up yet. Let a day pass with advance_time before looking again.",
+            item.name
+        )));
+    }
+
+    let rules = game_state.rules();
+    let character = game_state
+        .character
+        .as_mut()
+        .ok_or_else(|| SharadError::Message("The player has no character sheet.".into()))?;
+    let total = item.price.checked_mul(quantity).ok_or_else(|| {
+        SharadError::Message(format!("{} x{} is too expensive.", item.name, quantity))
+    })?;
+    if total > character.funds() {
+        return Err(SharadError::Message(format!(
+            "{} costs {} {}, but only {} is left.",
+            item.name,
+            total,
+            rules.currency(),
+            character.funds()
+        )));
+    }
+
+    let mut output = json!({
+        "item": item.name,
+        "availability": item.availability_code(),
+        "legality": item.legality,
+    });
+    if item.availability > 0 {
+        let roll = rules.availability_test(Some(character), item.availability);
+        let found = roll["is_successful"].as_bool().unwrap_or(false);
+        output["availability_test"] = roll;
+        if !found {
+            game_state.ledger.failed_searches.push(FailedSearch {
+                item: item.name.clone(),
+                retry_at: now.saturating_add(SEARCH_RETRY_MINUTES),
+            });
+            output["found"] = json!(false);
+            return Ok(output);
+        }
+    }
+
+    character.spend(total)?;
+    for _ in 0..quantity {
+        character.add_gear(&item.name, item.price);
+    }
+    let description = match quantity {
+        1 => format!("Bought {}", item.name),
+        _ => format!("Bought {} x{}", item.name, quantity),
+    };
+    game_state.ledger.entries.push(LedgerEntry {
+        amount: -(total as i64),
+        description,
+        date: Some(game_state.clock.describe()),
+    });
+    output["found"] = json!(true);
+    output["spent"] = json!(total);
+    output["funds_left"] = json!(character.funds());
+    Ok(output)
+}
+
+#[derive(Deserialize)]
+struct BrowseShopArgs {
+    #[serde(default)]
+    category: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct BuyItemArgs {
+    item: String,
+    #[serde(default = "default_quantity")]
+    quantity: u32,
+}
+
+fn default_quantity() -> u32 {
+    1
+}
+
+pub fn browse_shop(cartridge: &Cartridge, args: Value) -> Result<Value, SharadError> {
+    let args: BrowseShopArgs = serde_json::from_value(args)?;
+    let catalog = Catalog::load(cartridge)?;
+    let items: Vec<Value> = catalog
+        .items
+        .iter()
+        .filter(|item| {
+            args.category
+                .as_deref()
+                .is_none_or(|category| item.category.eq_ignore_ascii_case(category.trim()))
+        })
+        .map(|item| {
+            json!({
+                "name": item.name,
+                "category": item.category,
+                "price": item.price,
+                "availability": item.availability_code(),
+            })
+        })
+        .collect();
+    Ok(json!({ "items": items }))
+}
+
+pub fn buy_item(game_state: &mut GameState, args: Value) -> Result<Value, SharadError> {
+    let args: BuyItemArgs = serde_json::from_value(args)?;
+    let catalog = Catalog::load(&game_state.cartridge)?;
+    purchase(game_state, &catalog, &args.item, args.quantity)
+}
+
+pub fn tool_definitions() -> Vec<FunctionObject> {
+    vec![
+        FunctionObject {
+            name: "browse_shop".to_string(),
+            description: Some(
+                "List the items the player can try to buy, with price and Availability".to_string(),
+            ),
+            parameters: Some(json!({
+                "type": "object",
+                "properties": {
+                    "category": { "type": "string" }
+                }
+            })),
+        },
+        FunctionObject {
+            name: "buy_item".to_string(),
+            description: Some(
+                "Buy an item from the catalog for the player when they find a seller. Rolls Availability, deducts the price and adds the item to their gear. An item that was not found can be looked for again after a day"
+                    .to_string(),
+            ),
+            parameters: Some(json!({
+                "type": "object",
+                "properties": {
+                    "item": { "type": "string", "description": "Exact catalog name" },
+                    "quantity": { "type": "integer" }
+                },
+                "required": ["item"]
+            })),
+        },
+    ]
+}
+
+/// Lets the player buy from the catalog between scenes. Returns whether anything was bought.
+pub fn shop_screen(game_state: &mut GameState, display: &mut Display) -> Result<bool, SharadError> {
+    let catalog = Catalog::load(&game_state.cartridge)?;
+    if catalog.items.is_empty() {
+        display.print_wrapped("There is nothing for sale in this game.", Color::Yellow);
+        return Ok(false);
+    }
+    let currency = game_state.rules().currency();
+
+    let mut changed = false;
+    loop {
+        let Some(funds) = game_state.character.as_ref().map(|c| c.funds()) else {
+            display.print_wrapped("Shopping needs a character sheet.", Color::Yellow);
+            return Ok(changed);
+        };
+        let items: Vec<String> = catalog
+            .items
+            .iter()
+            .map(|item| {
+                format!(
+                    "{} - {} {} (Avail {}) - {}",
+                    item.name,
+                    item.price,
+                    currency,
+                    item.availability_code(),
+                    item.category
+                )
+            })
+            .collect();
+        let title = format!("Shop - {} {}", funds, currency);
+        let Some(index) = select_from_list(display, &title, &items)? else {
+            return Ok(changed);
+        };
+
+        let item = &catalog.items[index];
+        if !item.description.is_empty() {
+            display.print_wrapped(&item.description, Color::White);
+        }
+        let prompt = format!(
+            "Look for {} ({} {})? (y/n)",
+            item.name, item.price, currency
+        );
+        match display
+            .get_user_input(&prompt)?
+            .map(|s| s.to_lowercase())
+            .as_deref()
+        {
+            Some("y") | Some("yes") => {}
+            _ => continue,
+        }
+
+        match purchase(game_state, &catalog, &item.name, 1) {
+            Ok(result) if result["found"] == json!(true) => {
+                display.print_wrapped(
+                    &format!("You bought {} for {} {}.", item.name, item.price, currency),
+                    Color::Green,
+                );
+                changed = true;
+            }
+            Ok(_) => display.print_wrapped(
+                &format!(
+                    "Nobody is selling {} right now. Try again tomorrow.",
+                    item.name
+                ),
+                Color::Yellow,
+            ),
+            Err(e) => display.print_wrapped(&e.to_string(), Color::Yellow),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::character::{test_sheet, Awakening};
+    use crate::rules::Character;
+
+    fn item(name: &str, price: u32) -> ShopItem {
+        ShopItem {
+            name: name.to_string(),
+            category: "Weapons".to_string(),
+            price,
+            availability: 0,
+            legality: Legality::Legal,
+            description: String::new(),
+        }
+    }
+
+    fn shop() -> (GameState, Catalog) {
+        let game_state = GameState {
+            character: Some(Character::Shadowrun(test_sheet(Awakening::Mundane, 0))),
+            ..GameState::default()
+        };
+        let catalog = Catalog {
+            items: vec![item("Ares Predator", 725), item("Medkit", 250)],
+        };
+        (game_state, catalog)
+    }
+
+    fn funds(game_state: &GameState) -> u32 {
+        game_state.character.as_ref().unwrap().funds()
+    }
+
+    #[test]
+    fn pays_for_what_the_character_can_afford() {
+        let (mut game_state, catalog) = shop();
+        let output = purchase(&mut game_state, &catalog, "medkit", 2).unwrap();
+        assert_eq!(output["found"], true);
+        assert_eq!(output["spent"], 500);
+        assert_eq!(funds(&game_state), 500);
+        assert_eq!(game_state.ledger.entries.len(), 1);
+        assert_eq!(game_state.ledger.entries[0].amount, -500);
+    }
+
+    #[test]
+    fn refuses_what_the_character_cannot_afford() {
+        let (mut game_state, catalog) = shop();
+        assert!(purchase(&mut game_state, &catalog, "Ares Predator", 2).is_err());
+        assert!(purchase(&mut game_state, &catalog, "Medkit", 0).is_err());
+        assert_eq!(funds(&game_state), 1000);
+        assert!(game_state.ledger.is_empty());
+
+        // Exactly what is left is enough
+        purchase(&mut game_state, &catalog, "Medkit", 4).unwrap();
+        assert_eq!(funds(&game_state), 0);
+    }
+
+    #[test]
+    fn failed_searches_wait_a_day() {
+        let (mut game_state, catalog) = shop();
+        game_state.clock.elapsed_minutes = 600;
+        game_state.ledger.failed_searches.push(FailedSearch {
+            item: "Ares Predator".to_string(),
+            retry_at: 600 + SEARCH_RETRY_MINUTES,
+        });
+        assert!(purchase(&mut game_state, &catalog, "Ares Predator", 1).is_err());
+        // Other items can still be bought
+        purchase(&mut game_state, &catalog, "Medkit", 1).unwrap();
+
+        game_state.clock.elapsed_minutes = 600 + SEARCH_RETRY_MINUTES - 1;
+        assert!(purchase(&mut game_state, &catalog, "Ares Predator", 1).is_err());
+
+        game_state.clock.elapsed_minutes = 600 + SEARCH_RETRY_MINUTES;
+        let output = purchase(&mut game_state, &catalog, "Ares Predator", 1).unwrap();
+        assert_eq!(output["found"], true);
+        assert!(game_state.ledger.failed_searches.is_empty());
+    }
+}
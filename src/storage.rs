@@ -0,0 +1,276 @@
+use crate::error::SharadError;
+use crate::logging::current_log;
+use crate::paths::{
+    audio_dir, export_dir, image_dir, log_dir, recording_dir, save_dir, transcript_dir,
+};
+use crate::save::{list_saves, save_path, Save};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+const MEGABYTE: u64 = 1024 * 1024;
+
+/// Files that pile up as the game is played, each cleaned up by its own policy.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    /// The player's spoken actions, kept only to be transcribed.
+    Recordings,
+    /// Narration read aloud, which replays and bundles use while it is kept.
+    Narration,
+    /// Generated portraits. Those a save shows are never removed.
+    Portraits,
+    Logs,
+    /// Exported stories and campaign bundles.
+    Exports,
+}
+
+impl Category {
+    pub const ALL: [Category; 5] = [
+        Category::Recordings,
+        Category::Narration,
+        Category::Portraits,
+        Category::Logs,
+        Category::Exports,
+    ];
+
+    pub fn dir(self) -> PathBuf {
+        match self {
+            Category::Recordings => recording_dir(),
+            Category::Narration => audio_dir(),
+            Category::Portraits => image_dir(),
+            Category::Logs => log_dir(),
+            Category::Exports => export_dir(),
+        }
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Category::Recordings => "Recordings",
+            Category::Narration => "Narration audio",
+            Category::Portraits => "Portraits",
+            Category::Logs => "Logs",
+            Category::Exports => "Exports",
+        })
+    }
+}
+
+/// How long the files of a category are kept, and how much room they may take.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+pub struct RetentionPolicy {
+    /// Files older than this are removed; `None` keeps them however old.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_age_days: Option<u32>,
+    /// Past this size the oldest files are removed first; `None` sets no limit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size_mb: Option<u64>,
+}
+
+impl RetentionPolicy {
+    fn new(max_age_days: Option<u32>, max_size_mb: Option<u64>) -> Self {
+        RetentionPolicy {
+            max_age_days,
+            max_size_mb,
+        }
+    }
+}
+
+impl fmt::Display for RetentionPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.max_age_days, self.max_size_mb) {
+            (None, None) => write!(f, "kept"),
+            (Some(days), None) => write!(f, "kept {} days", days),
+            (None, Some(size)) => write!(f, "up to {} MB", size),
+            (Some(days), Some(size)) => write!(f, "kept {} days, up to {} MB", days, size),
+        }
+    }
+}
+
+/// Retention policies by category, as kept in the settings.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Retention {
+    pub recordings: RetentionPolicy,
+    pub narration: RetentionPolicy,
+    pub portraits: RetentionPolicy,
+    pub logs: RetentionPolicy,
+    pub exports: RetentionPolicy,
+    /// Also cleans up when the game starts, instead of only on `sharad cleanup`.
+    pub on_start: bool,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Retention {
+            recordings: RetentionPolicy::new(Some(7), None),
+            narration: RetentionPolicy::new(Some(90), Some(500)),
+            portraits: RetentionPolicy::new(Some(180), None),
+            logs: RetentionPolicy::new(Some(30), Some(100)),
+            exports: RetentionPolicy::default(),
+            on_start: false,
+        }
+    }
+}
+
+impl Retention {
+    pub fn policy(&self, category: Category) -> RetentionPolicy {
+        match category {
+            Category::Recordings => self.recordings,
+            Category::Narration => self.narration,
+            Category::Portraits => self.portraits,
+            Category::Logs => self.logs,
+            Category::Exports => self.exports,
+        }
+    }
+}
+
+struct StoredFile {
+    path: PathBuf,
+    size: u64,
+    modified: SystemTime,
+}
+
+/// Files directly in `dir`, oldest first.
+fn stored_files(dir: &Path) -> Result<Vec<StoredFile>, SharadError> {
+    let mut files = Vec::new();
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Ok(files);
+    };
+    for entry in entries {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_file() {
+            files.push(StoredFile {
+                path: entry.path(),
+                size: metadata.len(),
+                modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            });
+        }
+    }
+    files.sort_by_key(|file| file.modified);
+    Ok(files)
+}
+
+/// Space taken by one directory.
+pub struct Usage {
+    pub label: String,
+    pub dir: PathBuf,
+    pub files: usize,
+    pub bytes: u64,
+}
+
+fn usage(label: String, dir: PathBuf) -> Result<Usage, SharadError> {
+    let files = stored_files(&dir)?;
+    Ok(Usage {
+        label,
+        files: files.len(),
+        bytes: files.iter().map(|file| file.size).sum(),
+        dir,
+    })
+}
+
+/// Space taken by saves, transcripts and each category of files that gets cleaned up.
+pub fn disk_usage() -> Result<Vec<Usage>, SharadError> {
+    let mut report = vec![
+        usage("Saves".to_string(), save_dir())?,
+        usage("Transcripts".to_string(), transcript_dir())?,
+    ];
+    for category in Category::ALL {
+        report.push(usage(category.to_string(), category.dir())?);
+    }
+    Ok(report)
+}
+
+/// Portraits the saves show, which no cleanup removes. Fails when a save cannot be read, since
+/// its portraits are then unknown.
+fn save_portraits() -> Result<HashSet<PathBuf>, SharadError> {
+    let mut portraits = HashSet::new();
+    for entry in list_saves()? {
+        // Parsed without migrating, so a cleanup never writes to saves
+        let save = fs::read(save_path(&entry.name))
+            .map_err(SharadError::from)
+            .and_then(|data| Save::parse(&data))
+            .map_err(|e| {
+                SharadError::Message(format!(
+                    "The save '{}' could not be read: {}",
+                    entry.name, e
+                ))
+            })?;
+        portraits.extend(save.game_state.media_files().into_iter().map(PathBuf::from));
+    }
+    Ok(portraits)
+}
+
+/// Files a cleanup removed, or would remove.
+#[derive(Default)]
+pub struct Cleanup {
+    pub removed: Vec<PathBuf>,
+    pub bytes: u64,
+    /// Categories left alone, with why.
+    pub skipped: Vec<String>,
+}
+
+/// Removes the files each policy no longer keeps: those past their age, then the oldest until
+/// the category fits its size. With `dry_run`, only tells what would go.
+pub fn clean_up(retention: &Retention, dry_run: bool) -> Result<Cleanup, SharadError> {
+    let now = SystemTime::now();
+    let mut cleanup = Cleanup::default();
+
+    for category in Category::ALL {
+        let policy = retention.policy(category);
+        let mut protected: HashSet<PathBuf> =
+            current_log().map(Path::to_path_buf).into_iter().collect();
+        if category == Category::Portraits {
+            match save_portraits() {
+                Ok(portraits) => protected.extend(portraits),
+                Err(e) => {
+                    cleanup.skipped.push(format!("{}: {}", category, e));
+                    continue;
+                }
+            }
+        }
+        let files: Vec<StoredFile> = stored_files(&category.dir())?
+            .into_iter()
+            .filter(|file| !protected.contains(&file.path))
+            .collect();
+        let mut size: u64 = files.iter().map(|file| file.size).sum();
+        let max_age = policy.max_age_days.map(|days| DAY * days);
+        let max_size = policy.max_size_mb.map(|size| size * MEGABYTE);
+
+        for file in files {
+            let age = now.duration_since(file.modified).unwrap_or_default();
+            let too_old = max_age.is_some_and(|max_age| age > max_age);
+            let too_big = max_size.is_some_and(|max_size| size > max_size);
+            if !too_old && !too_big {
+                continue;
+            }
+            if !dry_run {
+                fs::remove_file(&file.path)?;
+            }
+            size -= file.size;
+            cleanup.bytes += file.size;
+            cleanup.removed.push(file.path);
+        }
+    }
+    Ok(cleanup)
+}
+
+/// A size in the largest unit that keeps it above one, e.g. `3.2 MB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
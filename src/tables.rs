@@ -0,0 +1,274 @@
+use crate::cartridge::{cartridge_dir, Cartridge};
+use crate::error::SharadError;
+use crate::paths::asset_files;
+use crate::utils::SaveRng;
+use async_openai::types::FunctionObject;
+use rand::Rng;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// References nested deeper than this are left as they are, which also stops reference cycles.
+const MAX_DEPTH: usize = 8;
+const MAX_DRAWS: u8 = 10;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct TableEntry {
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+    /// May reference other tables as `{table name}`; they are rolled in turn.
+    pub text: String,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Table {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub entries: Vec<TableEntry>,
+}
+
+impl Table {
+    fn total_weight(&self) -> u32 {
+        self.entries.iter().map(|entry| entry.weight).sum()
+    }
+
+    fn pick(&self, rng: &mut SaveRng) -> &TableEntry {
+        let mut roll = rng.gen_range(0..self.total_weight());
+        for entry in &self.entries {
+            if roll < entry.weight {
+                return entry;
+            }
+            roll -= entry.weight;
+        }
+        &self.entries[self.entries.len() - 1]
+    }
+}
+
+/// A data file holding any number of tables.
+#[derive(Deserialize)]
+struct TablePack {
+    tables: Vec<Table>,
+}
+
+#[derive(Debug, Default)]
+pub struct TableLibrary {
+    tables: BTreeMap<String, Table>,
+}
+
+impl TableLibrary {
+    pub fn load(cartridge: &Cartridge) -> Result<TableLibrary, SharadError> {
+        let mut library = TableLibrary::default();
+        // Tables shared by every cartridge, which a cartridge's own `tables` directory overrides
+        library.load_files(asset_files(Path::new("tables")))?;
+        library.load_files(asset_files(&cartridge_dir(&cartridge.name).join("tables")))?;
+        Ok(library)
+    }
+
+    fn load_files(&mut self, paths: Vec<PathBuf>) -> Result<(), SharadError> {
+        let packs = paths
+            .into_iter()
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"));
+        for path in packs {
+            let data = fs::read_to_string(&path)?;
+            let pack: TablePack = serde_json::from_str(&data).map_err(|e| {
+                SharadError::Message(format!("Invalid table pack {}: {}", path.display(), e))
+            })?;
+            for table in pack.tables {
+                if table.total_weight() == 0 {
+                    return Err(SharadError::Message(format!(
+                        "Table '{}' in {} has no entries to roll.",
+                        table.name,
+                        path.display()
+                    )));
+                }
+                self.tables.insert(table.name.to_lowercase(), table);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Table> {
+        self.tables.get(&name.trim().to_lowercase())
+    }
+
+    pub fn tables(&self) -> impl Iterator<Item = &Table> {
+        self.tables.values()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    pub fn roll(&self, name: &str, rng: &mut SaveRng) -> Result<String, SharadError> {
+        let table = self.get(name).ok_or_else(|| {
+            SharadError::Message(format!(
+                "No table named '{}'. Available tables: {}",
+                name,
+                self.tables()
+                    .map(|table| table.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+        })?;
+        let text = table.pick(rng).text.clone();
+        Ok(self.expand(&text, rng, 1))
+    }
+
+    /// Replaces `{table name}` references with a roll on that table.
+    fn expand(&self, text: &str, rng: &mut SaveRng, depth: usize) -> String {
+        let mut result = String::new();
+        let mut rest = text;
+        while let Some(start) = rest.find('{') {
+            let Some(length) = rest[start..].find('}') else {
+                break;
+            };
+            let reference = &rest[start + 1..start + length];
+            result.push_str(&rest[..start]);
+            match self.get(reference) {
+                Some(table) if depth < MAX_DEPTH => {
+                    let text = table.pick(rng).text.clone();
+                    result.push_str(&self.expand(&text, rng, depth + 1));
+                }
+                _ => result.push_str(&rest[start..=start + length]),
+            }
+            rest = &rest[start + length + 1..];
+        }
+        result.push_str(rest);
+        result
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Likelihood {
+    VeryUnlikely,
+    Unlikely,
+    #[default]
+    EvenOdds,
+    Likely,
+    VeryLikely,
+}
+
+impl Likelihood {
+    /// Chance out of 100 that the answer is yes.
+    fn chance(&self) -> u32 {
+        match self {
+            Likelihood::VeryUnlikely => 10,
+            Likelihood::Unlikely => 25,
+            Likelihood::EvenOdds => 50,
+            Likelihood::Likely => 75,
+            Likelihood::VeryLikely => 90,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ConsultTableArgs {
+    table: Option<String>,
+    #[serde(default = "default_count")]
+    count: u8,
+}
+
+fn default_count() -> u8 {
+    1
+}
+
+#[derive(Deserialize)]
+struct AskOracleArgs {
+    question: String,
+    #[serde(default)]
+    likelihood: Likelihood,
+}
+
+pub fn consult_table(
+    cartridge: &Cartridge,
+    rng: &mut SaveRng,
+    args: Value,
+) -> Result<Value, SharadError> {
+    let args: ConsultTableArgs = serde_json::from_value(args)?;
+    let library = TableLibrary::load(cartridge)?;
+
+    let Some(name) = args.table else {
+        return Ok(json!({
+            "tables": library
+                .tables()
+                .map(|table| json!({ "name": table.name, "description": table.description }))
+                .collect::<Vec<_>>()
+        }));
+    };
+    let results = (0..args.count.clamp(1, MAX_DRAWS))
+        .map(|_| library.roll(&name, rng))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(json!({ "table": name, "results": results }))
+}
+
+/// Yes/no oracle: a d100 under the likelihood's chance is a yes. Rolls far from the
+/// threshold are emphatic, and doubles add a twist.
+pub fn ask_oracle(rng: &mut SaveRng, args: Value) -> Result<Value, SharadError> {
+    let args: AskOracleArgs = serde_json::from_value(args)?;
+    let chance = args.likelihood.chance();
+    let roll = rng.gen_range(1..=100u32);
+
+    let answer = if roll <= chance / 5 {
+        "Yes, and"
+    } else if roll <= chance {
+        "Yes"
+    } else if roll > 100 - (100 - chance) / 5 {
+        "No, and"
+    } else {
+        "No"
+    };
+    Ok(json!({
+        "question": args.question,
+        "roll": roll,
+        "answer": answer,
+        "twist": roll % 11 == 0,
+    }))
+}
+
+pub fn tool_definitions() -> Vec<FunctionObject> {
+    vec![
+        FunctionObject {
+            name: "consult_table".to_string(),
+            description: Some(
+                "Roll on a random table for names, places, encounters and other details instead of improvising them. Call without a table to list the available tables"
+                    .to_string(),
+            ),
+            parameters: Some(json!({
+                "type": "object",
+                "properties": {
+                    "table": { "type": "string" },
+                    "count": {
+                        "type": "integer",
+                        "description": "Number of results to draw, up to 10"
+                    }
+                }
+            })),
+        },
+        FunctionObject {
+            name: "ask_oracle".to_string(),
+            description: Some(
+                "Ask the oracle a yes/no question about the world when the outcome is uncertain and no dice test applies"
+                    .to_string(),
+            ),
+            parameters: Some(json!({
+                "type": "object",
+                "properties": {
+                    "question": { "type": "string" },
+                    "likelihood": {
+                        "type": "string",
+                        "enum": ["very_unlikely", "unlikely", "even_odds", "likely", "very_likely"]
+                    }
+                },
+                "required": ["question"]
+            })),
+        },
+    ]
+}
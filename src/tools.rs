@@ -0,0 +1,262 @@
+use crate::clock;
+use crate::display::Display;
+use crate::error::SharadError;
+use crate::game_state::GameState;
+use crate::image::{generate_character_image, Appearance, CharacterInfo};
+use crate::journal;
+use crate::npc;
+use crate::reputation;
+use crate::rules::RuleSystem;
+use crate::shop;
+use crate::tables;
+use crate::utils::open_image;
+use async_openai::types::{
+    AssistantTools, AssistantToolsFunction, FunctionObject, RunToolCallObject,
+};
+use crossterm::style::Color;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::spawn;
+
+/// Merges the tools configured on the assistant with the ones the game engine implements.
+pub fn game_master_tools(
+    assistant_tools: Vec<AssistantTools>,
+    rules: &dyn RuleSystem,
+) -> Vec<AssistantTools> {
+    let mut tools = assistant_tools;
+    let definitions = base_tool_definitions()
+        .into_iter()
+        .chain(rules.tool_definitions())
+        .chain(npc::tool_definitions())
+        .chain(reputation::tool_definitions())
+        .chain(journal::tool_definitions())
+        .chain(tables::tool_definitions())
+        .chain(shop::tool_definitions())
+        .chain(clock::tool_definitions());
+
+    for function in definitions {
+        let already_defined = tools.iter().any(|tool| {
+            matches!(tool, AssistantTools::Function(existing) if existing.function.name == function.name)
+        });
+        if !already_defined {
+            tools.push(AssistantTools::Function(AssistantToolsFunction {
+                function,
+            }));
+        }
+    }
+    tools
+}
+
+pub async fn handle_tool_call(
+    tool_call: &RunToolCallObject,
+    game_state: &mut GameState,
+    display: &mut Display,
+) -> Result<String, SharadError> {
+    // Malformed arguments go back to the Game Master like any other error, so it can retry
+    let args: Value = match serde_json::from_str(&tool_call.function.arguments) {
+        Ok(args) => args,
+        Err(e) => {
+            return Ok(json!({ "error": format!("Invalid arguments: {}", e) }).to_string());
+        }
+    };
+
+    let result = match tool_call.function.name.as_str() {
+        "generate_character_image" => {
+            let character_info = character_info_from_args(&args);
+            let character_name = character_info.name.clone();
+            let generated_portraits = Arc::clone(&game_state.generated_portraits);
+            let mut display_clone = display.clone();
+
+            // Image generation is slow, so let it finish in the background
+            spawn(async move {
+                match generate_character_image(character_info).await {
+                    Ok(image_path) => {
+                        display_clone.print_debug(
+                            &format!("Character image generated: {}", image_path),
+                            Color::Magenta,
+                        );
+                        if let Err(e) = open_image(&image_path) {
+                            display_clone
+                                .print_debug(&format!("Failed to open image: {}", e), Color::Red);
+                        }
+                        if let Ok(mut portraits) = generated_portraits.lock() {
+                            portraits.push((character_name, image_path));
+                        }
+                    }
+                    Err(e) => display_clone.print_debug(
+                        &format!("Failed to generate character image: {}", e),
+                        Color::Red,
+                    ),
+                }
+            });
+            return Ok("Tool started".to_string());
+        }
+        "create_npc" => npc::create_npc(&mut game_state.npcs, args),
+        "lookup_npc" => npc::lookup_npc(&game_state.npcs, args),
+        "update_npc" => npc::update_npc(&mut game_state.npcs, args),
+        "adjust_reputation" => reputation::adjust_reputation(
+            &mut game_state.reputation,
+            &mut game_state.npcs,
+            game_state.clock.describe(),
+            args,
+        ),
+        "add_quest" => journal::add_quest(&mut game_state.journal, args),
+        "update_quest" => journal::update_quest(&mut game_state.journal, args),
+        "close_quest" => journal::close_quest(&mut game_state.journal, args),
+        "consult_table" => tables::consult_table(&game_state.cartridge, &mut game_state.rng, args),
+        "ask_oracle" => tables::ask_oracle(&mut game_state.rng, args),
+        "browse_shop" => shop::browse_shop(&game_state.cartridge, args),
+        "buy_item" => shop::buy_item(game_state, args),
+        "advance_time" => {
+            clock::advance_time(&mut game_state.clock, args).map(|(output, fired)| {
+                game_state.apply_events(&fired);
+                output
+            })
+        }
+        "schedule_event" => {
+            clock::schedule_event(&mut game_state.clock, &mut game_state.journal, args)
+        }
+        name => game_state
+            .rules()
+            .handle_tool_call(name, args, game_state)
+            .unwrap_or_else(|| Err(SharadError::Message(format!("Unknown tool '{}'.", name)))),
+    };
+
+    // Errors are reported back to the Game Master so it can correct its call
+    let output = result.unwrap_or_else(|e| json!({ "error": e.to_string() }));
+    Ok(output.to_string())
+}
+
+fn character_info_from_args(args: &Value) -> CharacterInfo {
+    let string_list = |value: &Value| -> Vec<String> {
+        value
+            .as_array()
+            .map(|arr| {
+                arr.iter()
+                    .filter_map(|v| v.as_str().map(String::from))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+
+    CharacterInfo {
+        name: args["name"].as_str().unwrap_or("").to_string(),
+        appearance: Appearance {
+            gender: args["appearance"]["gender"]
+                .as_str()
+                .unwrap_or("")
+                .to_string(),
+            age: args["appearance"]["age"].as_str().unwrap_or("").to_string(),
+            height: args["appearance"]["height"]
+                .as_str()
+                .unwrap_or("")
+                .to_string(),
+            build: args["appearance"]["build"]
+                .as_str()
+                .unwrap_or("")
+                .to_string(),
+            hair: args["appearance"]["hair"]
+                .as_str()
+                .unwrap_or("")
+                .to_string(),
+            eyes: args["appearance"]["eyes"]
+                .as_str()
+                .unwrap_or("")
+                .to_string(),
+            skin: args["appearance"]["skin"]
+                .as_str()
+                .unwrap_or("")
+                .to_string(),
+        },
+        distinctive_signs: string_list(&args["distinctive_signs"]),
+        accessories: string_list(&args["accessories"]),
+        location: args["location"].as_str().unwrap_or("").to_string(),
+        ambiance: args["ambiance"].as_str().unwrap_or("").to_string(),
+        environment: args["environment"].as_str().unwrap_or("").to_string(),
+        image_generation_prompt: args["image_generation_prompt"]
+            .as_str()
+            .unwrap_or("")
+            .to_string(),
+    }
+}
+
+fn base_tool_definitions() -> Vec<FunctionObject> {
+    vec![FunctionObject {
+        name: "generate_character_image".to_string(),
+        description: Some("Generate a character image based on the provided details".to_string()),
+        parameters: Some(json!({
+            "type": "object",
+            "properties": {
+                "name": {
+                    "type": "string",
+                    "description": "The name of the character"
+                },
+                "appearance": {
+                    "type": "object",
+                    "description": "Details about the character's physical appearance in English",
+                    "properties": {
+                        "gender": {
+                            "type": "string",
+                            "description": "The character's gender in English"
+                        },
+                        "age": {
+                            "type": "string",
+                            "description": "The character's approximate age in English"
+                        },
+                        "height": {
+                            "type": "string",
+                            "description": "The character's height in English"
+                        },
+                        "build": {
+                            "type": "string",
+                            "description": "The character's body type in English"
+                        },
+                        "hair": {
+                            "type": "string",
+                            "description": "The character's hair color and style in English"
+                        },
+                        "eyes": {
+                            "type": "string",
+                            "description": "The character's eye color in English"
+                        },
+                        "skin": {
+                            "type": "string",
+                            "description": "The character's skin tone in English"
+                        }
+                    }
+                },
+                "distinctive_signs": {
+                    "type": "array",
+                    "description": "List of distinctive signs or features in English",
+                    "items": {
+                        "type": "string"
+                    }
+                },
+                "accessories": {
+                    "type": "array",
+                    "description": "List of accessories worn by the character in English",
+                    "items": {
+                        "type": "string"
+                    }
+                },
+                "location": {
+                    "type": "string",
+                    "description": "The specific location where the character is situated in English"
+                },
+                "ambiance": {
+                    "type": "string",
+                    "description": "The mood or atmosphere of the scene in English"
+                },
+                "environment": {
+                    "type": "string",
+                    "description": "The surrounding environment or setting in English"
+                },
+                "image_generation_prompt": {
+                    "type": "string",
+                    "description": "A detailed prompt for generating the character image on Dall-E following content Policy rules, in English"
+                }
+            },
+            "required": ["name", "appearance", "location", "environment", "image_generation_prompt"],
+        })),
+    }]
+}
@@ -0,0 +1,128 @@
+use crate::error::SharadError;
+use crate::paths::transcript_dir;
+use crate::utils::write_atomic;
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Assistant,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TranscriptEntry {
+    /// RFC 3339 timestamp.
+    pub timestamp: String,
+    pub role: Role,
+    /// Id of the message in the thread.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// The message as sent, usually JSON with `player_action` or `narration` tags.
+    pub content: String,
+    /// Narration read aloud for this message, when audio output was on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio: Option<String>,
+}
+
+impl TranscriptEntry {
+    /// What the player did or the Game Master narrated. `None` for messages that are only
+    /// instructions, like the opening message or revision requests.
+    pub fn text(&self) -> Option<String> {
+        let tag = match self.role {
+            Role::User => "player_action",
+            Role::Assistant => "narration",
+        };
+        match serde_json::from_str::<Value>(&self.content) {
+            Ok(json) => json.get(tag)?.as_str().map(str::to_string),
+            // Replies that are not JSON are plain narration
+            Err(_) if self.role == Role::Assistant => Some(self.content.clone()),
+            Err(_) => None,
+        }
+    }
+}
+
+/// Local copy of a conversation thread, one JSON line per message.
+pub fn transcript_path(thread_id: &str) -> PathBuf {
+    transcript_dir().join(format!("{}.jsonl", thread_id))
+}
+
+pub fn exists(thread_id: &str) -> bool {
+    transcript_path(thread_id).exists()
+}
+
+/// Adds a message to the end of the thread's transcript.
+pub fn append(
+    thread_id: &str,
+    message_id: &str,
+    role: Role,
+    content: &str,
+) -> Result<(), SharadError> {
+    fs::create_dir_all(transcript_dir())?;
+    let entry = TranscriptEntry {
+        timestamp: Local::now().to_rfc3339(),
+        role,
+        id: Some(message_id.to_string()),
+        content: content.to_string(),
+        audio: None,
+    };
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(transcript_path(thread_id))?;
+    writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+    Ok(())
+}
+
+/// Records the audio file read aloud for the Game Master's last message.
+pub fn attach_audio(thread_id: &str, audio: &Path) -> Result<(), SharadError> {
+    let mut entries = read(thread_id)?;
+    if let Some(entry) = entries
+        .iter_mut()
+        .rev()
+        .find(|entry| entry.role == Role::Assistant)
+    {
+        entry.audio = Some(audio.display().to_string());
+        write(thread_id, &entries)?;
+    }
+    Ok(())
+}
+
+/// The entries up to the message a save was written at, dropping turns played after it.
+pub fn until(entries: Vec<TranscriptEntry>, last_message_id: Option<&str>) -> Vec<TranscriptEntry> {
+    let end = last_message_id.and_then(|last_message_id| {
+        entries
+            .iter()
+            .position(|entry| entry.id.as_deref() == Some(last_message_id))
+    });
+    match end {
+        Some(end) => entries.into_iter().take(end + 1).collect(),
+        None => entries,
+    }
+}
+
+/// Replaces the thread's transcript, for threads copied or fetched back from the server.
+pub fn write(thread_id: &str, entries: &[TranscriptEntry]) -> Result<(), SharadError> {
+    fs::create_dir_all(transcript_dir())?;
+    let mut data = String::new();
+    for entry in entries {
+        data.push_str(&serde_json::to_string(entry)?);
+        data.push('\n');
+    }
+    write_atomic(&transcript_path(thread_id), data.as_bytes())?;
+    Ok(())
+}
+
+/// Reads the thread's transcript, skipping lines that were cut off mid-write.
+pub fn read(thread_id: &str) -> Result<Vec<TranscriptEntry>, SharadError> {
+    let data = fs::read_to_string(transcript_path(thread_id))?;
+    Ok(data
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
@@ -5,11 +5,13 @@ use crossterm::{
     execute, terminal,
     terminal::ClearType,
 };
-use rand::{self, Rng};
-use serde::Serialize;
+use rand::{self, Rng, RngCore};
+use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::fmt;
+use std::fs::{self, File};
 use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 use tokio::process::Command;
 
@@ -59,6 +61,17 @@ pub fn correct_input(
                         println!(); // Move to the next line
                         return Ok(Some(input.iter().collect()));
                     }
+                    // Quicksave and quickload keys stand for their commands
+                    KeyCode::F(5) | KeyCode::F(9) => {
+                        terminal::disable_raw_mode()?;
+                        println!();
+                        let command = if code == KeyCode::F(5) {
+                            "/quicksave"
+                        } else {
+                            "/quickload"
+                        };
+                        return Ok(Some(command.to_string()));
+                    }
                     KeyCode::Char('v') if modifiers.contains(KeyModifiers::CONTROL) => {
                         // Handle paste (Ctrl+V)
                         if let Ok(clipboard) = cli_clipboard::get_contents() {
@@ -113,6 +126,16 @@ pub struct RollResult {
     is_successful: bool,
 }
 
+impl RollResult {
+    pub fn successes(&self) -> u8 {
+        self.successes
+    }
+
+    pub fn glitch(&self) -> bool {
+        self.glitch
+    }
+}
+
 impl fmt::Display for RollResult {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -168,6 +191,93 @@ pub fn shadowrun_dice_roll(dice_number: u8, threshold: u8) -> RollResult {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RollMode {
+    #[default]
+    Normal,
+    Advantage,
+    Disadvantage,
+}
+
+#[derive(Debug, Serialize)]
+pub struct D20RollResult {
+    rolls: Vec<i32>,
+    natural: i32,
+    modifier: i32,
+    total: i32,
+    difficulty: i32,
+    critical_success: bool,
+    critical_failure: bool,
+    is_successful: bool,
+}
+
+/// Rolls a d20 (two with advantage or disadvantage) plus a modifier against a difficulty class.
+pub fn d20_roll(modifier: i32, difficulty: i32, mode: RollMode) -> D20RollResult {
+    let mut rng = rand::thread_rng();
+    let rolls: Vec<i32> = match mode {
+        RollMode::Normal => vec![rng.gen_range(1..=20)],
+        _ => vec![rng.gen_range(1..=20), rng.gen_range(1..=20)],
+    };
+    let natural = match mode {
+        RollMode::Disadvantage => rolls.iter().copied().min().unwrap_or(1),
+        _ => rolls.iter().copied().max().unwrap_or(1),
+    };
+    let total = natural + modifier;
+
+    D20RollResult {
+        rolls,
+        natural,
+        modifier,
+        total,
+        difficulty,
+        critical_success: natural == 20,
+        critical_failure: natural == 1,
+        is_successful: total >= difficulty,
+    }
+}
+
+/// Random number generator stored in the save, so draws made from it replay identically
+/// from the same save. SplitMix64, which keeps its whole state in one serializable number.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaveRng {
+    state: u64,
+}
+
+impl Default for SaveRng {
+    fn default() -> Self {
+        SaveRng {
+            state: rand::thread_rng().gen(),
+        }
+    }
+}
+
+impl RngCore for SaveRng {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
 pub fn open_image(path: &str) -> Result<(), std::io::Error> {
     #[cfg(target_os = "windows")]
     {
@@ -184,3 +294,29 @@ pub fn open_image(path: &str) -> Result<(), std::io::Error> {
 
     Ok(())
 }
+
+/// Where `write_with_backup` keeps the version it replaced, e.g. `settings.json.bak`.
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".bak");
+    PathBuf::from(backup)
+}
+
+/// Writes a file so that an interruption leaves either the old or the new version, never a
+/// truncated one: the contents go to a temporary file that then replaces the original.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let mut file = File::create(&temporary)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    fs::rename(&temporary, path)
+}
+
+/// Writes a file like `write_atomic`, keeping the previous version at `backup_path`.
+pub fn write_with_backup(path: &Path, contents: &[u8]) -> io::Result<()> {
+    if path.is_file() {
+        fs::copy(path, backup_path(path))?;
+    }
+    write_atomic(path, contents)
+}